@@ -88,6 +88,9 @@ impl AnimationFSM {
     pub fn next(&mut self) {
         self.state.next();
     }
+    pub fn is_idle(&self) -> bool {
+        self.state == AnimationFSMState::Idle
+    }
     pub fn run(&mut self) {
         match self.state {
             AnimationFSMState::Idle => {}
@@ -0,0 +1,10 @@
+// Headless runner, see `simd_asm_cli --help`
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    std::process::exit(simd_asm_code_visualization::cli::run(&args));
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() {}
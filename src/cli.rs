@@ -0,0 +1,273 @@
+use cpulib::CPU;
+use crate::utilities::{Register, ValueType, create_value_from_bytes, get_default_value_type};
+use crate::instruction_actuator::{get_written_element, parse_register};
+use crate::value_editor::{self, EditLocation};
+use crate::scenario::Scenario;
+use crate::simulator::Simulator;
+use crate::trace::{self, TraceEntry, TraceTarget};
+use crate::expectation::Outcome;
+
+// Headless runs for scripts and CI, the `simd_asm_cli` binary
+
+const USAGE: &str = "\
+Usage: simd_asm_cli --asm FILE [options]
+
+Runs the program without the GUI and prints the final state.
+
+Options:
+  --asm FILE            assembly source, one instruction per line
+  --state FILE          initial state, one entry per line:
+                          ymm0 f64 iota 1 1
+                          rdi u64 const 0x40000000
+                          mem 0x40000000 f32 16 random 7 -1 1
+                        generators: iota <start> <step>, const <x>, random <seed> <min> <max>, file <path>
+  --type REG=TYPE       element type used to print REG, e.g. ymm0=f64 (repeatable)
+  --mem ADDR:TYPE:COUNT also print COUNT elements of TYPE at ADDR (repeatable)
+  --max-steps N         stop with an error after N lines (default 10000)
+  --format FORMAT       text (default), json, trace-json or trace-csv
+  --help                show this message
+
+Registers and memory from the state file are printed, together with everything the program wrote.
+`; expect` comments in the source are checked after their line, failures are printed to stderr.
+Exit code: 0 when the program finished and every expectation held, 1 on an execution error
+or a failed expectation, 2 on invalid arguments or files.";
+
+#[derive(Copy, Clone, PartialEq)]
+enum Format {
+    Text,
+    Json,
+    TraceJson,
+    TraceCsv,
+}
+
+struct Options {
+    asm: String,
+    state: Option<String>,
+    types: Vec<(Register, ValueType)>,
+    memory: Vec<(usize, ValueType, usize)>,
+    max_steps: usize,
+    format: Format,
+}
+
+// What gets printed: a register or `count` elements at an address, with their element type
+enum Output {
+    Register(Register, ValueType),
+    Memory(usize, ValueType, usize),
+}
+
+fn parse_address(text: &str) -> Option<usize> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(&hex.replace('_', ""), 16).ok(),
+        None => text.parse::<usize>().ok(),
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Option<Options>, String> {
+    // Ok(None): help was requested
+    let mut options = Options { asm: "".into(), state: None, types: vec![], memory: vec![], max_steps: 10000, format: Format::Text };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--help" || arg == "-h" {
+            return Ok(None);
+        }
+        let value = args.next().ok_or(format!("Missing value for {}", arg))?;
+        match arg.as_str() {
+            "--asm" => options.asm = value.clone(),
+            "--state" => options.state = Some(value.clone()),
+            "--type" => {
+                let (name, value_type) = value.split_once('=').ok_or(format!("Expected REG=TYPE: {}", value))?;
+                let reg = parse_register(name).ok_or(format!("Invalid register: {}", name))?;
+                let value_type = ValueType::from_name(value_type).ok_or(format!("Invalid type: {}", value_type))?;
+                options.types.push((reg, value_type));
+            }
+            "--mem" => {
+                let parts: Vec<&str> = value.split(':').collect();
+                if parts.len() != 3 {
+                    return Err(format!("Expected ADDR:TYPE:COUNT: {}", value));
+                }
+                let address = parse_address(parts[0]).ok_or(format!("Invalid address: {}", parts[0]))?;
+                let value_type = ValueType::from_name(parts[1]).ok_or(format!("Invalid type: {}", parts[1]))?;
+                let count = parts[2].parse::<usize>().map_err(|_| format!("Invalid count: {}", parts[2]))?;
+                options.memory.push((address, value_type, count));
+            }
+            "--max-steps" => options.max_steps = value.parse::<usize>().map_err(|_| format!("Invalid step count: {}", value))?,
+            "--format" => {
+                options.format = match value.to_lowercase().as_str() {
+                    "text" => Format::Text,
+                    "json" => Format::Json,
+                    "trace-json" => Format::TraceJson,
+                    "trace-csv" => Format::TraceCsv,
+                    _ => return Err(format!("Invalid format: {}", value)),
+                }
+            }
+            _ => return Err(format!("Unknown option: {}", arg)),
+        }
+    }
+    if options.asm.is_empty() {
+        return Err("Missing --asm FILE".into());
+    }
+    Ok(Some(options))
+}
+
+fn read_file(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))
+}
+
+fn get_outputs(options: &Options, scenario: &Scenario, entries: &[TraceEntry]) -> Vec<Output> {
+    // State file entries first, then whatever the program wrote, types from --type win
+    let mut registers: Vec<(Register, ValueType)> = vec![];
+    let mut add_register = |reg: Register, value_type: ValueType| {
+        match registers.iter_mut().find(|(r, _)| *r == reg) {
+            Some(entry) => entry.1 = value_type,
+            None => registers.push((reg, value_type)),
+        }
+    };
+    scenario.registers.iter().for_each(|init| {
+        if let Some(reg) = parse_register(&init.name) {
+            add_register(reg, init.value_type);
+        }
+    });
+    let mut memory: Vec<(usize, ValueType, usize)> = scenario.memory.iter().map(|init| (init.address, init.value_type, init.count)).collect();
+    entries.iter().flat_map(|entry| entry.accesses.iter().map(move |a| (entry, a))).filter(|(_, a)| a.is_written).for_each(|(entry, a)| match a.target {
+        TraceTarget::Register(reg) => {
+            if !registers.iter().any(|(r, _)| *r == reg) {
+                registers.push((reg, get_default_value_type(&reg)));
+            }
+        }
+        TraceTarget::Memory(addr, size) => {
+            let addr = addr as usize;
+            let covered = memory.iter().any(|(start, value_type, count)| addr >= *start && addr + size <= start + count * value_type.size() / 8);
+            if !covered {
+                // Elements of the written type, bytes when they do not fill the access
+                let value_type = get_written_element(&entry.opcode).filter(|t| size % (t.size() / 8) == 0).unwrap_or(ValueType::U8);
+                memory.push((addr, value_type, size / (value_type.size() / 8)));
+            }
+        }
+        _ => {}
+    });
+    options.types.iter().for_each(|(reg, value_type)| {
+        match registers.iter_mut().find(|(r, _)| r == reg) {
+            Some(entry) => entry.1 = *value_type,
+            None => registers.push((*reg, *value_type)),
+        }
+    });
+    memory.extend(options.memory.iter().copied());
+    registers.into_iter().map(|(reg, t)| Output::Register(reg, t))
+        .chain(memory.into_iter().map(|(addr, t, count)| Output::Memory(addr, t, count)))
+        .collect()
+}
+
+fn get_values(cpu: &CPU, output: &Output) -> Vec<String> {
+    let (bytes, value_type) = match output {
+        Output::Register(reg, value_type) => {
+            let location = EditLocation::Register(*reg);
+            (value_editor::read_bytes(cpu, &location, 0, value_editor::get_location_size(&location)), *value_type)
+        }
+        Output::Memory(addr, value_type, count) => (cpu.memory.read_vec::<u8>(*addr, count * value_type.size() / 8), *value_type),
+    };
+    bytes.chunks(value_type.size() / 8).map(|chunk| match create_value_from_bytes(chunk, value_type) {
+        Some(value) => format!("{}", value),
+        // Wider than 128 bits, as one hexadecimal number
+        None => format!("0x{}", chunk.iter().rev().map(|b| format!("{:02X}", b)).collect::<String>()),
+    }).collect()
+}
+
+fn get_name(output: &Output) -> (String, ValueType) {
+    match output {
+        Output::Register(reg, value_type) => (format!("{}", reg).to_lowercase(), *value_type),
+        Output::Memory(addr, value_type, _) => (format!("[0x{:X}]", addr), *value_type),
+    }
+}
+
+fn to_text(cpu: &CPU, mxcsr: u32, outputs: &[Output], outcomes: &[Outcome], steps: usize) -> String {
+    let mut text = format!("steps: {}\n", steps);
+    outputs.iter().for_each(|output| {
+        let (name, value_type) = get_name(output);
+        text.push_str(&format!("{}.{} = [{}]\n", name, value_type.get_name().to_lowercase(), get_values(cpu, output).join(", ")));
+    });
+    text.push_str(&format!("mxcsr = 0x{:04X}\n", mxcsr));
+    if !outcomes.is_empty() {
+        let failed = outcomes.iter().filter(|o| o.result.is_err()).count();
+        text.push_str(&format!("expectations: {} passed, {} failed\n", outcomes.len() - failed, failed));
+    }
+    text
+}
+
+fn to_json(cpu: &CPU, mxcsr: u32, outputs: &[Output], outcomes: &[Outcome], steps: usize) -> String {
+    // Values as strings so NaN, infinities and 128-bit integers survive
+    let entries = |registers: bool| outputs.iter().filter(|o| matches!(o, Output::Register(..)) == registers).map(|output| {
+        let (name, value_type) = get_name(output);
+        let values = get_values(cpu, output).iter().map(|v| format!("\"{}\"", v)).collect::<Vec<String>>().join(", ");
+        format!("    {{\"name\": \"{}\", \"type\": \"{}\", \"values\": [{}]}}", name, value_type.get_name().to_lowercase(), values)
+    }).collect::<Vec<String>>().join(",\n");
+    let expectations = outcomes.iter().map(|outcome| match &outcome.result {
+        Ok(()) => format!("    {{\"line\": {}, \"passed\": true}}", outcome.line),
+        Err(e) => format!("    {{\"line\": {}, \"passed\": false, \"message\": \"{}\"}}", outcome.line, trace::escape_json(e)),
+    }).collect::<Vec<String>>().join(",\n");
+    format!(
+        "{{\n  \"steps\": {},\n  \"registers\": [\n{}\n  ],\n  \"memory\": [\n{}\n  ],\n  \"mxcsr\": \"0x{:04X}\",\n  \"expectations\": [\n{}\n  ]\n}}\n",
+        steps,
+        entries(true),
+        entries(false),
+        mxcsr,
+        expectations,
+    )
+}
+
+pub fn run(args: &[String]) -> i32 {
+    let options = match parse_options(args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return 0;
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    let loaded = read_file(&options.asm).and_then(|code| {
+        let scenario = match &options.state {
+            Some(path) => Scenario::parse(&read_file(path)?).map_err(|e| format!("{}: {}", path, e))?,
+            None => Scenario::default(),
+        };
+        let cpu = scenario.build()?;
+        Ok((code, scenario, cpu))
+    });
+    let (code, scenario, cpu) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+    let mut simulator = Simulator::new(cpu, &code);
+    let result = simulator.run(options.max_steps);
+    // Instructions executed, labels are not counted
+    let cpu = simulator.cpu.lock().unwrap();
+    let state = simulator.state.lock().unwrap();
+    let steps = state.trace.len();
+    let outputs = get_outputs(&options, &scenario, state.trace.get_entries());
+    let mxcsr = state.mxcsr.value;
+    let output = match options.format {
+        Format::Text => to_text(&cpu, mxcsr, &outputs, &simulator.outcomes, steps),
+        Format::Json => to_json(&cpu, mxcsr, &outputs, &simulator.outcomes, steps),
+        Format::TraceJson => trace::to_json(state.trace.get_entries()),
+        Format::TraceCsv => trace::to_csv(state.trace.get_entries()),
+    };
+    print!("{}", output);
+    let failures = simulator.get_failures();
+    failures.iter().for_each(|outcome| {
+        if let Err(e) = &outcome.result {
+            eprintln!("line {}: {}", outcome.line, e);
+        }
+    });
+    match result {
+        Ok(_) if failures.is_empty() => 0,
+        Ok(_) => 1,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use cpulib::{CPU, VecRegName};
+use eframe::egui::{self, Ui, Vec2, Sense, Color32, FontId, TextEdit, DragValue, Grid};
+use crate::utilities::ValueType;
+use crate::expression::evaluate;
+
+pub struct Breakpoint {
+    pub line: Option<usize>, // None: checked after every line
+    pub condition: String,   // empty: always
+    pub enabled: bool,
+}
+
+pub struct Watch {
+    pub expression: String,
+    pub result: String,
+}
+
+pub struct Debugger {
+    pub breakpoints: Vec<Breakpoint>,
+    pub watches: Vec<Watch>,
+    pub last_hit: Option<String>,
+    new_line: usize,
+    new_any_line: bool,
+    new_condition: String,
+    new_watch: String,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self {
+            breakpoints: vec![],
+            watches: vec![],
+            last_hit: None,
+            new_line: 1,
+            new_any_line: false,
+            new_condition: "".into(),
+            new_watch: "".into(),
+        }
+    }
+}
+
+impl Debugger {
+    pub fn has_breakpoint(&self, line: usize) -> bool {
+        self.breakpoints.iter().any(|bp| bp.line == Some(line))
+    }
+    pub fn toggle_breakpoint(&mut self, line: usize) {
+        if self.has_breakpoint(line) {
+            self.breakpoints.retain(|bp| bp.line != Some(line));
+        } else {
+            self.breakpoints.push(Breakpoint { line: Some(line), condition: "".into(), enabled: true });
+        }
+    }
+    pub fn check(&self, line: usize, cpu: &CPU, vrt: &HashMap<(VecRegName, usize), ValueType>) -> Option<String> {
+        // Called before `line` executes, the first hit stops the run
+        self.breakpoints.iter().filter(|bp| bp.enabled && (bp.line.is_none() || bp.line == Some(line))).find_map(|bp| {
+            let location = match bp.line {
+                Some(l) => format!("line {}", l),
+                None => format!("line {} (any line)", line),
+            };
+            if bp.condition.trim().is_empty() {
+                return Some(format!("Breakpoint at {}", location));
+            }
+            match evaluate(&bp.condition, cpu, vrt) {
+                Ok(value) if value != 0.0 => Some(format!("Breakpoint at {}: {}", location, bp.condition)),
+                Ok(_) => None,
+                // A broken condition stops rather than being skipped silently
+                Err(e) => Some(format!("Breakpoint at {}: {} ({})", location, bp.condition, e)),
+            }
+        })
+    }
+    pub fn update_watches(&mut self, cpu: &CPU, vrt: &HashMap<(VecRegName, usize), ValueType>) {
+        self.watches.iter_mut().for_each(|watch| {
+            watch.result = match evaluate(&watch.expression, cpu, vrt) {
+                Ok(value) => format!("{}", value),
+                Err(e) => e,
+            };
+        });
+    }
+    pub fn show_gutter(&mut self, ui: &mut Ui, lines: usize, font_size: f32, current: usize) {
+        // Clickable column next to the editor, one cell per code line
+        let row_height = ui.fonts(|f| f.row_height(&FontId::monospace(font_size)));
+        ui.vertical(|ui| {
+            ui.spacing_mut().item_spacing.y = 0.0;
+            ui.add_space(2.0); // TextEdit margin
+            for line in 1..=lines.max(1) {
+                let (rect, response) = ui.allocate_exact_size(Vec2::new(14.0, row_height), Sense::click());
+                let hovered = response.hovered();
+                let response = response.on_hover_text(format!("Toggle breakpoint on line {}", line));
+                if response.clicked() {
+                    self.toggle_breakpoint(line);
+                }
+                let enabled = self.breakpoints.iter().any(|bp| bp.line == Some(line) && bp.enabled);
+                let conditional = self.breakpoints.iter().any(|bp| bp.line == Some(line) && !bp.condition.trim().is_empty());
+                if self.has_breakpoint(line) {
+                    let color = if enabled { Color32::from_rgb(220, 50, 50) } else { Color32::GRAY };
+                    ui.painter().circle_filled(rect.center(), row_height * 0.3, color);
+                    if conditional {
+                        ui.painter().circle_filled(rect.center(), row_height * 0.12, Color32::WHITE);
+                    }
+                } else if hovered {
+                    ui.painter().circle_stroke(rect.center(), row_height * 0.3, (1.0, Color32::from_rgb(220, 50, 50)));
+                }
+                if line == current {
+                    ui.painter().rect_stroke(rect.shrink(1.0), 2.0, (1.0, Color32::YELLOW));
+                }
+            }
+        });
+    }
+    pub fn show(&mut self, ui: &mut Ui, cpu: &CPU, vrt: &HashMap<(VecRegName, usize), ValueType>) {
+        if let Some(hit) = &self.last_hit {
+            ui.colored_label(Color32::from_rgb(220, 50, 50), hit);
+        }
+        ui.label("Breakpoints:");
+        let mut remove = None;
+        Grid::new("debugger_breakpoints").striped(true).show(ui, |ui| {
+            for (i, bp) in self.breakpoints.iter_mut().enumerate() {
+                ui.checkbox(&mut bp.enabled, "");
+                match bp.line {
+                    Some(line) => ui.label(format!("line {}", line)),
+                    None => ui.label("any line"),
+                };
+                ui.add(TextEdit::singleline(&mut bp.condition).desired_width(160.0).hint_text("condition, e.g. rdi == 96"));
+                if ui.button("Remove").clicked() {
+                    remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = remove {
+            self.breakpoints.remove(i);
+        }
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.new_any_line, "Any line");
+            if !self.new_any_line {
+                ui.label("Line");
+                ui.add(DragValue::new(&mut self.new_line).clamp_range(1..=usize::MAX));
+            }
+            ui.add(TextEdit::singleline(&mut self.new_condition).desired_width(160.0).hint_text("ymm4[2] > 10.0"));
+            if ui.button("Add").clicked() {
+                let line = if self.new_any_line { None } else { Some(self.new_line) };
+                self.breakpoints.push(Breakpoint { line, condition: self.new_condition.trim().into(), enabled: true });
+                self.new_condition.clear();
+            }
+        });
+        ui.separator();
+        ui.label("Watches:");
+        let mut remove = None;
+        Grid::new("debugger_watches").striped(true).show(ui, |ui| {
+            for (i, watch) in self.watches.iter().enumerate() {
+                ui.monospace(&watch.expression);
+                ui.monospace(&watch.result);
+                if ui.button("Remove").clicked() {
+                    remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = remove {
+            self.watches.remove(i);
+        }
+        ui.horizontal(|ui| {
+            let response = ui.add(TextEdit::singleline(&mut self.new_watch).desired_width(200.0).hint_text("ymm4.f64[0] + rdi"));
+            let enter = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if (ui.button("Watch").clicked() || enter) && !self.new_watch.trim().is_empty() {
+                let expression = self.new_watch.trim().to_string();
+                let result = evaluate(&expression, cpu, vrt).map_or_else(|e| e, |value| format!("{}", value));
+                self.watches.push(Watch { expression, result });
+                self.new_watch.clear();
+            }
+        });
+        ui.label("Watches are evaluated after every step. Breakpoints stop a run before their line executes.");
+    }
+}
@@ -0,0 +1,167 @@
+use cpulib::CPU;
+use eframe::egui::{Ui, Vec2, Sense, Color32, FontId, Stroke};
+use crate::utilities::{ValueType, create_value_from_bytes, get_default_value_type};
+use crate::instruction_actuator::{parse_register, split_comment};
+use crate::value_editor::{self, EditLocation};
+
+// Assertions in comments, checked after their line executes:
+//   vpaddd zmm0, zmm0, zmm1 ; expect zmm0.u32 = [1, 3, 6, 10, ...]
+//   ; expect [0x40000080].f64x4 = [1.5, 2.5, 3.5, 4.5]
+// `x<count>` limits the lanes, a trailing `...` compares only the listed ones.
+
+#[derive(Clone)]
+pub struct Outcome {
+    pub line: usize,
+    pub result: Result<(), String>,
+}
+
+struct Expectation {
+    name: String, // as written, for messages
+    location: EditLocation,
+    value_type: ValueType,
+    count: usize,
+    values: Vec<String>,
+}
+
+fn parse_address(text: &str) -> Option<usize> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(&hex.replace('_', ""), 16).ok(),
+        None => text.parse::<usize>().ok(),
+    }
+}
+
+fn parse_expectation(text: &str) -> Result<Expectation, String> {
+    // `text` is what follows `expect`
+    let (target, values) = text.split_once('=').ok_or(format!("Expected <location> = [values]: {}", text))?;
+    let target = target.trim();
+    let values = values.trim();
+    let values = values.strip_prefix('[').and_then(|v| v.strip_suffix(']')).ok_or(format!("Values must be in brackets: {}", values))?;
+    let mut values: Vec<String> = values.split(',').map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).collect();
+    let partial = values.last().is_some_and(|v| v == "...");
+    if partial {
+        values.pop();
+    }
+    // Location, then an optional `.type` or `.typexN`
+    let (location, suffix) = if target.starts_with('[') {
+        let end = target.find(']').ok_or(format!("Missing ]: {}", target))?;
+        (&target[..=end], target[end + 1..].strip_prefix('.'))
+    } else {
+        match target.split_once('.') {
+            Some((location, suffix)) => (location, Some(suffix)),
+            None => (target, None),
+        }
+    };
+    let (value_type, count) = match suffix {
+        Some(suffix) => {
+            let (name, count) = match suffix.split_once(['x', 'X']) {
+                Some((name, count)) => (name, Some(count.parse::<usize>().map_err(|_| format!("Invalid lane count: {}", count))?)),
+                None => (suffix, None),
+            };
+            (Some(ValueType::from_name(name).ok_or(format!("Invalid type: {}", name))?), count)
+        }
+        None => (None, None),
+    };
+    let (location, value_type, size) = if location.starts_with('[') {
+        let address = parse_address(location[1..location.len() - 1].trim()).ok_or(format!("Invalid address: {}", location))?;
+        (EditLocation::Memory(address), value_type.unwrap_or(ValueType::U32), None)
+    } else {
+        let reg = parse_register(location).ok_or(format!("Invalid register: {}", location))?;
+        let location = EditLocation::Register(reg);
+        let size = value_editor::get_location_size(&location);
+        (location, value_type.unwrap_or(get_default_value_type(&reg)), Some(size))
+    };
+    let lanes = size.map(|size| size * 8 / value_type.size());
+    let count = match (count, lanes) {
+        (Some(count), _) => count,
+        (None, Some(lanes)) if !partial => lanes,
+        _ => values.len(),
+    };
+    if let Some(lanes) = lanes.filter(|lanes| count > *lanes) {
+        return Err(format!("{} has only {} lanes of {}", target, lanes, value_type.get_name().to_lowercase()));
+    }
+    if values.len() > count || (!partial && values.len() != count) {
+        return Err(format!("{}: {} values for {} lanes", target, values.len(), count));
+    }
+    Ok(Expectation { name: target.to_string(), location, value_type, count, values })
+}
+
+fn is_nan(bytes: &[u8], value_type: ValueType) -> bool {
+    matches!(value_type, ValueType::F16 | ValueType::BF16 | ValueType::F32 | ValueType::F64)
+        && create_value_from_bytes(bytes, value_type).is_some_and(|value| value.to_f64().is_nan())
+}
+
+fn format_lane(bytes: &[u8], value_type: ValueType) -> String {
+    match create_value_from_bytes(bytes, value_type) {
+        Some(value) => format!("{}", value),
+        None => format!("0x{}", bytes.iter().rev().map(|b| format!("{:02X}", b)).collect::<String>()),
+    }
+}
+
+impl Expectation {
+    fn check(&self, cpu: &CPU) -> Result<(), String> {
+        let lane_size = self.value_type.size() / 8;
+        let actual = value_editor::read_bytes(cpu, &self.location, 0, self.count * lane_size);
+        let mut mismatches = vec![];
+        for (i, text) in self.values.iter().enumerate() {
+            let lane = &actual[i * lane_size..(i + 1) * lane_size];
+            let expected = value_editor::parse_value_bytes(text, self.value_type)
+                .ok_or(format!("{}: invalid {} value {}", self.name, self.value_type.get_name().to_lowercase(), text))?;
+            // Any NaN matches any NaN
+            if expected != lane && !(is_nan(&expected, self.value_type) && is_nan(lane, self.value_type)) {
+                mismatches.push(i);
+            }
+        }
+        if mismatches.is_empty() {
+            return Ok(());
+        }
+        let actual: Vec<String> = actual.chunks(lane_size).take(self.values.len()).map(|lane| format_lane(lane, self.value_type)).collect();
+        Err(format!(
+            "{}: expected [{}], actual [{}], lanes {:?} differ",
+            self.name,
+            self.values.join(", "),
+            actual.join(", "),
+            mismatches,
+        ))
+    }
+}
+
+pub fn check_line(line: &str, cpu: &CPU) -> Vec<Result<(), String>> {
+    // One result per `expect` in the comment, several can be separated by `;`
+    let comment = match split_comment(line).1 {
+        Some(comment) => comment,
+        None => return vec![],
+    };
+    comment.split(';').filter_map(|part| {
+        let part = part.trim();
+        let rest = part.strip_prefix("expect ")?;
+        Some(parse_expectation(rest).and_then(|expectation| expectation.check(cpu)))
+    }).collect()
+}
+
+pub fn show_gutter(ui: &mut Ui, lines: usize, font_size: f32, outcomes: &[Outcome]) {
+    // Column next to the breakpoint gutter: a green dot for passing lines, a red cross for failing ones
+    let row_height = ui.fonts(|f| f.row_height(&FontId::monospace(font_size)));
+    ui.vertical(|ui| {
+        ui.spacing_mut().item_spacing.y = 0.0;
+        ui.add_space(2.0); // TextEdit margin
+        for line in 1..=lines.max(1) {
+            let (rect, response) = ui.allocate_exact_size(Vec2::new(14.0, row_height), Sense::hover());
+            let results: Vec<&Result<(), String>> = outcomes.iter().filter(|o| o.line == line).map(|o| &o.result).collect();
+            if results.is_empty() {
+                continue;
+            }
+            let mut failures: Vec<&String> = results.iter().filter_map(|r| r.as_ref().err()).collect();
+            failures.dedup();
+            if failures.is_empty() {
+                ui.painter().circle_filled(rect.center(), row_height * 0.2, Color32::from_rgb(60, 180, 75));
+                response.on_hover_text(format!("Line {}: expectations passed", line));
+            } else {
+                let r = row_height * 0.25;
+                let stroke = Stroke::new(2.0, Color32::from_rgb(220, 50, 50));
+                ui.painter().line_segment([rect.center() + Vec2::new(-r, -r), rect.center() + Vec2::new(r, r)], stroke);
+                ui.painter().line_segment([rect.center() + Vec2::new(-r, r), rect.center() + Vec2::new(r, -r)], stroke);
+                response.on_hover_text(failures.iter().map(|f| f.as_str()).collect::<Vec<&str>>().join("\n"));
+            }
+        }
+    });
+}
@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+use cpulib::{CPU, VecRegName};
+use crate::utilities::{RegType, ValueType, create_value_from_bytes, create_value_with_gpr};
+use crate::instruction_actuator::parse_register;
+
+// Small evaluator for breakpoint conditions and watches, e.g.
+//   rdi == 96
+//   ymm4[2] > 10.0
+//   ymm4.u32[0] + [0x40000080].f64[1] * 2
+//   rax != 0 && zmm1.i32[15] < 0
+// Values are f64, comparisons and logic give 1 or 0. Vector lanes use the displayed type
+// unless `.type` is given; memory defaults to u64. Memory addresses are evaluated in u64.
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Integer(u64),
+    Number(f64),
+    Ident(String),
+    Op(&'static str),
+}
+
+const OPS: [&str; 19] = [
+    "==", "!=", "<=", ">=", "&&", "||",
+    "<", ">", "+", "-", "*", "/", "%", "!", "(", ")", "[", "]", ".",
+];
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            if c == '0' && i + 1 < chars.len() && (chars[i + 1] == 'x' || chars[i + 1] == 'X') {
+                i += 2;
+                while i < chars.len() && (chars[i].is_ascii_hexdigit() || chars[i] == '_') {
+                    i += 1;
+                }
+                let hex: String = chars[start + 2..i].iter().filter(|c| **c != '_').collect();
+                let value = u64::from_str_radix(&hex, 16).map_err(|_| format!("Invalid number: {}", chars[start..i].iter().collect::<String>()))?;
+                tokens.push(Token::Integer(value));
+            } else {
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.' || chars[i] == '_' || chars[i] == 'e' || chars[i] == 'E') {
+                    // An exponent may have a sign, e.g. 1.5e-3
+                    if (chars[i] == 'e' || chars[i] == 'E') && i + 1 < chars.len() && (chars[i + 1] == '+' || chars[i + 1] == '-') {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().filter(|c| **c != '_').collect();
+                match text.parse::<u64>() {
+                    Ok(value) => tokens.push(Token::Integer(value)),
+                    Err(_) => tokens.push(Token::Number(text.parse::<f64>().map_err(|_| format!("Invalid number: {}", text))?)),
+                }
+            }
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else {
+            let rest: String = chars[i..].iter().take(2).collect();
+            match OPS.iter().find(|op| rest.starts_with(**op)) {
+                Some(op) => {
+                    tokens.push(Token::Op(op));
+                    i += op.len();
+                }
+                None => return Err(format!("Unexpected character: {}", c)),
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    cpu: &'a CPU,
+    vrt: &'a HashMap<(VecRegName, usize), ValueType>,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+    fn eat(&mut self, op: &'static str) -> bool {
+        if self.peek() == Some(&Token::Op(op)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+    fn expect(&mut self, op: &'static str) -> Result<(), String> {
+        if self.eat(op) { Ok(()) } else { Err(format!("Expected '{}'", op)) }
+    }
+    fn binary(&mut self, ops: &[&'static str], next: fn(&mut Self) -> Result<f64, String>, apply: fn(&str, f64, f64) -> f64) -> Result<f64, String> {
+        let mut lhs = next(self)?;
+        while let Some(op) = ops.iter().find(|op| self.peek() == Some(&Token::Op(**op))) {
+            self.pos += 1;
+            let rhs = next(self)?;
+            lhs = apply(op, lhs, rhs);
+        }
+        Ok(lhs)
+    }
+    fn or(&mut self) -> Result<f64, String> {
+        self.binary(&["||"], Self::and, |_, a, b| ((a != 0.0) || (b != 0.0)) as u8 as f64)
+    }
+    fn and(&mut self) -> Result<f64, String> {
+        self.binary(&["&&"], Self::comparison, |_, a, b| ((a != 0.0) && (b != 0.0)) as u8 as f64)
+    }
+    fn comparison(&mut self) -> Result<f64, String> {
+        self.binary(&["==", "!=", "<=", ">=", "<", ">"], Self::sum, |op, a, b| match op {
+            "==" => (a == b) as u8 as f64,
+            "!=" => (a != b) as u8 as f64,
+            "<=" => (a <= b) as u8 as f64,
+            ">=" => (a >= b) as u8 as f64,
+            "<" => (a < b) as u8 as f64,
+            _ => (a > b) as u8 as f64,
+        })
+    }
+    fn sum(&mut self) -> Result<f64, String> {
+        self.binary(&["+", "-"], Self::term, |op, a, b| if op == "+" { a + b } else { a - b })
+    }
+    fn term(&mut self) -> Result<f64, String> {
+        self.binary(&["*", "/", "%"], Self::unary, |op, a, b| match op {
+            "*" => a * b,
+            "/" => a / b,
+            _ => a % b,
+        })
+    }
+    fn unary(&mut self) -> Result<f64, String> {
+        if self.eat("-") {
+            Ok(-self.unary()?)
+        } else if self.eat("!") {
+            Ok((self.unary()? == 0.0) as u8 as f64)
+        } else {
+            self.primary()
+        }
+    }
+    fn value_type(&mut self) -> Result<Option<ValueType>, String> {
+        // Optional `.type` suffix
+        if !self.eat(".") {
+            return Ok(None);
+        }
+        match self.peek().cloned() {
+            Some(Token::Ident(name)) => {
+                self.pos += 1;
+                ValueType::from_name(&name).map(Some).ok_or(format!("Unknown type: {}", name))
+            }
+            _ => Err("Expected a type after '.'".into()),
+        }
+    }
+    fn index(&mut self) -> Result<Option<usize>, String> {
+        // Optional `[lane]` suffix
+        if !self.eat("[") {
+            return Ok(None);
+        }
+        let index = self.or()?;
+        self.expect("]")?;
+        if index < 0.0 || index.fract() != 0.0 {
+            return Err(format!("Invalid index: {}", index));
+        }
+        Ok(Some(index as usize))
+    }
+    fn address(&mut self) -> Result<u64, String> {
+        // The sums, products and registers of a memory address in u64, wrapping around like the CPU
+        let mut lhs = self.address_term()?;
+        while let Some(op) = ["+", "-"].into_iter().find(|op| self.eat(op)) {
+            let rhs = self.address_term()?;
+            lhs = if op == "+" { lhs.wrapping_add(rhs) } else { lhs.wrapping_sub(rhs) };
+        }
+        Ok(lhs)
+    }
+    fn address_term(&mut self) -> Result<u64, String> {
+        let mut lhs = self.address_unary()?;
+        while let Some(op) = ["*", "/", "%"].into_iter().find(|op| self.eat(op)) {
+            let rhs = self.address_unary()?;
+            lhs = match op {
+                "*" => lhs.wrapping_mul(rhs),
+                _ if rhs == 0 => return Err("Division by zero in an address".into()),
+                "/" => lhs / rhs,
+                _ => lhs % rhs,
+            };
+        }
+        Ok(lhs)
+    }
+    fn address_unary(&mut self) -> Result<u64, String> {
+        if self.eat("-") {
+            return Ok(self.address_unary()?.wrapping_neg());
+        }
+        match self.peek().cloned() {
+            Some(Token::Integer(x)) => {
+                self.pos += 1;
+                Ok(x)
+            }
+            Some(Token::Op("(")) => {
+                self.pos += 1;
+                let value = self.address()?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            Some(Token::Ident(name)) if !matches!(self.tokens.get(self.pos + 1), Some(Token::Op(".")) | Some(Token::Op("["))) => {
+                // A whole GPR, other registers and lanes go through f64
+                match parse_register(&name) {
+                    Some(reg) if reg.get_type() == RegType::GPR => {
+                        self.pos += 1;
+                        Ok(self.cpu.registers.get_gpr_value(reg.get_gpr()))
+                    }
+                    _ => self.address_value(),
+                }
+            }
+            _ => self.address_value(),
+        }
+    }
+    fn address_value(&mut self) -> Result<u64, String> {
+        let value = self.primary()?;
+        if value < 0.0 || value.fract() != 0.0 || value >= u64::MAX as f64 {
+            return Err(format!("Invalid address: {}", value));
+        }
+        Ok(value as u64)
+    }
+    fn decode(bytes: &[u8], value_type: ValueType) -> Result<f64, String> {
+        create_value_from_bytes(bytes, value_type)
+            .map(|value| value.to_f64())
+            .ok_or(format!("{} is not supported in expressions", value_type.get_name()))
+    }
+    fn primary(&mut self) -> Result<f64, String> {
+        match self.peek().cloned() {
+            Some(Token::Integer(x)) => {
+                self.pos += 1;
+                Ok(x as f64)
+            }
+            Some(Token::Number(x)) => {
+                self.pos += 1;
+                Ok(x)
+            }
+            Some(Token::Op("(")) => {
+                self.pos += 1;
+                let value = self.or()?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            Some(Token::Op("[")) => {
+                // Memory: [address].type[index]
+                self.pos += 1;
+                let address = self.address()?;
+                self.expect("]")?;
+                let value_type = self.value_type()?.unwrap_or(ValueType::U64);
+                let index = self.index()?.unwrap_or(0);
+                let size = value_type.size() / 8;
+                let bytes = self.cpu.memory.read_vec::<u8>(address.wrapping_add((index * size) as u64) as usize, size);
+                Self::decode(&bytes, value_type)
+            }
+            Some(Token::Ident(name)) => {
+                self.pos += 1;
+                let reg = parse_register(&name).ok_or(format!("Unknown register: {}", name))?;
+                let value_type = self.value_type()?;
+                let index = self.index()?;
+                match reg.get_type() {
+                    RegType::GPR => {
+                        let value = self.cpu.registers.get_gpr_value(reg.get_gpr());
+                        match (value_type, index) {
+                            (None, None) => Ok(create_value_with_gpr(value, &reg.get_gpr()).to_f64()),
+                            (value_type, index) => {
+                                let value_type = value_type.unwrap_or(ValueType::U8);
+                                let size = value_type.size() / 8;
+                                let offset = index.unwrap_or(0) * size;
+                                let bytes = value.to_le_bytes();
+                                if offset + size > cpulib::Utilities::get_gpr_size(&reg.get_gpr()) as usize / 8 {
+                                    return Err(format!("{} has no lane {}", name, index.unwrap_or(0)));
+                                }
+                                Self::decode(&bytes[offset..offset + size], value_type)
+                            }
+                        }
+                    }
+                    _ => {
+                        let (vec_name, vec_index) = reg.get_vector();
+                        let value_type = value_type
+                            .or_else(|| self.vrt.get(&(vec_name, vec_index)).copied())
+                            .unwrap_or(ValueType::U32);
+                        let index = index.ok_or(format!("{} needs a lane, e.g. {}[0]", name, name))?;
+                        let size = value_type.size() / 8;
+                        let bytes = self.cpu.registers.get_by_sections::<u8>(vec_name, vec_index).unwrap();
+                        if (index + 1) * size > bytes.len() {
+                            return Err(format!("{} has no lane {}", name, index));
+                        }
+                        Self::decode(&bytes[index * size..(index + 1) * size], value_type)
+                    }
+                }
+            }
+            Some(token) => Err(format!("Unexpected {:?}", token)),
+            None => Err("Unexpected end of expression".into()),
+        }
+    }
+}
+
+pub fn evaluate(expression: &str, cpu: &CPU, vrt: &HashMap<(VecRegName, usize), ValueType>) -> Result<f64, String> {
+    let mut parser = Parser { tokens: tokenize(expression)?, pos: 0, cpu, vrt };
+    let value = parser.or()?;
+    match parser.peek() {
+        Some(token) => Err(format!("Unexpected {:?}", token)),
+        None => Ok(value),
+    }
+}
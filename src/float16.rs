@@ -0,0 +1,99 @@
+use crate::mxcsr::RoundingMode;
+
+// IEEE 754 binary16 (F16) and bfloat16 (BF16), stored as raw bits
+
+pub fn f16_to_f32(bits: u16) -> f32 {
+    let sign = ((bits & 0x8000) as u32) << 16;
+    let exp = ((bits >> 10) & 0x1F) as u32;
+    let man = (bits & 0x3FF) as u32;
+    if exp == 0 {
+        // Zero / Subnormal: man * 2^-24
+        let v = man as f32 * 2f32.powi(-24);
+        return if sign != 0 { -v } else { v };
+    }
+    if exp == 0x1F {
+        // Inf / NaN
+        return f32::from_bits(sign | 0x7F800000 | (man << 13));
+    }
+    f32::from_bits(sign | ((exp + 127 - 15) << 23) | (man << 13))
+}
+
+pub fn f64_to_f16(value: f64, rounding: RoundingMode) -> u16 {
+    f64_to_f16_with_tail(value, 0.0, rounding)
+}
+
+pub fn f64_to_f16_with_tail(value: f64, tail: f64, rounding: RoundingMode) -> u16 {
+    // Rounds value + tail once, the tail is far below the last bit of value (e.g. a TwoSum error)
+    let bits = value.to_bits();
+    let sign = ((bits >> 48) & 0x8000) as u16;
+    let exp = ((bits >> 52) & 0x7FF) as i32;
+    let man = bits & ((1u64 << 52) - 1);
+    if exp == 0x7FF {
+        // Inf / NaN (NaN is quieted)
+        return sign | 0x7C00 | if man != 0 { 0x200 | (man >> 42) as u16 } else { 0 };
+    }
+    let e = exp - 1023 + 15;
+    if e >= 31 {
+        // Overflow
+        return match (rounding, sign == 0) {
+            (RoundingMode::Nearest, _) | (RoundingMode::Up, true) | (RoundingMode::Down, false) => sign | 0x7C00,
+            _ => sign | 0x7BFF,
+        };
+    }
+    let m = if exp == 0 { man } else { man | (1u64 << 52) };
+    let shift = (if e > 0 { 42 } else { 42 + 1 - e }) as u32;
+    let (half_m, rem, halfway) = if shift >= 64 {
+        (0u64, m, u64::MAX)
+    } else {
+        (m >> shift, m & ((1u64 << shift) - 1), 1u64 << (shift - 1))
+    };
+    // The tail in the direction of the magnitude
+    let tail = if sign == 0 { tail } else { -tail };
+    let is_away = match rounding {
+        RoundingMode::Up => sign == 0,
+        RoundingMode::Down => sign != 0,
+        _ => false,
+    };
+    let round_up = match rounding {
+        RoundingMode::Nearest => rem > halfway || (rem == halfway && (tail > 0.0 || (tail == 0.0 && half_m & 1 == 1))),
+        _ => is_away && (rem != 0 || tail > 0.0),
+    };
+    // Exact bits with a negative tail are just above the result when rounding toward zero
+    let below = rounding != RoundingMode::Nearest && !is_away && rem == 0 && tail < 0.0;
+    let result = if e > 0 {
+        ((e as u64) << 10) | (half_m & 0x3FF)
+    } else {
+        half_m
+    };
+    // A carry out of the mantissa correctly bumps the exponent (up to Inf), a borrow lowers it
+    sign | (result + round_up as u64 - below as u64) as u16
+}
+
+pub fn f32_to_f16(value: f32, rounding: RoundingMode) -> u16 {
+    f64_to_f16(value as f64, rounding)
+}
+
+pub fn bf16_to_f32(bits: u16) -> f32 {
+    f32::from_bits((bits as u32) << 16)
+}
+
+pub fn f32_to_bf16(value: f32) -> u16 {
+    // Round to nearest even, NaN is quieted
+    let bits = value.to_bits();
+    if value.is_nan() {
+        return ((bits >> 16) | 0x40) as u16;
+    }
+    let rounding_bias = 0x7FFF + ((bits >> 16) & 1);
+    ((bits + rounding_bias) >> 16) as u16
+}
+
+pub fn flush_f32(value: f32) -> f32 {
+    // The BF16 instructions treat denormal inputs and results as zero whatever MXCSR says
+    if value.is_subnormal() { 0.0f32.copysign(value) } else { value }
+}
+
+pub fn f32_to_bf16_flushed(value: f32) -> u16 {
+    // VCVTNE2PS2BF16: denormal inputs and results are zero
+    let result = f32_to_bf16(flush_f32(value));
+    if result & 0x7F80 == 0 { result & 0x8000 } else { result }
+}
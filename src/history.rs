@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use cpulib::{CPU, VecRegName, GPRName, FLAGSName};
+use crate::mxcsr::Mxcsr;
+use crate::provenance::Provenance;
+use crate::expectation::Outcome;
+use crate::simulator::MachineState;
+
+// Memory operands are at most a ZMM register wide
+const MEMORY_REGION_SIZE: usize = 64;
+
+const GPRS: [GPRName; 16] = [
+    GPRName::RAX, GPRName::RBX, GPRName::RCX, GPRName::RDX, GPRName::RSI, GPRName::RDI, GPRName::RBP, GPRName::RSP,
+    GPRName::R8, GPRName::R9, GPRName::R10, GPRName::R11, GPRName::R12, GPRName::R13, GPRName::R14, GPRName::R15,
+];
+
+pub struct Snapshot {
+    vector_registers: Vec<Vec<u8>>, // ZMM0..ZMM31
+    gprs: Vec<u64>,
+    rflags: u64,
+    memory: HashMap<usize, Vec<u8>>, // every region touched so far
+    mxcsr: Mxcsr,
+    provenance: Provenance,
+    trace_len: usize,
+    pub highlight: usize,
+    pub finished: bool,
+    pub outcomes: Vec<Outcome>, // expectation results checked so far
+}
+
+impl Snapshot {
+    fn capture(cpu: &CPU, state: &MachineState, regions: &[usize], highlight: usize, finished: bool, outcomes: &[Outcome]) -> Self {
+        Self {
+            vector_registers: (0..32).map(|i| cpu.registers.get_by_sections::<u8>(VecRegName::ZMM, i).unwrap()).collect(),
+            gprs: GPRS.iter().map(|gpr| cpu.registers.get_gpr_value(*gpr)).collect(),
+            rflags: cpu.registers.get_flags_value(FLAGSName::RFLAGS),
+            memory: regions.iter().map(|addr| (*addr, cpu.memory.read_vec::<u8>(*addr, MEMORY_REGION_SIZE))).collect(),
+            mxcsr: state.mxcsr.clone(),
+            provenance: state.provenance.clone(),
+            trace_len: state.trace.len(),
+            highlight,
+            finished,
+            outcomes: outcomes.to_vec(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct History {
+    snapshots: Vec<Snapshot>,
+    position: usize, // index of the restored snapshot, `snapshots.len()` while at the newest state
+    regions: Vec<usize>,
+}
+
+impl History {
+    pub fn record(&mut self, cpu: &CPU, state: &MachineState, touched: Vec<usize>, highlight: usize, finished: bool, outcomes: &[Outcome]) {
+        // Called before every step; stepping from a restored state drops the states after it
+        self.snapshots.truncate(self.position);
+        touched.into_iter().for_each(|addr| {
+            if !self.regions.contains(&addr) {
+                self.regions.push(addr);
+            }
+        });
+        self.snapshots.push(Snapshot::capture(cpu, state, &self.regions, highlight, finished, outcomes));
+        self.position = self.snapshots.len();
+    }
+    pub fn restore(&mut self, cpu: &mut CPU, state: &mut MachineState, index: usize, highlight: usize, finished: bool, outcomes: &[Outcome]) -> Option<&Snapshot> {
+        if index >= self.len() || index == self.position {
+            return None;
+        }
+        if self.position == self.snapshots.len() {
+            // Keep the newest state so the slider can come back to it
+            self.snapshots.push(Snapshot::capture(cpu, state, &self.regions, highlight, finished, outcomes));
+        }
+        let snapshot = &self.snapshots[index];
+        snapshot.vector_registers.iter().enumerate().for_each(|(i, bytes)| {
+            cpu.registers.set_by_sections::<u8>(VecRegName::ZMM, i, bytes.clone());
+        });
+        GPRS.iter().zip(snapshot.gprs.iter()).for_each(|(gpr, value)| cpu.registers.set_gpr_value(*gpr, *value));
+        cpu.registers.set_flags_value(FLAGSName::RFLAGS, snapshot.rflags);
+        // A region first touched later still held the bytes its first snapshot saw
+        self.regions.iter().for_each(|addr| {
+            if let Some(bytes) = self.snapshots[index..].iter().find_map(|s| s.memory.get(addr)) {
+                cpu.memory.write_vec::<u8>(*addr, bytes.clone());
+            }
+        });
+        state.mxcsr = snapshot.mxcsr.clone();
+        state.provenance = snapshot.provenance.clone();
+        state.trace.truncate(snapshot.trace_len);
+        self.position = index;
+        Some(&self.snapshots[index])
+    }
+    pub fn len(&self) -> usize {
+        // States to choose from, including the current one
+        if self.position == self.snapshots.len() { self.snapshots.len() + 1 } else { self.snapshots.len() }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+    pub fn position(&self) -> usize {
+        self.position
+    }
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
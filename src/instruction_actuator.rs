@@ -1,20 +1,32 @@
 use std::collections::{HashMap};
-use lazy_static::lazy_static;
 use std::convert::Into;
-use std::ops::{Add, Mul};
 use std::sync::{Arc, Mutex};
-use cpulib::{CPU, VecRegName, GPRName, SectionCompatible, u256, u512, FLAGSName};
+use cpulib::{CPU, VecRegName, GPRName, u256, u512, FLAGSName};
 use cpulib::Utilities;
 use eframe::egui::Context;
 use crate::animation_fsm::{AnimationFSM, FSMCtrlMsg};
-use crate::reg_visualizer::{LayoutLocation, RegVisualizer};
+use crate::reg_visualizer::{LayoutLocation, RegVisualizer, UpperLaneState};
 use crate::reg_visualizer_data::RegVisualizerData;
-use crate::utilities::{create_value, create_values, Register, RegType, Value, ValueType};
-use crate::{add_animation_data, vec_reg, ElementAnimationData, add_register_group_animation_data, gpr};
+use crate::mxcsr::RoundingMode;
+use crate::simulator::MachineState;
+use crate::provenance::{self, Provenance, Location, Origin};
+use crate::trace::TraceTarget;
+use crate::utilities::{create_value, create_values, create_values_f16, create_values_bf16, get_gpr_alias_info, get_vec_reg_size, Register, RegType, Value, ValueType};
+use crate::{vec_reg, ElementAnimationData, gpr};
 use regex::Regex;
+use definition::{InstructionDef, FlagsEffect, MxcsrEffect};
+
+pub fn split_comment(line: &str) -> (&str, Option<&str>) {
+    // Code and the `;` comment after it
+    match line.split_once(';') {
+        Some((code, comment)) => (code.trim(), Some(comment.trim())),
+        None => (line.trim(), None),
+    }
+}
 
 fn split_instruction(instruction: &str) -> (String, Vec<String>) {
-    let parts: Vec<&str> = instruction.splitn(2, ' ').collect();
+    let (code, _) = split_comment(instruction);
+    let parts: Vec<&str> = code.splitn(2, ' ').collect();
     if parts.is_empty() {
         return (String::from(""), Vec::new());
     }
@@ -27,6 +39,31 @@ fn split_instruction(instruction: &str) -> (String, Vec<String>) {
     (opcode.into(), operands)
 }
 
+fn get_broadcast(operand: &str) -> Option<usize> {
+    // AVX-512 embedded broadcast after a memory operand, e.g. `dword ptr [rax]{1to16}` repeats one element in 16 lanes
+    let (_, decoration) = operand.split_once("]{")?;
+    decoration.strip_suffix('}')?.to_lowercase().strip_prefix("1to")?.parse().ok()
+}
+
+fn get_ptr_size(operand: &str) -> Result<Option<usize>, String> {
+    // Bits of a size prefix like `dword ptr [rax]`, None when the operand has none
+    let operand = operand.to_lowercase();
+    let prefix = match operand.split_once("ptr") {
+        Some((prefix, _)) => prefix.trim(),
+        None => return Ok(None),
+    };
+    match prefix {
+        "byte" => Ok(Some(8)),
+        "word" => Ok(Some(16)),
+        "dword" => Ok(Some(32)),
+        "qword" => Ok(Some(64)),
+        "xmmword" => Ok(Some(128)),
+        "ymmword" => Ok(Some(256)),
+        "zmmword" => Ok(Some(512)),
+        _ => Err(format!("Invalid size prefix: {} ptr", prefix)),
+    }
+}
+
 #[derive(Clone, Eq, PartialEq)]
 enum Operand {
     Reg(Register),
@@ -34,8 +71,19 @@ enum Operand {
     Imm(u64),
 }
 
-fn get_gpr(str: String) -> Register {
-    match str.as_str() {
+// Elements of a vector register or `$count` elements starting at a memory operand
+macro_rules! read_source {
+    ($cpu:expr, $operand:expr, $ty:ty, $count:expr) => {
+        match $operand {
+            Operand::Reg(reg) => $cpu.registers.get_by_sections::<$ty>(reg.get_vector().0, reg.get_vector().1).unwrap_or_default(),
+            Operand::Mem(addr) => $cpu.memory.read_vec::<$ty>(*addr as usize, $count),
+            Operand::Imm(_) => vec![],
+        }
+    };
+}
+
+fn get_gpr(str: String) -> Result<Register, String> {
+    Ok(match str.as_str() {
         "RAX" => gpr!(RAX),
         "RBX" => gpr!(RBX),
         "RCX" => gpr!(RCX),
@@ -104,1258 +152,569 @@ fn get_gpr(str: String) -> Register {
         "R13B" => gpr!(R13B),
         "R14B" => gpr!(R14B),
         "R15B" => gpr!(R15B),
-        _ => panic!("Invalid GPR"),
+        _ => return Err(format!("Invalid GPR: {}", str)),
+    })
+}
+
+pub fn parse_register(name: &str) -> Option<Register> {
+    let name = name.trim().to_uppercase();
+    if name.len() > 3 && (name.starts_with("XMM") || name.starts_with("YMM") || name.starts_with("ZMM")) {
+        let index = name[3..].parse::<usize>().ok().filter(|index| *index < 32)?;
+        match &name[..3] {
+            "XMM" => Some(vec_reg!(XMM, index)),
+            "YMM" => Some(vec_reg!(YMM, index)),
+            _ => Some(vec_reg!(ZMM, index)),
+        }
+    } else {
+        get_gpr(name).ok()
     }
 }
 
-fn create_operands(operands: Vec<String>, cpu: Arc<Mutex<CPU>>) -> Vec<Operand> {
-    let mut operand_vec = vec![];
-    operands.iter().for_each(|operand| {
-        let operand = operand.to_uppercase();
-        if operand.starts_with('[') && operand.ends_with(']') {
-            // Memory: reg + offset, TODO: support full memory format
-            let address = &operand[1..operand.len() - 1];
-            let mut address: String = address.into();
-            address = address.replace(" ", "");
-            address = address.to_uppercase();
-            let re = Regex::new(r"(0X[0-9a-fA-F]+|\d+|[a-zA-Z]+|[+\-*/])").unwrap();
-            let parts: Vec<&str> = re.find_iter(&*address).map(|m| m.as_str()).collect();
-            if parts.len() != 3 {
-                println!("Unsupported address format: {}", address);
-            }
-            let (number_str, operator, register_str) = if parts[0].chars().next().unwrap().is_digit(10) || &parts[0][0..2] == "0x" {
-                (parts[0], parts[1], parts[2])
+fn get_address(address: &str, cpu: Arc<Mutex<CPU>>) -> Result<u64, String> {
+    // Terms like `RSI`, `RAX*4` or `0x10` joined by + and -, e.g. `RSI+RAX*4-0x10`
+    let address = address.replace(' ', "").to_uppercase();
+    let re = Regex::new(r"([+\-]?)([^+\-]+)").unwrap();
+    let cpu = cpu.lock().unwrap();
+    let sum = re.captures_iter(&address).try_fold(0u64, |sum, term| {
+        let value = term[2].split('*').try_fold(1u64, |product, factor| {
+            let factor = if let Some(hex) = factor.strip_prefix("0X") {
+                u64::from_str_radix(hex, 16).map_err(|_| format!("Invalid hex number: {}", factor))?
+            } else if factor.starts_with(|c: char| c.is_ascii_digit()) {
+                factor.parse::<u64>().map_err(|_| format!("Invalid number: {}", factor))?
             } else {
-                (parts[2], parts[1], parts[0])
+                let register = get_gpr(factor.into())?;
+                cpu.registers.get_gpr_value(register.get_gpr())
             };
-            let number = if number_str.starts_with("0X") {
-                u64::from_str_radix(&number_str[2..], 16).expect("Invalid hex number")
-            } else {
-                number_str.parse::<u64>().expect("Invalid number")
-            };
-            let register = get_gpr(register_str.into());
-            let mut cpu = cpu.lock().unwrap();
-            let reg_value = cpu.registers.get_gpr_value(register.get_gpr());
-            let result = match operator {
-                "+" => number + reg_value,
-                "-" => number - reg_value,
-                "*" => number * reg_value,
-                "/" => number / reg_value,
-                _ => 0u64,
-            };
-            operand_vec.push(Operand::Mem(result));
-        } else if operand.chars().all(|c| c.is_ascii_hexdigit() || c == 'X' || c == 'x') {
-            // Immediate
-            let imm = if operand.starts_with("0X") || operand.starts_with("0x") {
-                u64::from_str_radix(&operand[2..], 16).expect("Invalid immediate")
-            } else if operand.starts_with("0B") || operand.starts_with("0b") {
-                u64::from_str_radix(&operand[2..], 2).expect("Invalid immediate")
+            Ok::<u64, String>(product.wrapping_mul(factor))
+        })?;
+        Ok::<u64, String>(if &term[1] == "-" { sum.wrapping_sub(value) } else { sum.wrapping_add(value) })
+    })?;
+    Ok(sum)
+}
+
+fn create_operands(operands: Vec<String>, cpu: Arc<Mutex<CPU>>) -> Result<Vec<Operand>, String> {
+    operands.iter().map(|operand| {
+        let operand = operand.to_uppercase();
+        // Size prefixes and broadcasts only decorate the address, e.g. `DWORD PTR [RAX]{1TO16}`
+        let operand = match operand.split_once("PTR") {
+            Some((_, memory)) => memory.trim().to_string(),
+            None => operand,
+        };
+        let operand = match operand.find("]{") {
+            Some(end) => operand[..=end].to_string(),
+            None => operand,
+        };
+        if operand.starts_with('[') && operand.ends_with(']') {
+            // Memory: base + index * scale + displacement
+            let address = get_address(&operand[1..operand.len() - 1], cpu.clone())?;
+            Ok(Operand::Mem(address))
+        } else if operand.starts_with(|c: char| c.is_ascii_digit()) {
+            // Immediate, registers like EAX are made of hex digits too
+            let imm = if operand.starts_with("0X") {
+                u64::from_str_radix(&operand[2..], 16)
+            } else if operand.starts_with("0B") {
+                u64::from_str_radix(&operand[2..], 2)
             } else {
-                u64::from_str_radix(operand.as_str(), 10).expect("Invalid immediate")
+                operand.parse::<u64>()
             };
-            operand_vec.push(Operand::Imm(imm));
-        } else if operand.starts_with("XMM") || operand.starts_with("YMM") || operand.starts_with("ZMM") {
-            // Vector Register
-            let reg_name = &operand[..3];
-            let index = operand[3..].parse::<usize>().expect("Invalid Vector Register Index");
-            let reg = match reg_name {
-                "XMM" => vec_reg!(XMM, index),
-                "YMM" => vec_reg!(YMM, index),
-                "ZMM" => vec_reg!(ZMM, index),
-                _ => panic!("Invalid Vector Register"),
-            };
-            operand_vec.push(Operand::Reg(reg));
+            imm.map(Operand::Imm).map_err(|_| format!("Invalid immediate: {}", operand))
         } else {
-            // GPR
-            let reg = get_gpr(operand);
-            operand_vec.push(Operand::Reg(reg));
+            // Vector Register or GPR
+            parse_register(&operand).map(Operand::Reg).ok_or(format!("Invalid operand: {}", operand))
         }
-    });
-    operand_vec
+    }).collect()
 }
 
-trait FloatCalc {
-    fn fadd(self, other: Self) -> Self;
-    fn fmul(self, other: Self) -> Self;
-}
-impl FloatCalc for u32 {
-    fn fadd(self, other: Self) -> Self {
-        Utilities::f32_to_u32(Utilities::u32_to_f32(self) + Utilities::u32_to_f32(other))
-    }
-    fn fmul(self, other: Self) -> Self {
-        Utilities::f32_to_u32(Utilities::u32_to_f32(self) * Utilities::u32_to_f32(other))
-    }
-}
-impl FloatCalc for u64 {
-    fn fadd(self, other: Self) -> Self {
-        Utilities::f64_to_u64(Utilities::u64_to_f64(self) + Utilities::u64_to_f64(other))
-    }
-    fn fmul(self, other: Self) -> Self {
-        Utilities::f64_to_u64(Utilities::u64_to_f64(self) * Utilities::u64_to_f64(other))
-    }
-}
-macro_rules! fake_float_calc {
-    ($ty:ident) => {
-        impl FloatCalc for $ty {
-            fn fadd(self, other: Self) -> Self {
-                self + other
-            }
-            fn fmul(self, other: Self) -> Self {
-                self * other
-            }
+fn get_values_from_register(reg: Register, cpu: Arc<Mutex<CPU>>, vrt: HashMap<(VecRegName, usize), ValueType>) -> Vec<Value> {
+    match reg.get_type() {
+        RegType::GPR => {
+            let cpu = cpu.lock().unwrap();
+            vec![create_value(cpu.registers.get_gpr_value(reg.get_gpr()))]
         }
-    };
-}
-fake_float_calc!(u8);
-fake_float_calc!(u16);
-fake_float_calc!(u128);
-fake_float_calc!(u256);
-fake_float_calc!(u512);
-
-macro_rules! create_calc_common {
-    ($func_name:ident, $calc:ident, $fcalc:ident) => {
-        fn $func_name<T>(cpu: Arc<Mutex<CPU>>, _vrt: HashMap<(VecRegName, usize), ValueType>, target: Operand, source1: Operand, source2: Operand, is_float: bool)
-            where Vec<T>: FromIterator<<T as Add>::Output>, Vec<T>: FromIterator<<T as Mul>::Output>, T: SectionCompatible + Add + FloatCalc + Mul
-        {
-            match target {
-                Operand::Reg(dst) => {
-                    if let (Operand::Reg(src1), Operand::Reg(src2)) = (source1.clone(), source2.clone()) {
-                        if !(dst.get_type() == src1.get_type() && dst.get_type() == src2.get_type()) { return; }
-                        if dst.get_type() == RegType::GPR {
-                            let mut cpu = cpu.lock().unwrap();
-                            let result = if is_float {
-                                cpu.registers.get_gpr_value(src1.get_gpr()).$fcalc(cpu.registers.get_gpr_value(src2.get_gpr()))
-                            } else {
-                                cpu.registers.get_gpr_value(src1.get_gpr()).$calc(cpu.registers.get_gpr_value(src2.get_gpr()))
-                            };
-                            cpu.registers.set_gpr_value(dst.get_gpr(), result);
-                        } else if dst.get_type() == RegType::Vector && dst.get_vector().0 == src1.get_vector().0 && dst.get_vector().0 == src2.get_vector().0 {
-                            let mut cpu = cpu.lock().unwrap();
-                            let mut a: Vec<T> = cpu.registers.get_by_sections::<T>(src1.get_vector().0, src1.get_vector().1).unwrap();
-                            let mut b: Vec<T> = cpu.registers.get_by_sections::<T>(src2.get_vector().0, src2.get_vector().1).unwrap();
-                            let result = if is_float {
-                                a.iter().zip(b.iter()).map(|(x, y)| { (*x).$fcalc(*y) }).collect()
-                            } else {
-                                a.iter().zip(b.iter()).map(|(x, y)| { (*x).$calc(*y) }).collect()
-                            };
-                            cpu.registers.set_by_sections::<T>(dst.get_vector().0, dst.get_vector().1, result);
-                        }
-                    } else if let (Operand::Reg(src1), Operand::Imm(src2)) = (source1.clone(), source2.clone()) {
-                        if dst.get_type() == src1.get_type() && dst.get_type() == RegType::GPR {
-                            let mut cpu = cpu.lock().unwrap();
-                            let result = if is_float {
-                                cpu.registers.get_gpr_value(src1.get_gpr()).$fcalc(src2)
-                            } else {
-                                cpu.registers.get_gpr_value(src1.get_gpr()).$calc(src2)
-                            };
-                            cpu.registers.set_gpr_value(dst.get_gpr(), result);
-                        }
-                    } else if let (Operand::Reg(_src1), Operand::Mem(_src2)) = (source1.clone(), source2.clone()) {
-                        todo!()
-                    }
-                }
-                _ => panic!("Invalid Instruction"),
+        RegType::Vector => {
+            let cpu = cpu.lock().unwrap();
+            match vrt.get(&reg.get_vector()).unwrap() {
+                ValueType::U8 => create_values(cpu.registers.get_by_sections::<u8>(reg.get_vector().0, reg.get_vector().1).unwrap()),
+                ValueType::U16 => create_values(cpu.registers.get_by_sections::<u16>(reg.get_vector().0, reg.get_vector().1).unwrap()),
+                ValueType::U32 => create_values(cpu.registers.get_by_sections::<u32>(reg.get_vector().0, reg.get_vector().1).unwrap()),
+                ValueType::U64 => create_values(cpu.registers.get_by_sections::<u64>(reg.get_vector().0, reg.get_vector().1).unwrap()),
+                ValueType::U128 => create_values(cpu.registers.get_by_sections::<u128>(reg.get_vector().0, reg.get_vector().1).unwrap()),
+                ValueType::U256 => create_values(cpu.registers.get_by_sections::<u256>(reg.get_vector().0, reg.get_vector().1).unwrap()),
+                ValueType::U512 => create_values(cpu.registers.get_by_sections::<u512>(reg.get_vector().0, reg.get_vector().1).unwrap()),
+                ValueType::F32 => create_values(Utilities::u32vec_to_f32vec(cpu.registers.get_by_sections::<u32>(reg.get_vector().0, reg.get_vector().1).unwrap())),
+                ValueType::F64 => create_values(Utilities::u64vec_to_f64vec(cpu.registers.get_by_sections::<u64>(reg.get_vector().0, reg.get_vector().1).unwrap())),
+                ValueType::F16 => create_values_f16(cpu.registers.get_by_sections::<u16>(reg.get_vector().0, reg.get_vector().1).unwrap()),
+                ValueType::BF16 => create_values_bf16(cpu.registers.get_by_sections::<u16>(reg.get_vector().0, reg.get_vector().1).unwrap()),
+                ValueType::I8 => create_values(cpu.registers.get_by_sections::<u8>(reg.get_vector().0, reg.get_vector().1).unwrap().into_iter().map(|x| x as i8).collect::<Vec<i8>>()),
+                ValueType::I16 => create_values(cpu.registers.get_by_sections::<u16>(reg.get_vector().0, reg.get_vector().1).unwrap().into_iter().map(|x| x as i16).collect::<Vec<i16>>()),
+                ValueType::I32 => create_values(cpu.registers.get_by_sections::<u32>(reg.get_vector().0, reg.get_vector().1).unwrap().into_iter().map(|x| x as i32).collect::<Vec<i32>>()),
+                ValueType::I64 => create_values(cpu.registers.get_by_sections::<u64>(reg.get_vector().0, reg.get_vector().1).unwrap().into_iter().map(|x| x as i64).collect::<Vec<i64>>()),
             }
         }
-    };
-}
-
-create_calc_common!(add_common, add, fadd);
-create_calc_common!(mul_common, mul, fmul);
-
-fn vaddps(cpu: Arc<Mutex<CPU>>, operands: Vec<Operand>, vrt: HashMap<(VecRegName, usize), ValueType>) {
-    if operands.len() != 3 { return; }
-    add_common::<u32>(cpu, vrt, operands[0].clone(), operands[1].clone(), operands[2].clone(), true);
-}
-
-fn vpaddd(cpu: Arc<Mutex<CPU>>, operands: Vec<Operand>, vrt: HashMap<(VecRegName, usize), ValueType>) {
-    if operands.len() != 3 { return; }
-    add_common::<u32>(cpu, vrt, operands[0].clone(), operands[1].clone(), operands[2].clone(), false);
+        RegType::None => vec![],
+    }
 }
 
-fn vmulpd(cpu: Arc<Mutex<CPU>>, operands: Vec<Operand>, vrt: HashMap<(VecRegName, usize), ValueType>) {
-    if operands.len() != 3 { return; }
-    mul_common::<u64>(cpu, vrt, operands[0].clone(), operands[1].clone(), operands[2].clone(), true);
+fn get_values_from_memory(address: usize, value_type: ValueType, num: usize, cpu: &CPU) -> Vec<Value> {
+    match value_type {
+        ValueType::U8 => create_values(cpu.memory.read_vec::<u8>(address, num)),
+        ValueType::U16 => create_values(cpu.memory.read_vec::<u16>(address, num)),
+        ValueType::U32 => create_values(cpu.memory.read_vec::<u32>(address, num)),
+        ValueType::U64 => create_values(cpu.memory.read_vec::<u64>(address, num)),
+        ValueType::U128 => create_values(cpu.memory.read_vec::<u128>(address, num)),
+        ValueType::U256 => create_values(cpu.memory.read_vec::<u256>(address, num)),
+        ValueType::U512 => create_values(cpu.memory.read_vec::<u512>(address, num)),
+        ValueType::F32 => create_values(Utilities::u32vec_to_f32vec(cpu.memory.read_vec::<u32>(address, num))),
+        ValueType::F64 => create_values(Utilities::u64vec_to_f64vec(cpu.memory.read_vec::<u64>(address, num))),
+        ValueType::F16 => create_values_f16(cpu.memory.read_vec::<u16>(address, num)),
+        ValueType::BF16 => create_values_bf16(cpu.memory.read_vec::<u16>(address, num)),
+        ValueType::I8 => create_values(cpu.memory.read_vec::<u8>(address, num).into_iter().map(|x| x as i8).collect::<Vec<i8>>()),
+        ValueType::I16 => create_values(cpu.memory.read_vec::<u16>(address, num).into_iter().map(|x| x as i16).collect::<Vec<i16>>()),
+        ValueType::I32 => create_values(cpu.memory.read_vec::<u32>(address, num).into_iter().map(|x| x as i32).collect::<Vec<i32>>()),
+        ValueType::I64 => create_values(cpu.memory.read_vec::<u64>(address, num).into_iter().map(|x| x as i64).collect::<Vec<i64>>()),
+    }
 }
 
-fn add(cpu: Arc<Mutex<CPU>>, operands: Vec<Operand>, vrt: HashMap<(VecRegName, usize), ValueType>) {
-    if operands.len() != 3 { return; }
-    add_common::<u64>(cpu, vrt, operands[0].clone(), operands[1].clone(), operands[2].clone(), false);
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Encoding {
+    Legacy, // SSE: bits above the destination width are preserved
+    VEX,    // VEX/EVEX: bits above the destination width are zeroed
 }
 
-fn valignd(cpu: Arc<Mutex<CPU>>, operands: Vec<Operand>, _vrt: HashMap<(VecRegName, usize), ValueType>) {
-    if operands.len() != 4 { return; }
-    let target = operands[0].clone();
-    let source1 = operands[1].clone();
-    let source2 = operands[2].clone();
-    let imm8 = operands[3].clone();
-    if let (Operand::Reg(dst), Operand::Reg(src1), Operand::Reg(src2), Operand::Imm(imm8))
-        = (target, source1, source2, imm8) {
-        if dst.get_type() == RegType::Vector && src1.get_type() == RegType::Vector && src2.get_type() == RegType::Vector {
-            let mut cpu = cpu.lock().unwrap();
-            let mut vec1 = cpu.registers.get_by_sections::<u32>(src1.get_vector().0, src1.get_vector().1).unwrap();
-            let mut vec2 = cpu.registers.get_by_sections::<u32>(src2.get_vector().0, src2.get_vector().1).unwrap();
-            let mut veca = vec2.clone();
-            veca.append(&mut vec1);
-            let mut veca = Vec::from(&veca[imm8 as usize..]);
-            if veca.len() > 16 {
-                veca = Vec::from(&veca[..16]);
-            } else {
-                veca.resize(16, 0);
-            }
-            cpu.registers.set_by_sections(dst.get_vector().0, dst.get_vector().1, veca);
+impl Encoding {
+    fn from_opcode(opcode: &str) -> Self {
+        if opcode.starts_with('v') {
+            Encoding::VEX
+        } else {
+            Encoding::Legacy
         }
     }
-}
-
-fn unpack_common(cpu: Arc<Mutex<CPU>>, operands: Vec<Operand>, _vrt: HashMap<(VecRegName, usize), ValueType>, is_high: bool) {
-    let target = operands[0].clone();
-    let source1 = operands[1].clone();
-    let source2 = operands[2].clone();
-    if let (Operand::Reg(dst), Operand::Reg(src1), Operand::Reg(src2))
-        = (target, source1, source2) {
-        if dst.get_type() == RegType::Vector && src1.get_type() == RegType::Vector && src2.get_type() == RegType::Vector &&
-            dst.get_vector().0 == src1.get_vector().0 && dst.get_vector().0 == src2.get_vector().0 {
-            let mut cpu = cpu.lock().unwrap();
-            let interleave_dwords = if !is_high {
-                |src1: Vec<u32>, src2: Vec<u32>| -> Vec<u32> {
-                    let mut dst: Vec<u32> = vec![];
-                    dst.push(src1[0]);
-                    dst.push(src2[0]);
-                    dst.push(src1[1]);
-                    dst.push(src2[1]);
-                    dst
-                }
-            } else {
-                |src1: Vec<u32>, src2: Vec<u32>| -> Vec<u32> {
-                    let mut dst: Vec<u32> = vec![];
-                    dst.push(src1[2]);
-                    dst.push(src2[2]);
-                    dst.push(src1[3]);
-                    dst.push(src2[3]);
-                    dst
-                }
-            };
-            let v1 = cpu.registers.get_by_sections::<u32>(src1.get_vector().0, src1.get_vector().1).unwrap();
-            let v2 = cpu.registers.get_by_sections::<u32>(src2.get_vector().0, src2.get_vector().1).unwrap();
-            let vs = match dst.get_vector().0 {
-                VecRegName::XMM => {
-                    interleave_dwords(v1, v2)
-                },
-                VecRegName::YMM => {
-                    let mut tmp = vec![];
-                    tmp.append(&mut interleave_dwords(Vec::from(&v1[0..4]), Vec::from(&v2[0..4])));
-                    tmp.append(&mut interleave_dwords(Vec::from(&v1[4..8]), Vec::from(&v2[4..8])));
-                    tmp
-                },
-                VecRegName::ZMM => {
-                    let mut tmp = vec![];
-                    tmp.append(&mut interleave_dwords(Vec::from(&v1[0..4]), Vec::from(&v2[0..4])));
-                    tmp.append(&mut interleave_dwords(Vec::from(&v1[4..8]), Vec::from(&v2[4..8])));
-                    tmp.append(&mut interleave_dwords(Vec::from(&v1[8..12]), Vec::from(&v2[8..12])));
-                    tmp.append(&mut interleave_dwords(Vec::from(&v1[12..16]), Vec::from(&v2[12..16])));
-                    tmp
-                },
-            };
-            cpu.registers.set_by_sections(dst.get_vector().0, dst.get_vector().1, vs);
+    fn upper_lane_state(&self) -> UpperLaneState {
+        match self {
+            Encoding::Legacy => UpperLaneState::Kept,
+            Encoding::VEX => UpperLaneState::Zeroed,
         }
     }
 }
 
-fn vunpcklps(cpu: Arc<Mutex<CPU>>, operands: Vec<Operand>, vrt: HashMap<(VecRegName, usize), ValueType>) {
-    if operands.len() != 3 { return; }
-    unpack_common(cpu, operands, vrt, false);
-}
-
-fn vunpckhps(cpu: Arc<Mutex<CPU>>, operands: Vec<Operand>, vrt: HashMap<(VecRegName, usize), ValueType>) {
-    if operands.len() != 3 { return; }
-    unpack_common(cpu, operands, vrt, true);
-}
-
-fn vshufps(cpu: Arc<Mutex<CPU>>, operands: Vec<Operand>, _vrt: HashMap<(VecRegName, usize), ValueType>) {
-    if operands.len() != 4 { return; }
-    let target = operands[0].clone();
-    let source1 = operands[1].clone();
-    let source2 = operands[2].clone();
-    let imm = operands[3].clone();
-    if let (Operand::Reg(dst), Operand::Reg(src1), Operand::Reg(src2), Operand::Imm(imm8))
-        = (target, source1, source2, imm) {
-        if dst.get_type() == RegType::Vector && src1.get_type() == RegType::Vector && src2.get_type() == RegType::Vector &&
-            dst.get_vector().0 == src1.get_vector().0 && dst.get_vector().0 == src2.get_vector().0 {
-            let mut cpu = cpu.lock().unwrap();
-            let mut imm8v = vec![];
-            [0u8, 2, 4, 6].iter().for_each(|i| {
-                let tmp = imm8 as u8 >> i;
-                imm8v.push((tmp & 0b00000011) as usize);
-            });
-            let s1v = cpu.registers.get_by_sections::<u32>(src1.get_vector().0, src1.get_vector().1).unwrap();
-            let s2v = cpu.registers.get_by_sections::<u32>(src2.get_vector().0, src2.get_vector().1).unwrap();
-            let mut dv = vec![];
-            match dst.get_vector().0 {
-                VecRegName::XMM => {todo!()}
-                VecRegName::YMM => {
-                    dv.push(s1v[imm8v[0]]);
-                    dv.push(s1v[imm8v[1]]);
-                    dv.push(s2v[imm8v[2]]);
-                    dv.push(s2v[imm8v[3]]);
-                    dv.push(s1v[4 + imm8v[0]]);
-                    dv.push(s1v[4 + imm8v[1]]);
-                    dv.push(s2v[4 + imm8v[2]]);
-                    dv.push(s2v[4 + imm8v[3]]);
-                }
-                VecRegName::ZMM => {todo!()}
-            }
-            cpu.registers.set_by_sections(dst.get_vector().0, dst.get_vector().1, dv);
+fn get_upper_bits(cpu: Arc<Mutex<CPU>>, target: &Operand) -> Option<Vec<u128>> {
+    if let Operand::Reg(dst) = target {
+        if dst.get_type() == RegType::Vector {
+            let cpu = cpu.lock().unwrap();
+            return cpu.registers.get_by_sections::<u128>(VecRegName::ZMM, dst.get_vector().1);
         }
     }
-}
-
-fn vperm2f128(cpu: Arc<Mutex<CPU>>, operands: Vec<Operand>, _vrt: HashMap<(VecRegName, usize), ValueType>) {
-    if operands.len() != 4 { return; }
-    let target = operands[0].clone();
-    let source1 = operands[1].clone();
-    let source2 = operands[2].clone();
-    let imm = operands[3].clone();
-    if let (Operand::Reg(dst), Operand::Reg(src1), Operand::Reg(src2), Operand::Imm(imm8))
-        = (target, source1, source2, imm) {
-        if dst.get_type() == RegType::Vector && src1.get_type() == RegType::Vector && src2.get_type() == RegType::Vector &&
-            dst.get_vector().0 == src1.get_vector().0 && dst.get_vector().0 == src2.get_vector().0 {
-            let mut cpu = cpu.lock().unwrap();
-            let v1 = cpu.registers.get_by_sections::<u128>(src1.get_vector().0, src1.get_vector().1).unwrap();
-            let v2 = cpu.registers.get_by_sections::<u128>(src2.get_vector().0, src2.get_vector().1).unwrap();
-            let mut dv = vec![];
-            let select4 = |src1: &Vec<u128>, src2: &Vec<u128>, control: u8| -> u128 {
-                if (control >> 3) & 0b00000001 == 1 {
-                    return 0u128;
-                }
-                match control & 0b00000011 {
-                    0 => src1[0],
-                    1 => src1[1],
-                    2 => src2[0],
-                    3 => src2[1],
-                    _ => 0u128
-                }
+    None
+}
+
+fn set_upper_bits(cpu: Arc<Mutex<CPU>>, target: &Operand, encoding: Encoding, before: Option<Vec<u128>>) {
+    if let (Operand::Reg(dst), Some(before)) = (target, before) {
+        let mut cpu = cpu.lock().unwrap();
+        let (name, index) = dst.get_vector();
+        let mut after = cpu.registers.get_by_sections::<u128>(VecRegName::ZMM, index).unwrap();
+        (get_vec_reg_size(&name) / 128..after.len()).for_each(|i| {
+            after[i] = match encoding {
+                Encoding::Legacy => before[i],
+                Encoding::VEX => 0u128,
             };
-            dv.push(select4(&v1, &v2, imm8 as u8 & 0b00001111));
-            dv.push(select4(&v1, &v2, (imm8 as u8 >> 4) & 0b00001111));
-            cpu.registers.set_by_sections(dst.get_vector().0, dst.get_vector().1, dv);
-        }
+        });
+        cpu.registers.set_by_sections(VecRegName::ZMM, index, after);
     }
 }
 
-fn vextractf128(cpu: Arc<Mutex<CPU>>, operands: Vec<Operand>, _vrt: HashMap<(VecRegName, usize), ValueType>) {
-    if operands.len() != 3 { return; }
-    let target = operands[0].clone();
-    let source = operands[1].clone();
-    let imm = operands[2].clone();
-    if let (Operand::Reg(dst), Operand::Reg(src), Operand::Imm(imm8)) =
-        (target, source, imm) {
-        if dst.get_type() == RegType::Vector && dst.get_vector().0 == VecRegName::XMM &&
-            src.get_type() == RegType::Vector && src.get_vector().0 == VecRegName::YMM {
+fn zero_extend_gpr(cpu: Arc<Mutex<CPU>>, target: &Operand) {
+    // Writing a 32-bit GPR clears bits 63:32 of the full register
+    if let Operand::Reg(dst) = target {
+        if dst.get_type() == RegType::GPR && Utilities::get_gpr_size(&dst.get_gpr()) == 32 {
             let mut cpu = cpu.lock().unwrap();
-            let sv = cpu.registers.get_by_sections::<u128>(src.get_vector().0, src.get_vector().1).unwrap();
-            let mut dv = vec![];
-            if imm8 as u8 & 0b00000001 == 1 {
-                dv.push(sv[1]);
-            } else {
-                dv.push(sv[0]);
-            }
-            cpu.registers.set_by_sections(dst.get_vector().0, dst.get_vector().1, dv);
+            let v = cpu.registers.get_gpr_value(dst.get_gpr());
+            cpu.registers.set_gpr_value(get_gpr_alias_info(&dst.get_gpr()).0, v & 0xFFFFFFFF);
         }
     }
 }
 
-fn shufpd(cpu: Arc<Mutex<CPU>>, operands: Vec<Operand>, _vrt: HashMap<(VecRegName, usize), ValueType>) {
-    if operands.len() != 4 { return; }
-    let target = operands[0].clone();
-    let source1 = operands[1].clone();
-    let source2 = operands[2].clone();
-    let imm = operands[3].clone();
-    if let (Operand::Reg(dst), Operand::Reg(src1), Operand::Reg(src2), Operand::Imm(imm8)) =
-        (target, source1, source2, imm) {
-        if dst.get_type() == RegType::Vector && src1.get_type() == RegType::Vector && src2.get_type() == RegType::Vector &&
-            dst.get_vector().0 == VecRegName::XMM && dst.get_vector().0 == src1.get_vector().0 && dst.get_vector().0 == src2.get_vector().0 {
-            let mut cpu = cpu.lock().unwrap();
-            let v1 = cpu.registers.get_by_sections::<u64>(src1.get_vector().0, src1.get_vector().1).unwrap();
-            let v2 = cpu.registers.get_by_sections::<u64>(src2.get_vector().0, src2.get_vector().1).unwrap();
-            let mut dv = vec![];
-            if imm8 as u8 & 0b00000001 == 0 {
-                dv.push(v1[0]);
-            } else {
-                dv.push(v1[1]);
-            }
-            if (imm8 as u8 >> 1) & 0b00000001 == 0 {
-                dv.push(v2[0]);
-            } else {
-                dv.push(v2[1]);
-            }
-            cpu.registers.set_by_sections(dst.get_vector().0, dst.get_vector().1, dv);
-        }
+fn get_operand_locations(operand: &Operand, width: usize) -> Vec<Location> {
+    match operand {
+        Operand::Reg(reg) => provenance::get_register_locations(reg),
+        Operand::Mem(addr) => (0..width as u64).map(|i| Location::Memory(addr + i)).collect(),
+        Operand::Imm(_) => vec![],
     }
 }
 
-fn mov_common(cpu: Arc<Mutex<CPU>>, operands: Vec<Operand>, _vrt: HashMap<(VecRegName, usize), ValueType>) {
-    if operands.len() != 2 { return; }
-    let target = operands[0].clone();
-    let source = operands[1].clone();
-    match target {
-        Operand::Reg(dst) => {
-            match source {
-                Operand::Reg(src) => {
-                    // reg -> reg
-                    if dst.get_type() == src.get_type() {
-                        if dst.get_type() == RegType::GPR {
-                            // gpr -> gpr
-                            let mut cpu = cpu.lock().unwrap();
-                            let v = cpu.registers.get_gpr_value(src.get_gpr());
-                            cpu.registers.set_gpr_value(dst.get_gpr(), v);
-                        } else if dst.get_type() == RegType::Vector && dst.get_vector().0 == src.get_vector().0 {
-                            // vector -> vector
-                            let mut cpu = cpu.lock().unwrap();
-                            match dst.get_vector().0 {
-                                VecRegName::XMM => {
-                                    let v = cpu.registers.get_by_sections::<u128>(src.get_vector().0, src.get_vector().1).unwrap();
-                                    cpu.registers.set_by_sections(dst.get_vector().0, dst.get_vector().1, v);
-                                }
-                                VecRegName::YMM => {
-                                    let v = cpu.registers.get_by_sections::<u256>(src.get_vector().0, src.get_vector().1).unwrap();
-                                    cpu.registers.set_by_sections(dst.get_vector().0, dst.get_vector().1, v);
-                                }
-                                VecRegName::ZMM => {
-                                    let v = cpu.registers.get_by_sections::<u512>(src.get_vector().0, src.get_vector().1).unwrap();
-                                    cpu.registers.set_by_sections(dst.get_vector().0, dst.get_vector().1, v);
-                                }
-                            }
-                        }
-                    }
-                }
-                Operand::Mem(src) => {
-                    // mem -> reg
-                    let mut cpu = cpu.lock().unwrap();
-                    if dst.get_type() == RegType::GPR {
-                        // mem -> gpr
-                        let v = cpu.memory.read::<u64>(src as usize);
-                        cpu.registers.set_gpr_value(dst.get_gpr(), v);
-                    } else if dst.get_type() == RegType::Vector {
-                        // mem -> vector
-                        match dst.get_vector().0 {
-                            VecRegName::XMM => {
-                                let v = cpu.memory.read::<u128>(src as usize);
-                                cpu.registers.set_by_sections(dst.get_vector().0, dst.get_vector().1, vec![v]);
-                            }
-                            VecRegName::YMM => {
-                                let v = cpu.memory.read::<u256>(src as usize);
-                                cpu.registers.set_by_sections(dst.get_vector().0, dst.get_vector().1, vec![v]);
-                            }
-                            VecRegName::ZMM => {
-                                let v = cpu.memory.read::<u512>(src as usize);
-                                cpu.registers.set_by_sections(dst.get_vector().0, dst.get_vector().1, vec![v]);
-                            }
-                        }
-                    }
+fn track_provenance(provenance: &mut Provenance, def: &InstructionDef, instruction: &str, run: &dyn Fn(Arc<Mutex<CPU>>), operands: &Vec<Operand>, encoding: Encoding) {
+    provenance.next_step();
+    if !def.is_target_written() {
+        return;
+    }
+    // Bytes written to a memory destination
+    let width = match operands[0] {
+        Operand::Mem(_) => def.get_memory_size(operands, false).unwrap_or(0),
+        _ => 0,
+    };
+    let target = get_operand_locations(&operands[0], width);
+    let mut origins = vec![];
+    if def.is_data_movement() {
+        // Run the instruction on a shadow CPU holding a unique tag in every 16-bit granule of its sources
+        let shadow = Arc::new(Mutex::new(CPU::default()));
+        let mut tags: HashMap<u16, (Origin, Origin)> = HashMap::new();
+        let mut tag_granules = |locations: Vec<Location>| -> Vec<u16> {
+            locations.chunks(2).map(|pair| {
+                let tag = tags.len() as u16 + 1; // 0 is left for zeroed granules
+                tags.insert(tag, (provenance.get_origin(&pair[0]), provenance.get_origin(&pair[1])));
+                tag
+            }).collect()
+        };
+        operands.iter().skip(1).for_each(|operand| {
+            let mut cpu = shadow.lock().unwrap();
+            match operand {
+                Operand::Reg(reg) if reg.get_type() == RegType::Vector => {
+                    let full = Register::vector(VecRegName::ZMM, reg.get_vector().1);
+                    cpu.registers.set_by_sections::<u16>(VecRegName::ZMM, reg.get_vector().1, tag_granules(provenance::get_register_locations(&full)));
                 }
-                Operand::Imm(src) => {
-                    // imm -> reg(gpr)
-                    if dst.get_type() == RegType::GPR {
-                        let mut cpu = cpu.lock().unwrap();
-                        cpu.registers.set_gpr_value(dst.get_gpr(), src);
-                    }
+                Operand::Mem(addr) => {
+                    cpu.memory.write_vec::<u16>(*addr as usize, tag_granules(get_operand_locations(operand, 64)));
                 }
+                _ => {}
             }
-        }
-        Operand::Mem(dst) => {
-            if let Operand::Reg(src) = source {
-                // reg -> mem
-                if src.get_type() == RegType::GPR {
-                    // gpr -> mem
-                    let mut cpu = cpu.lock().unwrap();
-                    let v = cpu.registers.get_gpr_value(src.get_gpr());
-                    cpu.memory.write::<u64>(dst as usize, v);
-                } else if src.get_type() == RegType::Vector {
-                    // vector -> mem
-                    let mut cpu = cpu.lock().unwrap();
-                    match src.get_vector().0 {
-                        VecRegName::XMM => {
-                            let v = cpu.registers.get_by_sections::<u128>(src.get_vector().0, src.get_vector().1).unwrap();
-                            cpu.memory.write::<u128>(dst as usize, v[0]);
-                        }
-                        VecRegName::YMM => {
-                            let v = cpu.registers.get_by_sections::<u256>(src.get_vector().0, src.get_vector().1).unwrap();
-                            cpu.memory.write::<u256>(dst as usize, v[0]);
-                        }
-                        VecRegName::ZMM => {
-                            let v = cpu.registers.get_by_sections::<u512>(src.get_vector().0, src.get_vector().1).unwrap();
-                            cpu.memory.write::<u512>(dst as usize, v[0]);
-                        }
-                    }
+        });
+        run(shadow.clone());
+        let cpu = shadow.lock().unwrap();
+        let granules = match operands[0] {
+            Operand::Reg(reg) => cpu.registers.get_by_sections::<u16>(VecRegName::ZMM, reg.get_vector().1).unwrap(),
+            Operand::Mem(addr) => cpu.memory.read_vec::<u16>(addr as usize, width / 2),
+            Operand::Imm(_) => vec![],
+        };
+        let mut node = None;
+        target.chunks(2).zip(granules.iter()).for_each(|(pair, tag)| {
+            let (low, high) = match tags.get(tag) {
+                Some(origin) => *origin,
+                None if *tag == 0 => (Origin::Zero, Origin::Zero),
+                None => {
+                    // Not a moved granule after all
+                    let id = *node.get_or_insert_with(|| provenance.add_node(instruction));
+                    (Origin::Computation(id), Origin::Computation(id))
                 }
-            }
-        }
-        _ => {/*ERROR*/}
+            };
+            origins.push((pair[0], low));
+            origins.push((pair[1], high));
+        });
+    } else {
+        let node = provenance.add_node(instruction);
+        target.iter().for_each(|location| origins.push((*location, Origin::Computation(node))));
+    }
+    // Zeroed upper bits
+    if let Operand::Reg(reg) = operands[0] {
+        let zeroed = match reg.get_type() {
+            RegType::Vector if encoding == Encoding::VEX => (target.len()..64).map(|i| Location::Register(Register::vector(VecRegName::ZMM, reg.get_vector().1), i)).collect(),
+            RegType::GPR if Utilities::get_gpr_size(&reg.get_gpr()) == 32 => (4..8).map(|i| Location::Register(Register::gpr(get_gpr_alias_info(&reg.get_gpr()).0), i)).collect(),
+            _ => vec![],
+        };
+        zeroed.into_iter().for_each(|location| origins.push((location, Origin::Zero)));
     }
+    provenance.set_origins(origins);
 }
 
-fn vmovapd(cpu: Arc<Mutex<CPU>>, operands: Vec<Operand>, vrt: HashMap<(VecRegName, usize), ValueType>) {
-    mov_common(cpu, operands, vrt);
+fn vzeroupper(cpu: Arc<Mutex<CPU>>) {
+    let mut cpu = cpu.lock().unwrap();
+    (0..16).for_each(|i| {
+        let mut v = cpu.registers.get_by_sections::<u128>(VecRegName::ZMM, i).unwrap();
+        v.iter_mut().skip(1).for_each(|x| *x = 0u128);
+        cpu.registers.set_by_sections(VecRegName::ZMM, i, v);
+    });
 }
 
-fn vfmadd213pd(cpu: Arc<Mutex<CPU>>, operands: Vec<Operand>, _vrt: HashMap<(VecRegName, usize), ValueType>) {
-    //TODO: make it to a common function
-    if operands.len() != 4 { return; }
-    let target = operands[0].clone();
-    let source1 = operands[1].clone();
-    let source2 = operands[2].clone();
-    let source3 = operands[3].clone();
-    if let (Operand::Reg(dst), Operand::Reg(src1), Operand::Reg(src2), Operand::Reg(src3)) =
-        (target, source1, source2, source3) {
-        if dst.get_type() == RegType::Vector && src1.get_type() == RegType::Vector && src2.get_type() == RegType::Vector && src3.get_type() == RegType::Vector {
-            let mut cpu = cpu.lock().unwrap();
-            let v1 = cpu.registers.get_by_sections::<u64>(src1.get_vector().0, src1.get_vector().1).unwrap();
-            let v2 = cpu.registers.get_by_sections::<u64>(src2.get_vector().0, src2.get_vector().1).unwrap();
-            let v3 = cpu.registers.get_by_sections::<u64>(src3.get_vector().0, src3.get_vector().1).unwrap();
-            let dv: Vec<u64> = v1.iter().zip(v2.iter()).map(|(x, y)| { (*x).fmul(*y) }).collect();
-            let dv: Vec<u64> = dv.iter().zip(v3.iter()).map(|(x, y)| { (*x).fadd(*y) }).collect();
-            cpu.registers.set_by_sections(dst.get_vector().0, dst.get_vector().1, dv);
-        }
-    }
+fn vzeroall(cpu: Arc<Mutex<CPU>>) {
+    let mut cpu = cpu.lock().unwrap();
+    (0..16).for_each(|i| {
+        cpu.registers.set_by_sections(VecRegName::ZMM, i, vec![0u128; 4]);
+    });
 }
 
-fn vbroadcastsd(cpu: Arc<Mutex<CPU>>, operands: Vec<Operand>, _vrt: HashMap<(VecRegName, usize), ValueType>) {
-    //TODO: make it to a common function
-    if operands.len() != 6 { return; }
-    let target = operands[0].clone();
-    let source = operands[1].clone();
-    if let (Operand::Reg(dst), Operand::Reg(src)) =
-        (target, source) {
-        if dst.get_type() == RegType::Vector && src.get_type() == RegType::Vector && dst.get_vector().0 == VecRegName::YMM && src.get_vector().0 == VecRegName::XMM {
-            let mut cpu = cpu.lock().unwrap();
-            let v = cpu.registers.get_by_sections::<u64>(src.get_vector().0, src.get_vector().1).unwrap();
-            let v = v[0];
-            cpu.registers.set_by_sections(dst.get_vector().0, dst.get_vector().1, vec![v; 4]);
-        }
+fn animate(kind: InstructionKind, odd: Vec<(Operand, LayoutLocation, (usize, usize))>, cpu: Arc<Mutex<CPU>>, vrt: HashMap<(VecRegName, usize), ValueType>, is_broadcast: bool) -> Vec<(Vec<ElementAnimationData>, bool)> {
+    match kind {
+        InstructionKind::Defined(def) => definition::animate(def, odd, cpu, vrt, is_broadcast),
+        _ => vec![(vec![], false)],
     }
 }
 
-fn get_values_from_register(reg: Register, cpu: Arc<Mutex<CPU>>, vrt: HashMap<(VecRegName, usize), ValueType>) -> Vec<Value> {
-    match reg.get_type() {
-        RegType::GPR => {
-            let cpu = cpu.lock().unwrap();
-            vec![create_value(cpu.registers.get_gpr_value(reg.get_gpr()))]
+fn get_trace_targets(kind: InstructionKind, operands: &[Operand], broadcast: Option<usize>) -> (Vec<u64>, Vec<(TraceTarget, bool, bool)>) {
+    // Effective addresses and (target, is_read, is_written) of every operand; operand 0 is the destination
+    let def = match kind {
+        InstructionKind::Defined(def) => Some(def),
+        _ => None,
+    };
+    let mut addresses = vec![];
+    let mut targets: Vec<(TraceTarget, bool, bool)> = vec![];
+    let mut add = |target: TraceTarget, is_read: bool, is_written: bool| {
+        match targets.iter_mut().find(|(t, _, _)| *t == target) {
+            Some(t) => {
+                t.1 |= is_read;
+                t.2 |= is_written;
+            }
+            None => targets.push((target, is_read, is_written)),
         }
-        RegType::Vector => {
-            let cpu = cpu.lock().unwrap();
-            match vrt.get(&reg.get_vector()).unwrap() {
-                ValueType::U8 => create_values(cpu.registers.get_by_sections::<u8>(reg.get_vector().0, reg.get_vector().1).unwrap()),
-                ValueType::U16 => create_values(cpu.registers.get_by_sections::<u16>(reg.get_vector().0, reg.get_vector().1).unwrap()),
-                ValueType::U32 => create_values(cpu.registers.get_by_sections::<u32>(reg.get_vector().0, reg.get_vector().1).unwrap()),
-                ValueType::U64 => create_values(cpu.registers.get_by_sections::<u64>(reg.get_vector().0, reg.get_vector().1).unwrap()),
-                ValueType::U128 => create_values(cpu.registers.get_by_sections::<u128>(reg.get_vector().0, reg.get_vector().1).unwrap()),
-                ValueType::U256 => create_values(cpu.registers.get_by_sections::<u256>(reg.get_vector().0, reg.get_vector().1).unwrap()),
-                ValueType::U512 => create_values(cpu.registers.get_by_sections::<u512>(reg.get_vector().0, reg.get_vector().1).unwrap()),
-                ValueType::F32 => create_values(Utilities::u32vec_to_f32vec(cpu.registers.get_by_sections::<u32>(reg.get_vector().0, reg.get_vector().1).unwrap())),
-                ValueType::F64 => create_values(Utilities::u64vec_to_f64vec(cpu.registers.get_by_sections::<u64>(reg.get_vector().0, reg.get_vector().1).unwrap())),
+    };
+    operands.iter().enumerate().for_each(|(i, operand)| {
+        match operand {
+            Operand::Reg(reg) => add(TraceTarget::Register(*reg), i != 0, i == 0),
+            Operand::Mem(addr) => {
+                addresses.push(*addr);
+                let width = def.and_then(|def| def.get_memory_size(operands, broadcast.is_some())).unwrap_or(8);
+                let is_written = i == 0 && def.map_or(true, |def| def.is_target_written());
+                add(TraceTarget::Memory(*addr, width), !is_written, is_written);
             }
+            Operand::Imm(_) => {}
         }
-        RegType::None => vec![],
+    });
+    if matches!(kind, InstructionKind::Compare) || def.is_some_and(|def| def.flags != FlagsEffect::None) {
+        add(TraceTarget::Flags, false, true);
     }
+    match def.map(|def| def.mxcsr) {
+        Some(MxcsrEffect::Load) => add(TraceTarget::Mxcsr, false, true),
+        Some(MxcsrEffect::Store) => add(TraceTarget::Mxcsr, true, false),
+        _ => {}
+    }
+    (addresses, targets)
 }
 
-macro_rules! create_calc_common_animation {
-    ($func_name:ident, $calc:literal) => {
-        fn $func_name(cpu: Arc<Mutex<CPU>>, vrt: HashMap<(VecRegName, usize), ValueType>,
-                                target: (Operand, LayoutLocation, (usize, usize)),
-                                source1: (Operand, LayoutLocation, (usize, usize)),
-                                source2: (Operand, LayoutLocation, (usize, usize))) -> Vec<(Vec<ElementAnimationData>, bool)>
-        {
-            return match target.0 {
-                Operand::Reg(tgt) => {
-                    if let (Operand::Reg(src1), Operand::Reg(src2)) = (source1.0.clone(), source2.0.clone()) {
-                        if src1.get_type() == src2.get_type() {
-                            let s1v = get_values_from_register(src1, cpu.clone(), vrt.clone());
-                            let s2v = get_values_from_register(src2, cpu.clone(), vrt.clone());
-                            let s1l = s1v.len();
-                            let s2l = s2v.len();
-                            let _l = s1l + s2l;
-                            let mut v1 = vec![];
-                            for i in 0..s1l {
-                                add_animation_data!(v1; src1, source1.1, if source1.1 == LayoutLocation::TOP {source1.2.0} else {source1.2.1}, i,
-                                    tgt, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                                    |_| {});
-                            }
-                            let mut v2 = vec![];
-                            for i in 0..s2l {
-                                let s1v_c = s1v.clone();
-                                let s2v_c = s2v.clone();
-                                add_animation_data!(v2; src2, source2.1, if source2.1 == LayoutLocation::TOP {source2.2.0} else {source2.2.1}, i,
-                                    tgt, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                                    move |e| {e.set_string(format!("{} {} {}", s1v_c[i], $calc, s2v_c[i]))});
-                            }
-                            let mut v3 = vec![];
-                            for i in 0..s2l {
-                                add_animation_data!(v3;
-                                    tgt, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                                    tgt, LayoutLocation::None, 0, i, |_| {});
-                            }
-                            return vec![(v1, false), (v2, false), (v3, false)];
-                        }
-                    } else if let (Operand::Reg(src1), Operand::Imm(src2)) = (source1.0.clone(), source2.0.clone()) {
-                        if src1.get_type() == RegType::GPR {
-                            let mut v1 = vec![];
-                            add_register_group_animation_data!(v1;
-                                src1, source1.1, if source1.1 == LayoutLocation::TOP {source1.2.0} else {source1.2.1},
-                                tgt, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1},
-                                move |e| {e.set_string(format!("{} {} {}", e.get_value(), $calc, src2))});
-                            let mut v2 = vec![];
-                            add_register_group_animation_data!(v2;
-                                tgt, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1},
-                                tgt, LayoutLocation::None, 0, |_| {});
-                            return vec![(v1, false), (v2, false)];
-                        }
-                    } else if let (Operand::Reg(_src1), Operand::Mem(_src2)) = (source1.0.clone(), source2.0.clone()) {
-                        todo!()
-                    }
-                    // Error
-                    vec![(vec![], false)]
-                }
-                _ => vec![(vec![], false)],
-            }
-        }
-    };
+pub fn get_memory_operands(instruction: &str, cpu: Arc<Mutex<CPU>>) -> Vec<usize> {
+    // Addresses an instruction may touch, resolved against the current registers
+    let (_, mut operands) = split_instruction(instruction);
+    if operands.last().and_then(|operand| RoundingMode::from_embedded(operand)).is_some() {
+        operands.pop();
+    }
+    create_operands(operands, cpu).unwrap_or_default().into_iter().filter_map(|operand| match operand {
+        Operand::Mem(addr) => Some(addr as usize),
+        _ => None,
+    }).collect()
 }
 
-create_calc_common_animation!(add_common_animation, "+");
-create_calc_common_animation!(mul_common_animation, "*");
-
-fn vaddps_animation(odd: Vec<(Operand, LayoutLocation, (usize, usize))>, cpu: Arc<Mutex<CPU>>, vrt: HashMap<(VecRegName, usize), ValueType>) -> Vec<(Vec<ElementAnimationData>, bool)> {
-    if odd.len() != 3 { return vec![(vec![], false)]; }
-    add_common_animation(cpu, vrt, odd[0].clone(), odd[1].clone(), odd[2].clone())
+pub fn get_written_element(opcode: &str) -> Option<ValueType> {
+    // Element type an instruction writes its destination in, e.g. U64 for vmovapd
+    definition::find(opcode).filter(|def| def.is_target_written()).map(|def| def.element)
 }
 
-fn vpaddd_animation(odd: Vec<(Operand, LayoutLocation, (usize, usize))>, cpu: Arc<Mutex<CPU>>, vrt: HashMap<(VecRegName, usize), ValueType>) -> Vec<(Vec<ElementAnimationData>, bool)> {
-    if odd.len() != 3 { return vec![(vec![], false)]; }
-    add_common_animation(cpu, vrt, odd[0].clone(), odd[1].clone(), odd[2].clone())
+#[derive(Copy, Clone)]
+enum InstructionKind {
+    Compare,
+    ZeroUpper,
+    ZeroAll,
+    Defined(&'static InstructionDef),
 }
 
-fn vmulpd_animation(odd: Vec<(Operand, LayoutLocation, (usize, usize))>, cpu: Arc<Mutex<CPU>>, vrt: HashMap<(VecRegName, usize), ValueType>) -> Vec<(Vec<ElementAnimationData>, bool)> {
-    if odd.len() != 3 { return vec![(vec![], false)]; }
-    mul_common_animation(cpu, vrt, odd[0].clone(), odd[1].clone(), odd[2].clone())
+#[derive(Clone)]
+pub struct Decoded {
+    pub opcode: String,
+    pub instruction: String,
+    operands: Vec<Operand>, // expanded: a target that is also read is listed twice
+    embedded_rounding: Option<RoundingMode>,
+    broadcast: Option<usize>, // lanes the element of a `{1toN}` memory operand is repeated in
+    kind: InstructionKind,
+    encoding: Encoding,
 }
 
-fn add_animation(odd: Vec<(Operand, LayoutLocation, (usize, usize))>, cpu: Arc<Mutex<CPU>>, vrt: HashMap<(VecRegName, usize), ValueType>) -> Vec<(Vec<ElementAnimationData>, bool)> {
-    if odd.len() != 3 { return vec![(vec![], false)]; }
-    add_common_animation(cpu, vrt, odd[0].clone(), odd[1].clone(), odd[2].clone())
+#[derive(Default)]
+pub struct StepEvent {
+    pub written: Vec<Register>,
+    pub exceptions: Vec<(usize, usize, u32)>, // lane exceptions of the destination
 }
 
-fn valignd_animation(odd: Vec<(Operand, LayoutLocation, (usize, usize))>, cpu: Arc<Mutex<CPU>>, vrt: HashMap<(VecRegName, usize), ValueType>) -> Vec<(Vec<ElementAnimationData>, bool)> {
-    if odd.len() != 4 { return vec![(vec![], false)]; }
-    let target = odd[0].clone();
-    let source1 = odd[1].clone();
-    let source2 = odd[2].clone();
-    let imm = odd[3].clone();
-    if let  (Operand::Reg(dst), Operand::Reg(src1), Operand::Reg(src2), Operand::Imm(imm8))
-        = (target.0, source1.0, source2.0, imm.0) {
-        if imm8 < 16 {
-            let s1v = get_values_from_register(src1, cpu.clone(), vrt.clone());
-            let s2v = get_values_from_register(src2, cpu.clone(), vrt.clone());
-            if s1v.len() == 16 && s2v.len() == 16 {
-                let _s1v = Vec::from(&s1v[..imm8 as usize]); // high in result
-                let _s2v = Vec::from(&s2v[imm8 as usize..]); // low in result
-                let mut v1 = vec![];
-                let mut j = 0;
-                for i in imm8 as usize..16 {
-                    // s2v
-                    add_animation_data!(v1; src2, source2.1, if source2.1 == LayoutLocation::TOP {source2.2.0} else {source2.2.1}, i,
-                        dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, j,
-                        |_| {});
-                    j += 1;
-                }
-                for i in 0..imm8 as usize {
-                    // s1v
-                    add_animation_data!(v1; src1, source1.1, if source1.1 == LayoutLocation::TOP {source1.2.0} else {source1.2.1}, i,
-                        dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, j,
-                        |_| {});
-                    j += 1;
-                }
-                let mut v2 = vec![];
-                for i in 0..16 {
-                    add_animation_data!(v2;
-                        dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                        dst, LayoutLocation::None, 0, i, |_| {});
-                }
-                return vec![(v1, false), (v2, false)];
-            }
-        }
-    }
-    vec![(vec![], false)]
-}
-
-fn unpack_animation(odd: Vec<(Operand, LayoutLocation, (usize, usize))>, _cpu: Arc<Mutex<CPU>>, _vrt: HashMap<(VecRegName, usize), ValueType>, is_high: bool) -> Vec<(Vec<ElementAnimationData>, bool)> {
-    let target = odd[0].clone();
-    let source1 = odd[1].clone();
-    let source2 = odd[2].clone();
-    if let  (Operand::Reg(dst), Operand::Reg(src1), Operand::Reg(src2))
-        = (target.0, source1.0, source2.0) {
-        if dst.get_type() == RegType::Vector && src1.get_type() == RegType::Vector && src2.get_type() == RegType::Vector &&
-            dst.get_vector().0 == src1.get_vector().0 && dst.get_vector().0 == src2.get_vector().0 {
-            match dst.get_vector().0 {
-                VecRegName::XMM => {todo!()}
-                VecRegName::YMM => {
-                    let mut v1 = vec![];
-                    let sv: Vec<usize> = if is_high {
-                        vec![2, 2, 3, 3, 6, 6, 7, 7]
-                    } else {
-                        vec![0, 0, 1, 1, 4, 4, 5, 5]
-                    };
-                    (0usize..8).for_each(|i| {
-                        if i % 2 == 0 {
-                            add_animation_data!(v1; src1, source1.1, if source1.1 == LayoutLocation::TOP {source1.2.0} else {source1.2.1}, sv[i],
-                                dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                                |_| {});
-                        } else {
-                            add_animation_data!(v1; src2, source2.1, if source2.1 == LayoutLocation::TOP {source2.2.0} else {source2.2.1}, sv[i],
-                                dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                                |_| {});
-                        }
-                    });
-                    let mut v2 = vec![];
-                    for i in 0..8 {
-                        add_animation_data!(v2;
-                            dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                            dst, LayoutLocation::None, 0, i, |_| {});
-                    }
-                    return vec![(v1, false), (v2, false)];
-                }
-                VecRegName::ZMM => {todo!()}
-            }
-        }
+pub fn decode(instruction: &str, cpu: Arc<Mutex<CPU>>) -> Result<Decoded, String> {
+    // Parse operands and opcode
+    let (opcode, mut operands) = split_instruction(instruction);
+    // AVX-512 embedded rounding, e.g. `vaddps zmm0, zmm1, zmm2, {rz-sae}`
+    let embedded_rounding = operands.last().and_then(|operand| RoundingMode::from_embedded(operand));
+    if embedded_rounding.is_some() {
+        operands.pop();
     }
-    vec![(vec![], false)]
-}
-
-fn vunpcklps_animation(odd: Vec<(Operand, LayoutLocation, (usize, usize))>, cpu: Arc<Mutex<CPU>>, vrt: HashMap<(VecRegName, usize), ValueType>) -> Vec<(Vec<ElementAnimationData>, bool)> {
-    if odd.len() != 3 { return vec![(vec![], false)]; }
-    unpack_animation(odd, cpu, vrt, false)
-}
-
-fn vunpckhps_animation(odd: Vec<(Operand, LayoutLocation, (usize, usize))>, cpu: Arc<Mutex<CPU>>, vrt: HashMap<(VecRegName, usize), ValueType>) -> Vec<(Vec<ElementAnimationData>, bool)> {
-    if odd.len() != 3 { return vec![(vec![], false)]; }
-    unpack_animation(odd, cpu, vrt, true)
-}
-
-fn vshufps_animation(odd: Vec<(Operand, LayoutLocation, (usize, usize))>, _cpu: Arc<Mutex<CPU>>, _vrt: HashMap<(VecRegName, usize), ValueType>) -> Vec<(Vec<ElementAnimationData>, bool)> {
-    if odd.len() != 4 { return vec![(vec![], false)]; }
-    let target = odd[0].clone();
-    let source1 = odd[1].clone();
-    let source2 = odd[2].clone();
-    let imm = odd[3].clone();
-    if let  (Operand::Reg(dst), Operand::Reg(src1), Operand::Reg(src2), Operand::Imm(imm8))
-        = (target.0, source1.0, source2.0, imm.0) {
-        if dst.get_type() == RegType::Vector && src1.get_type() == RegType::Vector && src2.get_type() == RegType::Vector &&
-            dst.get_vector().0 == src1.get_vector().0 && dst.get_vector().0 == src2.get_vector().0 {
-            let mut imm8v = vec![];
-            [0u8, 2, 4, 6].iter().for_each(|i| {
-                let tmp = imm8 as u8 >> i;
-                imm8v.push((tmp & 0b00000011) as usize);
-            });
-            match dst.get_vector().0 {
-                VecRegName::XMM => {todo!()}
-                VecRegName::YMM => {
-                    let mut v1 = vec![];
-                    add_animation_data!(v1; src1, source1.1, if source1.1 == LayoutLocation::TOP {source1.2.0} else {source1.2.1}, imm8v[0],
-                        dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, 0,
-                        |_| {});
-                    add_animation_data!(v1; src1, source1.1, if source1.1 == LayoutLocation::TOP {source1.2.0} else {source1.2.1}, imm8v[1],
-                        dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, 1,
-                        |_| {});
-                    add_animation_data!(v1; src2, source2.1, if source2.1 == LayoutLocation::TOP {source2.2.0} else {source2.2.1}, imm8v[2],
-                        dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, 2,
-                        |_| {});
-                    add_animation_data!(v1; src2, source2.1, if source2.1 == LayoutLocation::TOP {source2.2.0} else {source2.2.1}, imm8v[3],
-                        dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, 3,
-                        |_| {});
-                    add_animation_data!(v1; src1, source1.1, if source1.1 == LayoutLocation::TOP {source1.2.0} else {source1.2.1}, imm8v[0] + 4,
-                        dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, 4,
-                        |_| {});
-                    add_animation_data!(v1; src1, source1.1, if source1.1 == LayoutLocation::TOP {source1.2.0} else {source1.2.1}, imm8v[1] + 4,
-                        dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, 5,
-                        |_| {});
-                    add_animation_data!(v1; src2, source2.1, if source2.1 == LayoutLocation::TOP {source2.2.0} else {source2.2.1}, imm8v[2] + 4,
-                        dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, 6,
-                        |_| {});
-                    add_animation_data!(v1; src2, source2.1, if source2.1 == LayoutLocation::TOP {source2.2.0} else {source2.2.1}, imm8v[3] + 4,
-                        dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, 7,
-                        |_| {});
-                    let mut v2 = vec![];
-                    for i in 0..8 {
-                        add_animation_data!(v2;
-                            dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                            dst, LayoutLocation::None, 0, i, |_| {});
-                    }
-                    return vec![(v1, false), (v2, false)];
-                }
-                VecRegName::ZMM => {todo!()}
-            }
-        }
+    let kind = match opcode.as_str() {
+        "cmp" => InstructionKind::Compare,
+        "vzeroupper" => InstructionKind::ZeroUpper,
+        "vzeroall" => InstructionKind::ZeroAll,
+        _ => match definition::find(&opcode) {
+            Some(def) => InstructionKind::Defined(def),
+            None => return Err(format!("Unsupport opcode: {}", opcode)),
+        },
+    };
+    // AVX-512 embedded broadcast, e.g. `vaddps zmm0, zmm1, dword ptr [rax]{1to16}`
+    if let Some(operand) = operands.iter().find(|operand| operand.contains("]{") && get_broadcast(operand).is_none()) {
+        return Err(format!("Invalid broadcast: {}", operand));
     }
-    vec![(vec![], false)]
-}
-
-fn vperm2f128_animation(odd: Vec<(Operand, LayoutLocation, (usize, usize))>, _cpu: Arc<Mutex<CPU>>, vrt: HashMap<(VecRegName, usize), ValueType>) -> Vec<(Vec<ElementAnimationData>, bool)> {
-    if odd.len() != 4 { return vec![(vec![], false)]; }
-    let target = odd[0].clone();
-    let source1 = odd[1].clone();
-    let source2 = odd[2].clone();
-    let imm = odd[3].clone();
-    if let  (Operand::Reg(dst), Operand::Reg(src1), Operand::Reg(src2), Operand::Imm(imm8))
-        = (target.0, source1.0, source2.0, imm.0) {
-        if dst.get_type() == RegType::Vector && src1.get_type() == RegType::Vector && src2.get_type() == RegType::Vector &&
-            dst.get_vector().0 == src1.get_vector().0 && dst.get_vector().0 == src2.get_vector().0 {
-            let size1 = vrt.get(&(src1.get_vector().0, src1.get_vector().1)).unwrap().size();
-            let size2 = vrt.get(&(src2.get_vector().0, src2.get_vector().1)).unwrap().size();
-            if size1 == size2 {
-                let num = 128 / size1;
-                match dst.get_vector().0 {
-                    VecRegName::XMM => {todo!()}
-                    VecRegName::YMM => {
-                        let mut v1 = vec![];
-                        let mut dst_i = 0;
-                        [imm8 as u8 & 0b00001111, (imm8 as u8 >> 4) & 0b00001111].iter().for_each(|imm8| {
-                            match imm8 {
-                                0 => {
-                                    (0..num).for_each(|src_i| {
-                                        add_animation_data!(v1; src1, source1.1, if source1.1 == LayoutLocation::TOP {source1.2.0} else {source1.2.1}, src_i,
-                                            dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, dst_i,
-                                            |_| {});
-                                        dst_i += 1;
-                                    });
-                                }
-                                1 => {
-                                    (num..num * 2).for_each(|src_i| {
-                                        add_animation_data!(v1; src1, source1.1, if source1.1 == LayoutLocation::TOP {source1.2.0} else {source1.2.1}, src_i,
-                                            dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, dst_i,
-                                            |_| {});
-                                        dst_i += 1;
-                                    });
-                                }
-                                2 => {
-                                    (0..num).for_each(|src_i| {
-                                        add_animation_data!(v1; src2, source2.1, if source2.1 == LayoutLocation::TOP {source2.2.0} else {source2.2.1}, src_i,
-                                            dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, dst_i,
-                                            |_| {});
-                                        dst_i += 1;
-                                    });
-                                }
-                                3 => {
-                                    (num..num * 2).for_each(|src_i| {
-                                        add_animation_data!(v1; src2, source2.1, if source2.1 == LayoutLocation::TOP {source2.2.0} else {source2.2.1}, src_i,
-                                            dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, dst_i,
-                                            |_| {});
-                                        dst_i += 1;
-                                    });
-                                }
-                                _ if (imm8 >> 3) & 0b00000001 == 1 => {
-                                    (0..num).for_each(|src_i| {
-                                        add_animation_data!(v1; dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, src_i,
-                                            dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, dst_i,
-                                            |e| {e.set_string("0->".into())});
-                                        dst_i += 1;
-                                    });
-                                }
-                                _ => {}
-                            }
-                        });
-                        let mut v2 = vec![];
-                        for i in 0..8 {
-                            add_animation_data!(v2;
-                                dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                                dst, LayoutLocation::None, 0, i, |_| {});
-                        }
-                        return vec![(v1, false), (v2, false)];
-                    }
-                    VecRegName::ZMM => {todo!()}
-                }
-            }
+    let broadcast = operands.iter().find_map(|operand| get_broadcast(operand));
+    let mut sizes = operands.iter().map(|operand| get_ptr_size(operand)).collect::<Result<Vec<Option<usize>>, String>>()?;
+    // The size of a broadcast is one element, not the form's memory operand
+    let broadcast_size = operands.iter().position(|operand| get_broadcast(operand).is_some()).and_then(|i| sizes[i].take());
+    let mut operands = create_operands(operands, cpu)?;
+    forms::check(&opcode, &operands, &sizes)?;
+    if let Some(count) = broadcast {
+        match kind {
+            InstructionKind::Defined(def) => def.check_broadcast(&operands, count, broadcast_size)?,
+            _ => return Err(format!("{} does not support embedded broadcast", opcode)),
         }
     }
-    vec![(vec![], false)]
-}
-
-fn vextractf128_animation(odd: Vec<(Operand, LayoutLocation, (usize, usize))>, _cpu: Arc<Mutex<CPU>>, vrt: HashMap<(VecRegName, usize), ValueType>) -> Vec<(Vec<ElementAnimationData>, bool)> {
-    if odd.len() != 3 { return vec![(vec![], false)]; }
-    let target = odd[0].clone();
-    let source = odd[1].clone();
-    let imm = odd[2].clone();
-    if let  (Operand::Reg(dst), Operand::Reg(src), Operand::Imm(imm8))
-        = (target.0, source.0, imm.0) {
-        if dst.get_type() == RegType::Vector && src.get_type() == RegType::Vector &&
-            dst.get_vector().0 == VecRegName::XMM && src.get_vector().0 == VecRegName::YMM {
-            if vrt.get(&dst.get_vector()).unwrap() == vrt.get(&src.get_vector()).unwrap() {
-                let t_size = vrt.get(&dst.get_vector()).unwrap().size();
-                let xmm_num = 128 / t_size;
-                let _ymm_num = 256 / t_size;
-                let mut v1 = vec![];
-                if imm8 as u8 & 0b00000001 == 1 {
-                    (0..xmm_num).for_each(|i| {
-                        add_animation_data!(v1; src, source.1, if source.1 == LayoutLocation::TOP {source.2.0} else {source.2.1}, i + xmm_num,
-                            dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                            |_| {});
-                    });
-                } else {
-                    (0..xmm_num).for_each(|i| {
-                        add_animation_data!(v1; src, source.1, if source.1 == LayoutLocation::TOP {source.2.0} else {source.2.1}, i,
-                            dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                            |_| {});
-                    });
-                }
-                let mut v2 = vec![];
-                for i in 0..xmm_num {
-                    add_animation_data!(v2;
-                        dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                        dst, LayoutLocation::None, 0, i, |_| {});
-                }
-                return vec![(v1, false), (v2, false)];
-            }
+    if embedded_rounding.is_some() {
+        match kind {
+            InstructionKind::Defined(def) => def.check_embedded_rounding(&operands)?,
+            _ => return Err(format!("{} does not support embedded rounding", opcode)),
         }
     }
-    vec![(vec![], false)]
-}
-
-fn shufpd_animation(odd: Vec<(Operand, LayoutLocation, (usize, usize))>, _cpu: Arc<Mutex<CPU>>, vrt: HashMap<(VecRegName, usize), ValueType>) -> Vec<(Vec<ElementAnimationData>, bool)> {
-    if odd.len() != 4 { return vec![(vec![], false)]; }
-    let target = odd[0].clone();
-    let source1 = odd[1].clone();
-    let source2 = odd[2].clone();
-    let imm = odd[3].clone();
-    if let  (Operand::Reg(dst), Operand::Reg(src1), Operand::Reg(src2), Operand::Imm(imm8))
-        = (target.0, source1.0, source2.0, imm.0) {
-        if dst.get_type() == RegType::Vector && src1.get_type() == RegType::Vector && src2.get_type() == RegType::Vector &&
-            dst.get_vector().0 == VecRegName::XMM && dst.get_vector().0 == src1.get_vector().0 && dst.get_vector().0 == src2.get_vector().0 {
-            if vrt.get(&dst.get_vector()).unwrap() == vrt.get(&src1.get_vector()).unwrap() &&
-                vrt.get(&dst.get_vector()).unwrap() == vrt.get(&src2.get_vector()).unwrap() {
-                let size = vrt.get(&dst.get_vector()).unwrap().size();
-                let num = 128 / size;
-                let mut v1 = vec![];
-                if imm8 as u8 & 0b00000001 == 0 {
-                    (0..num / 2).for_each(|i| {
-                        add_animation_data!(v1; src1, source1.1, if source1.1 == LayoutLocation::TOP {source1.2.0} else {source1.2.1}, i,
-                            dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                            |_| {});
-                    });
-                } else {
-                    (0..num / 2).for_each(|i| {
-                        add_animation_data!(v1; src1, source1.1, if source1.1 == LayoutLocation::TOP {source1.2.0} else {source1.2.1}, i + num / 2,
-                            dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                            |_| {});
-                    });
-                }
-                if (imm8 as u8 >> 1) & 0b00000001 == 0 {
-                    (num / 2..num).for_each(|i| {
-                        add_animation_data!(v1; src2, source2.1, if source2.1 == LayoutLocation::TOP {source2.2.0} else {source2.2.1}, i - num / 2,
-                            dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                            |_| {});
-                    });
-                } else {
-                    (num / 2..num).for_each(|i| {
-                        add_animation_data!(v1; src2, source2.1, if source2.1 == LayoutLocation::TOP {source2.2.0} else {source2.2.1}, i,
-                            dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                            |_| {});
-                    });
-                }
-                let mut v2 = vec![];
-                for i in 0..num {
-                    add_animation_data!(v2;
-                        dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                        dst, LayoutLocation::None, 0, i, |_| {});
-                }
-                return vec![(v1, false), (v2, false)];
-            }
+    if matches!(kind, InstructionKind::Defined(def) if def.is_target_read()) {
+        if let Some(target) = operands.first() {
+            operands.insert(0, target.clone());
         }
     }
-    vec![(vec![], false)]
-}
-
-fn mov_common_animation(odd: Vec<(Operand, LayoutLocation, (usize, usize))>, cpu: Arc<Mutex<CPU>>, vrt: HashMap<(VecRegName, usize), ValueType>) -> Vec<(Vec<ElementAnimationData>, bool)> {
-    if odd.len() != 2 { return vec![(vec![], false)]; }
-    let target = odd[0].clone();
-    let source = odd[1].clone();
-    match target.0 {
-        Operand::Reg(dst) => {
-            match source.0 {
-                Operand::Reg(_src) => {
-                    // reg -> reg
-                    // TODO
-                }
-                Operand::Mem(src) => {
-                    // mem -> reg
-                    let cpu = cpu.lock().unwrap();
-                    if dst.get_type() == RegType::GPR {
-                        // mem -> gpr TODO
-                        let v = cpu.memory.read::<u64>(src as usize);
-                        let mut v1 = vec![];
-                        add_animation_data!(v1; dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, 0,
-                            dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, 0,
-                            move |e| {e.set_string(format!("{}->", v))});
-                        let mut v2 = vec![];
-                        add_animation_data!(v2;
-                            dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, 0,
-                            dst, LayoutLocation::None, 0, 0, |_| {});
-                        return vec![(v1, false), (v2, false)];
-                    } else if dst.get_type() == RegType::Vector {
-                        // mem -> vector
-                        let size = vrt.get(&dst.get_vector()).unwrap();
-                        let num = match dst.get_vector().0 {
-                            VecRegName::XMM => 128 / size.size(),
-                            VecRegName::YMM => 256 / size.size(),
-                            VecRegName::ZMM => 512 / size.size(),
-                        };
-                        let values = match size {
-                            ValueType::U8 => create_values(cpu.memory.read_vec::<u8>(src as usize, num)),
-                            ValueType::U16 => create_values(cpu.memory.read_vec::<u16>(src as usize, num)),
-                            ValueType::U32 => create_values(cpu.memory.read_vec::<u32>(src as usize, num)),
-                            ValueType::U64 => create_values(cpu.memory.read_vec::<u64>(src as usize, num)),
-                            ValueType::U128 => create_values(cpu.memory.read_vec::<u128>(src as usize, num)),
-                            ValueType::U256 => create_values(cpu.memory.read_vec::<u256>(src as usize, num)),
-                            ValueType::U512 => create_values(cpu.memory.read_vec::<u512>(src as usize, num)),
-                            ValueType::F32 => create_values(Utilities::u32vec_to_f32vec(cpu.memory.read_vec::<u32>(src as usize, num))),
-                            ValueType::F64 => create_values(Utilities::u64vec_to_f64vec(cpu.memory.read_vec::<u64>(src as usize, num))),
-                        };
-                        let mut v1 = vec![];
-                        values.iter().enumerate().for_each(|(i, v)| {
-                            let v = v.clone();
-                            add_animation_data!(v1; dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                                dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                                move |e| {e.set_string(format!("{}->", v))});
-                        });
-                        let mut v2 = vec![];
-                        (0..values.len()).for_each(|i| {
-                            add_animation_data!(v2;
-                                dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                                dst, LayoutLocation::None, 0, i, |_| {});
-                        });
-                        return vec![(v1, false), (v2, false)];
+    let encoding = Encoding::from_opcode(&opcode);
+    Ok(Decoded { opcode, instruction: split_comment(instruction).0.to_string(), operands, embedded_rounding, broadcast, kind, encoding })
+}
+
+pub fn apply(cpu: Arc<Mutex<CPU>>, state: &mut MachineState, decoded: &Decoded) -> StepEvent {
+    // Instruction semantics only, no GUI involved
+    let operands = &decoded.operands;
+    let MachineState { mxcsr, trace, provenance } = state;
+    match decoded.kind {
+        InstructionKind::Compare => {
+            let (addresses, mut targets) = get_trace_targets(decoded.kind, operands, None);
+            targets.iter_mut().filter(|(target, _, _)| *target != TraceTarget::Flags).for_each(|(_, is_read, is_written)| {
+                (*is_read, *is_written) = (true, false);
+            });
+            trace.record(&cpu, mxcsr, &decoded.instruction, addresses, targets, |_| {
+                if let (Operand::Reg(r1), Operand::Reg(r2)) = (operands[0].clone(), operands[1].clone()) {
+                    let mut cpu = cpu.lock().unwrap();
+                    let v1 = cpu.registers.get_gpr_value(r1.get_gpr());
+                    let v2 = cpu.registers.get_gpr_value(r2.get_gpr());
+                    let mut f = cpu.registers.get_flags_value(FLAGSName::RFLAGS);
+                    if v1 == v2 {
+                        f |= 0b1000000;
+                        cpu.registers.set_flags_value(FLAGSName::RFLAGS, f);
+                    } else {
+                        f &= 0xFFFFFFBF;
+                        cpu.registers.set_flags_value(FLAGSName::RFLAGS, f);
+                    }
+                } else if let (Operand::Reg(r), Operand::Imm(imm)) = (operands[0].clone(), operands[1].clone()) {
+                    let mut cpu = cpu.lock().unwrap();
+                    let v = cpu.registers.get_gpr_value(r.get_gpr());
+                    let mut f = cpu.registers.get_flags_value(FLAGSName::RFLAGS);
+                    if v == imm {
+                        f |= 0b1000000;
+                        cpu.registers.set_flags_value(FLAGSName::RFLAGS, f);
+                    } else {
+                        f &= 0xFFFFFFBF;
+                        cpu.registers.set_flags_value(FLAGSName::RFLAGS, f);
                     }
                 }
-                Operand::Imm(_src) => {
-                    // imm -> reg(gpr)
-                    // TODO
-                }
-            }
-        }
-        Operand::Mem(_dst) => {
-            // reg -> mem
-            // if let Operand::Reg(src) = source.0 {
-            //     if src.get_type() == RegType::GPR {
-            //         // gpr -> mem
-            //         // TODO
-            //     } else if src.get_type() == RegType::Vector {
-            //         // vector -> mem
-            //         let values = get_values_from_register(src, cpu.clone(), vrt.clone());
-            //         let mut v1 = vec![];
-            //         (0..values.len()).for_each(|i| {
-            //             add_animation_data!(v1; src, source.1, if source.1 == LayoutLocation::TOP {source.2.0} else {source.2.1}, i,
-            //                 src, source.1, if source.1 == LayoutLocation::TOP {source.2.0} else {source.2.1}, i,
-            //                 |e| {e.set_string(format!("<-{}", e.get_value()))});
-            //         });
-            //         let mut v2 = vec![];
-            //         (0..values.len()).for_each(|i| {
-            //             add_animation_data!(v1; src, source.1, if source.1 == LayoutLocation::TOP {source.2.0} else {source.2.1}, i,
-            //                 src, LayoutLocation::None, 0, i, |_| {});
-            //         });
-            //         return vec![(v1, false), (v2, false)];
-            //     }
-            // }
+            });
+            StepEvent::default()
         }
-        _ => {/*ERROR*/}
-    }
-    vec![(vec![], false)]
-}
-
-fn vmovapd_animation(odd: Vec<(Operand, LayoutLocation, (usize, usize))>, cpu: Arc<Mutex<CPU>>, vrt: HashMap<(VecRegName, usize), ValueType>) -> Vec<(Vec<ElementAnimationData>, bool)> {
-    mov_common_animation(odd, cpu, vrt)
-}
-
-fn vfmadd213pd_animation(odd: Vec<(Operand, LayoutLocation, (usize, usize))>, cpu: Arc<Mutex<CPU>>, vrt: HashMap<(VecRegName, usize), ValueType>) -> Vec<(Vec<ElementAnimationData>, bool)> {
-    //TODO: make it to a common function
-    if odd.len() != 4 { return vec![(vec![], false)]; }
-    let target = odd[0].clone();
-    let source1 = odd[1].clone();
-    let source2 = odd[2].clone();
-    let source3 = odd[3].clone();
-    if let  (Operand::Reg(dst), Operand::Reg(src1), Operand::Reg(src2), Operand::Reg(src3))
-        = (target.0, source1.0, source2.0, source3.0) {
-        let vs1 = get_values_from_register(src1, cpu.clone(), vrt.clone());
-        let vs2 = get_values_from_register(src2, cpu.clone(), vrt.clone());
-        let vs3 = get_values_from_register(src3, cpu.clone(), vrt.clone());
-        let num = vs1.len();
-        let mut v1 = vec![];
-        (0..num).for_each(|i| {
-            add_animation_data!(v1; src1, source1.1, if source1.1 == LayoutLocation::TOP {source1.2.0} else {source1.2.1}, i,
-                dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                |_| {});
-        });
-        let mut v2 = vec![];
-        (0..num).for_each(|i| {
-            let vs1c = vs1.clone();
-            let vs2c = vs2.clone();
-            let ic = i.clone();
-            add_animation_data!(v2; src2, source2.1, if source2.1 == LayoutLocation::TOP {source2.2.0} else {source2.2.1}, i,
-                dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                move |e| {e.set_string(format!("({} * {})", vs1c[ic], vs2c[ic]))});
-        });
-        let mut v3 = vec![];
-        (0..num).for_each(|i| {
-            let vs1c = vs1.clone();
-            let vs2c = vs2.clone();
-            let vs3c = vs3.clone();
-            let ic = i.clone();
-            add_animation_data!(v3; src3, source3.1, if source3.1 == LayoutLocation::TOP {source3.2.0} else {source3.2.1}, i,
-                dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                move |e| {e.set_string(format!("({} * {}) + {}", vs1c[ic], vs2c[ic], vs3c[ic]))});
-        });
-        let mut v4 = vec![];
-        for i in 0..num {
-            add_animation_data!(v4;
-                dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                dst, LayoutLocation::None, 0, i, |_| {});
+        InstructionKind::ZeroUpper | InstructionKind::ZeroAll => {
+            let is_all = matches!(decoded.kind, InstructionKind::ZeroAll);
+            let targets = (0..16).map(|i| (TraceTarget::Register(vec_reg!(ZMM, i)), false, true)).collect();
+            trace.record(&cpu, mxcsr, &decoded.instruction, vec![], targets, |_| {
+                if is_all {
+                    vzeroall(cpu.clone());
+                } else {
+                    vzeroupper(cpu.clone());
+                }
+            });
+            provenance.next_step();
+            provenance.set_origins((0..16).flat_map(|i| {
+                (if is_all {0} else {16}..64).map(move |byte| (Location::Register(vec_reg!(ZMM, i), byte), Origin::Zero))
+            }).collect());
+            StepEvent { written: (0..16).map(|i| vec_reg!(ZMM, i)).collect(), exceptions: vec![] }
         }
-        return vec![(v1, false), (v2, false), (v3, false), (v4, false)];
-    }
-    vec![(vec![], false)]
-}
-
-fn vbroadcastsd_animation(odd: Vec<(Operand, LayoutLocation, (usize, usize))>, cpu: Arc<Mutex<CPU>>, vrt: HashMap<(VecRegName, usize), ValueType>) -> Vec<(Vec<ElementAnimationData>, bool)> {
-    //TODO: make it to a common function
-    if odd.len() != 6 { return vec![(vec![], false)]; }
-    let target = odd[0].clone();
-    let source1 = odd[1].clone();
-    let source2 = odd[2].clone();
-    let source3 = odd[3].clone();
-    let source4 = odd[4].clone();
-    if let (Operand::Reg(dst), Operand::Reg(src1), Operand::Reg(src2), Operand::Reg(src3), Operand::Reg(src4)) =
-        (target.0, source1.0, source2.0, source3.0, source4.0) {
-        if dst.get_type() == RegType::Vector && src1.get_type() == RegType::Vector && dst.get_vector().0 == VecRegName::YMM && src1.get_vector().0 == VecRegName::XMM {
-            let mut v1 = vec![];
-            add_animation_data!(v1; src1, source1.1, if source1.1 == LayoutLocation::TOP {source1.2.0} else {source1.2.1}, 0,
-                dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, 0,
-                |_| {});
-            add_animation_data!(v1; src2, source2.1, if source2.1 == LayoutLocation::TOP {source2.2.0} else {source2.2.1}, 0,
-                dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, 1,
-                |_| {});
-            add_animation_data!(v1; src3, source3.1, if source3.1 == LayoutLocation::TOP {source3.2.0} else {source3.2.1}, 0,
-                dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, 2,
-                |_| {});
-            add_animation_data!(v1; src4, source4.1, if source4.1 == LayoutLocation::TOP {source4.2.0} else {source4.2.1}, 0,
-                dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, 3,
-                |_| {});
-            let mut v2 = vec![];
-            for i in 0..4 {
-                add_animation_data!(v2;
-                    dst, target.1, if target.1 == LayoutLocation::TOP {target.2.0} else {target.2.1}, i,
-                    dst, LayoutLocation::None, 0, i, |_| {});
-            }
-            return vec![(v1, false), (v2, false)];
+        InstructionKind::Defined(def) => {
+            // The shadow run for provenance must not touch the flags of the real MXCSR
+            let shadow_mxcsr = mxcsr.clone();
+            let run = |cpu: Arc<Mutex<CPU>>| definition::execute(def, cpu, &mut shadow_mxcsr.clone(), operands, decoded.broadcast.is_some());
+            let encoding = decoded.encoding;
+            let (addresses, targets) = get_trace_targets(decoded.kind, operands, decoded.broadcast);
+            let mut exceptions = vec![];
+            trace.record(&cpu, mxcsr, &decoded.instruction, addresses, targets, |mxcsr| {
+                track_provenance(provenance, def, &decoded.instruction, &run, operands, encoding);
+                let upper_bits = get_upper_bits(cpu.clone(), &operands[0]);
+                mxcsr.set_embedded_rounding(decoded.embedded_rounding);
+                mxcsr.clear_lane_exceptions();
+                definition::execute(def, cpu.clone(), mxcsr, operands, decoded.broadcast.is_some());
+                mxcsr.set_embedded_rounding(None);
+                exceptions = mxcsr.take_lane_exceptions(match operands[0] {
+                    Operand::Reg(reg) if reg.get_type() == RegType::Vector => get_vec_reg_size(&reg.get_vector().0),
+                    _ => 64,
+                });
+                set_upper_bits(cpu.clone(), &operands[0], encoding, upper_bits);
+                zero_extend_gpr(cpu.clone(), &operands[0]);
+            });
+            let written = match operands[0] {
+                Operand::Reg(reg) => vec![reg],
+                _ => vec![],
+            };
+            StepEvent { written, exceptions }
         }
     }
-    vec![(vec![], false)]
-}
-
-type Func = fn(Arc<Mutex<CPU>>, Vec<Operand>, HashMap<(VecRegName, usize), ValueType>);
-type AniFunc = fn(Vec<(Operand, LayoutLocation, (usize, usize))>, Arc<Mutex<CPU>>, HashMap<(VecRegName, usize), ValueType>) -> Vec<(Vec<ElementAnimationData>, bool)>;
-
-macro_rules! new_instruction {
-    ($map:expr; $inst:expr, $target_read:expr, $duplicate_last:expr, $func:expr, $ani_func:expr) => {
-        $map.insert(String::from($inst), ($target_read, $duplicate_last, $func as Func, $ani_func as AniFunc))
-    };
 }
 
-fn create_instruction_list() -> HashMap<String, (bool, usize, Func, AniFunc)>
-{
-    let mut map = HashMap::new();
-    new_instruction!(map; "vaddps", false, 0, vaddps, vaddps_animation);
-    new_instruction!(map; "vpaddd", false, 0, vpaddd, vpaddd_animation);
-    new_instruction!(map; "valignd", false, 0, valignd, valignd_animation);
-    new_instruction!(map; "vunpcklps", false, 0, vunpcklps, vunpcklps_animation);
-    new_instruction!(map; "vunpckhps", false, 0, vunpckhps, vunpckhps_animation);
-    new_instruction!(map; "vshufps", false, 0, vshufps, vshufps_animation);
-    new_instruction!(map; "vperm2f128", false, 0, vperm2f128, vperm2f128_animation);
-    new_instruction!(map; "vextractf128", false, 0, vextractf128, vextractf128_animation);
-    new_instruction!(map; "shufpd", true, 0, shufpd, shufpd_animation);
-    new_instruction!(map; "vmulpd", false, 0, vmulpd, vmulpd_animation);
-    new_instruction!(map; "vmovapd", false, 0, vmovapd, vmovapd_animation);
-    new_instruction!(map; "add", true, 0, add, add_animation);
-    new_instruction!(map; "vfmadd213pd", true, 0, vfmadd213pd, vfmadd213pd_animation);
-    new_instruction!(map; "vbroadcastsd", false, 4, vbroadcastsd, vbroadcastsd_animation);
-    map
-}
-
-lazy_static! {
-    static ref OPCODES: HashMap<String, (bool, usize, Func, AniFunc)> = {
-        create_instruction_list()
-    };
-}
-
-pub fn execute(rv: Arc<Mutex<RegVisualizer>>, cpu: Arc<Mutex<CPU>>, fsm: &mut AnimationFSM, rvd: &RegVisualizerData, ctx: &Context, instruction: &str, with_animation: bool) {
+pub fn execute(rv: Arc<Mutex<RegVisualizer>>, cpu: Arc<Mutex<CPU>>, state: Arc<Mutex<MachineState>>, fsm: &mut AnimationFSM, rvd: &RegVisualizerData, ctx: &Context, decoded: Decoded, with_animation: bool) {
+    // Animation layer: schedules `apply` on the FSM and animates the operands around it
     // Reset register highlight
     let mut rv_lock = rv.lock().unwrap();
     rv_lock.reset_highlight();
+    let previous_values = rv_lock.get_all_values();
     drop(rv_lock);
-    // Parse operands and opcode
-    let (opcode, operands) = split_instruction(instruction);
-    let mut operands = create_operands(operands, cpu.clone());
-    // CMP Instruction
-    if opcode == "cmp" {
-        if let (Operand::Reg(r1), Operand::Reg(r2)) = (operands[0].clone(), operands[1].clone()) {
-            let mut cpu = cpu.lock().unwrap();
-            let v1 = cpu.registers.get_gpr_value(r1.get_gpr());
-            let v2 = cpu.registers.get_gpr_value(r2.get_gpr());
-            let mut f = cpu.registers.get_flags_value(FLAGSName::RFLAGS);
-            if v1 == v2 {
-                f |= 0b1000000;
-                cpu.registers.set_flags_value(FLAGSName::RFLAGS, f);
-            } else {
-                f &= 0xFFFFFFBF;
-                cpu.registers.set_flags_value(FLAGSName::RFLAGS, f);
-            }
-        } else if let (Operand::Reg(r), Operand::Imm(imm)) = (operands[0].clone(), operands[1].clone()) {
-            let mut cpu = cpu.lock().unwrap();
-            let v = cpu.registers.get_gpr_value(r.get_gpr());
-            let mut f = cpu.registers.get_flags_value(FLAGSName::RFLAGS);
-            if v == imm {
-                f |= 0b1000000;
-                cpu.registers.set_flags_value(FLAGSName::RFLAGS, f);
-            } else {
-                f &= 0xFFFFFFBF;
-                cpu.registers.set_flags_value(FLAGSName::RFLAGS, f);
-            }
+    let kind = decoded.kind;
+    match kind {
+        // CMP Instruction
+        InstructionKind::Compare => {
+            apply(cpu, &mut state.lock().unwrap(), &decoded);
+            return;
         }
-        return;
-    }
-    // Other Instructions
-    if !OPCODES.contains_key(&opcode) {
-        println!("Unsupport opcode: {}", opcode);
-        return;
-    }
-    let (is_target_read, duplicate_last, func, ani_func) = OPCODES.get(&opcode).unwrap();
-    if *is_target_read {
-        if let Some(target) = operands.first() {
-            operands.insert(0, target.clone());
+        // VZEROUPPER/VZEROALL Instructions
+        InstructionKind::ZeroUpper | InstructionKind::ZeroAll => {
+            let cpu_clone = cpu.clone();
+            let is_all = matches!(kind, InstructionKind::ZeroAll);
+            let ctx_clone = ctx.clone();
+            fsm.set_update_data(move |fsm| {
+                apply(cpu_clone, &mut state.lock().unwrap(), &decoded);
+                ctx_clone.request_repaint();
+                fsm.next();
+            });
+            fsm.set_create_layout(|fsm| {
+                fsm.next();
+            });
+            fsm.set_run_animation(|fsm| {
+                fsm.next();
+            });
+            let rv_clone = rv.clone();
+            let ctx_clone = ctx.clone();
+            fsm.set_destroy_layout(move |fsm| {
+                let mut rv = rv_clone.lock().unwrap();
+                (0..16).for_each(|i| {
+                    if is_all {
+                        [vec_reg!(XMM, i), vec_reg!(YMM, i), vec_reg!(ZMM, i)].iter().for_each(|reg| {
+                            rv.highlight_diff(reg, previous_values.get(reg));
+                        });
+                    } else {
+                        rv.highlight_upper_lanes(&vec_reg!(XMM, i), UpperLaneState::Zeroed);
+                    }
+                });
+                ctx_clone.request_repaint();
+                fsm.next();
+            });
+            fsm.start();
+            return;
         }
+        // Other Instructions
+        InstructionKind::Defined(_) => {}
     }
-    if *duplicate_last != 0 {
-        (0..*duplicate_last).for_each(|_| {
-            let tmp = operands[operands.len() - 1].clone();
-            operands.push(tmp);
-        });
-    }
-    // TODO
+    let operands = decoded.operands.clone();
+    let encoding = decoded.encoding;
+    let is_broadcast = decoded.broadcast.is_some();
     // Animation FSM
     // Update CPU data - must run update date
     let cpu_clone = cpu.clone();
-    let operands_clone = operands.clone();
-    let vrt = rvd.vector_regs_type.clone();
     let ctx_clone = ctx.clone();
+    let exceptions = Arc::new(Mutex::new(vec![]));
+    let exceptions_clone = exceptions.clone();
     fsm.set_update_data(move |fsm| {
-        func(cpu_clone, operands_clone, vrt);
+        let event = apply(cpu_clone, &mut state.lock().unwrap(), &decoded);
+        *exceptions_clone.lock().unwrap() = event.exceptions;
         ctx_clone.request_repaint();
         fsm.next();
     });
@@ -1363,7 +722,7 @@ pub fn execute(rv: Arc<Mutex<RegVisualizer>>, cpu: Arc<Mutex<CPU>>, fsm: &mut An
     let need_animation = operands.iter().all(|operand| {
         match operand {
             Operand::Reg(reg) => {
-                rvd.registers[0].contains(reg)
+                rvd.contains(reg)
             }
             _ => true,
         }
@@ -1379,11 +738,15 @@ pub fn execute(rv: Arc<Mutex<RegVisualizer>>, cpu: Arc<Mutex<CPU>>, fsm: &mut An
         let operands_clone = operands.clone();
         let rv_clone = rv.clone();
         let ctx_clone = ctx.clone();
+        let exceptions_clone = exceptions.clone();
         fsm.set_destroy_layout(move |fsm| {
             match operands_clone[0] {
                 Operand::Reg(reg) => {
                     let mut rv = rv_clone.lock().unwrap();
-                    rv.highlight(&reg);
+                    rv.highlight_diff(&reg, previous_values.get(&reg));
+                    rv.highlight_aliases(&reg);
+                    rv.highlight_upper_lanes(&reg, encoding.upper_lane_state());
+                    rv.set_exception_badges(&reg, &exceptions_clone.lock().unwrap());
                 }
                 _ => {}
             }
@@ -1394,11 +757,11 @@ pub fn execute(rv: Arc<Mutex<RegVisualizer>>, cpu: Arc<Mutex<CPU>>, fsm: &mut An
         return;
     }
     // Create location and repeat times for every operands
-    let mut reg_operand_data: HashMap<Register, (usize, LayoutLocation, (usize, usize), bool)> = HashMap::new();
+    let mut reg_operand_data: HashMap<Register, ((usize, usize), LayoutLocation, (usize, usize), bool)> = HashMap::new();
     operands.iter().for_each(|operand| {
         match operand {
             Operand::Reg(reg) => {
-                if let Some(i) = rvd.registers[0].iter().position(|r| *r == *reg) {
+                if let Some(i) = rvd.position(reg) {
                     if reg_operand_data.contains_key(reg) {
                         let mut data = reg_operand_data.get_mut(reg).unwrap();
                         data.2.1 += 1;
@@ -1481,12 +844,19 @@ pub fn execute(rv: Arc<Mutex<RegVisualizer>>, cpu: Arc<Mutex<CPU>>, fsm: &mut An
     let rv_clone = rv.clone();
     let ctx_clone = ctx.clone();
     let reg_operand_data_clone = reg_operand_data.clone();
+    let target_display_data = operand_display_data[0].clone();
     fsm.set_destroy_layout(move |fsm| {
         let mut rv = rv_clone.lock().unwrap();
+        if let (Operand::Reg(target), loc, (top, bottom)) = &target_display_data {
+            rv.keep_lane_colors(target, *loc, if *loc == LayoutLocation::TOP {*top} else {*bottom});
+        }
         rv.remove_animation_layout(&ctx_clone);
         reg_operand_data_clone.iter().for_each(|(reg, (_, loc, rn, is_target))| {
             if *is_target {
-                rv.highlight(reg);
+                rv.highlight_diff(reg, previous_values.get(reg));
+                rv.highlight_aliases(reg);
+                rv.highlight_upper_lanes(reg, encoding.upper_lane_state());
+                rv.set_exception_badges(reg, &exceptions.lock().unwrap());
             }
         });
         fsm.next();
@@ -1500,7 +870,7 @@ pub fn execute(rv: Arc<Mutex<RegVisualizer>>, cpu: Arc<Mutex<CPU>>, fsm: &mut An
     let ctx_clone = ctx.clone();
     fsm.set_run_animation(move |fsm| {
         let mut rv = rv_clone.lock().unwrap();
-        let sequence = ani_func(odd, cpu_clone, vrt);
+        let sequence = animate(kind, odd, cpu_clone, vrt, is_broadcast);
         rv.set_group_move_animation_sequence(
             Arc::new(Mutex::new(sequence))
         );
@@ -1518,3 +888,10 @@ pub fn execute(rv: Arc<Mutex<RegVisualizer>>, cpu: Arc<Mutex<CPU>>, fsm: &mut An
     // Start FSM
     fsm.start();
 }
+
+mod definition;
+mod forms;
+#[cfg(test)]
+mod tests;
+#[cfg(all(test, target_arch = "x86_64"))]
+mod differential;
@@ -0,0 +1,770 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use cpulib::{CPU, VecRegName, FLAGSName, Utilities};
+use crate::reg_visualizer::{Element, LayoutLocation};
+use crate::float16::{f16_to_f32, bf16_to_f32, f32_to_bf16_flushed, flush_f32};
+use crate::mxcsr::{Mxcsr, RoundingMode};
+use crate::utilities::{create_value, get_default_value_type, get_vec_reg_size, Register, RegType, Value, ValueType};
+use crate::{add_animation_data, ElementAnimationData};
+use super::{get_values_from_memory, get_values_from_register, Operand};
+use super::forms::get_vec_reg_form;
+
+// Instructions described by their operands, element type and what happens to every lane.
+// Execution and the default animation are both generated from the description.
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum Role {
+    Dst,
+    DstSrc, // the destination is also the first source, e.g. the accumulator of an FMA
+    Move,   // the destination, or memory as wide as the source register for a store
+    Src,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum Width {
+    Gpr,
+    GprMemOrImm,
+    Vector,           // xmm, ymm or zmm
+    Same,             // a vector register as wide as the destination
+    SameOrMem,        // or memory as wide as the destination
+    Fixed(VecRegName),
+    FixedOrMem(VecRegName, usize), // or memory of the given bits
+    Half,             // half as wide as the other operands, at least an xmm
+    HalfOrMem,        // or memory of that width
+    Mem(usize),       // only memory of the given bits
+    Imm8,
+}
+
+#[derive(Copy, Clone)]
+pub struct OperandDef {
+    pub role: Role,
+    pub width: Width,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum LaneOp {
+    Add,
+    Mul,
+    FusedMulAdd, // a * b + c rounded once
+    DotBF16,     // a + b.hi * c.hi + b.lo * c.lo on the BF16 pairs of F32 lanes
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum Conversion {
+    F16ToF32,
+    F32ToF16,  // rounded as imm8 selects
+    F32ToBF16, // round to nearest even with denormals as zero, MXCSR is ignored
+}
+
+impl Conversion {
+    pub fn get_source(&self) -> ValueType {
+        match self {
+            Conversion::F16ToF32 => ValueType::F16,
+            Conversion::F32ToF16 | Conversion::F32ToBF16 => ValueType::F32,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum Semantics {
+    // dst[i] = op(a[i], b[i], ..), sources are indices into the operands after DstSrc is expanded
+    Lanes(LaneOp, &'static [usize]),
+    // (dst lane, lane count, imm8) -> (operand, lane) the lane is copied from, None zeroes it
+    Map(fn(usize, usize, u64) -> Option<(usize, usize)>),
+    // Like Map, the copied lane is converted to the element type of the destination
+    Convert(Conversion, fn(usize, usize, u64) -> Option<(usize, usize)>),
+    // No lanes, only the flags and MXCSR effects
+    None,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum FlagsEffect {
+    None,
+    ZeroFlag, // ZF is set when the result is zero
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum MxcsrEffect {
+    None,
+    Load,  // MXCSR := the memory source
+    Store, // the memory destination := MXCSR
+}
+
+pub struct InstructionDef {
+    pub opcode: &'static str,
+    pub operands: &'static [OperandDef],
+    pub element: ValueType,
+    pub semantics: Semantics,
+    pub flags: FlagsEffect,
+    pub mxcsr: MxcsrEffect,
+    pub evex: bool, // has an AVX-512 form, with embedded broadcast on its full-width memory source
+}
+
+impl InstructionDef {
+    pub fn is_target_read(&self) -> bool {
+        self.operands.first().is_some_and(|operand| operand.role == Role::DstSrc)
+    }
+    pub fn is_target_written(&self) -> bool {
+        self.operands.first().is_some_and(|operand| operand.role != Role::Src)
+    }
+    pub fn is_data_movement(&self) -> bool {
+        matches!(self.semantics, Semantics::Map(_))
+    }
+    pub fn get_source_element(&self) -> ValueType {
+        // Elements the sources are displayed in, the lanes are `element`
+        match self.semantics {
+            Semantics::Convert(conversion, _) => conversion.get_source(),
+            Semantics::Lanes(LaneOp::DotBF16, _) => ValueType::BF16,
+            _ => self.element,
+        }
+    }
+    fn get_read_element(&self) -> ValueType {
+        // Elements the lanes read, a BF16 pair of the dot product is one F32
+        match self.semantics {
+            Semantics::Convert(conversion, _) => conversion.get_source(),
+            _ => self.element,
+        }
+    }
+    pub fn get_forms(&self) -> Vec<String> {
+        // One form per destination width, e.g. `ymm, ymm, ymm, imm8`
+        let targets = match self.operands.first().map(|operand| operand.width) {
+            Some(Width::Fixed(name)) => vec![get_vec_reg_form(name).to_string()],
+            Some(Width::Gpr) | Some(Width::GprMemOrImm) => ["r64", "r32", "r16", "r8"].iter().map(|target| target.to_string()).collect(),
+            Some(Width::Mem(bits)) => vec![format!("m{}", bits)],
+            _ => ["xmm", "ymm", "zmm"].iter().map(|target| target.to_string()).collect(),
+        };
+        let mut forms: Vec<String> = targets.iter().map(|target| {
+            self.operands.iter().map(|operand| operand.width.get_form(target)).collect::<Vec<String>>().join(", ")
+        }).collect();
+        if self.operands.first().is_some_and(|operand| operand.role == Role::Move) {
+            // Stores, e.g. `m256, ymm`
+            forms.extend(targets.iter().map(|target| {
+                let sources = self.operands[1..].iter().map(|operand| operand.width.get_register_form(target));
+                std::iter::once(format!("m{}", get_form_bits(target))).chain(sources).collect::<Vec<String>>().join(", ")
+            }));
+        }
+        forms
+    }
+    pub fn get_expanded_operands(&self) -> Vec<OperandDef> {
+        // In the order of the decoded operands, a target that is also read is listed twice
+        let mut operands = self.operands.to_vec();
+        if self.is_target_read() {
+            operands.insert(0, operands[0]);
+        }
+        operands
+    }
+    pub fn get_memory_size(&self, operands: &[Operand], is_broadcast: bool) -> Option<usize> {
+        // Bytes read or written at the memory operand, a broadcast reads a single element
+        let shape = get_shape(operands);
+        self.get_expanded_operands().iter().zip(operands.iter())
+            .find(|(_, operand)| matches!(operand, Operand::Mem(_)))
+            .and_then(|(operand, _)| match (operand.width, shape) {
+                (Width::Mem(bits), _) => Some(bits / 8),
+                _ if is_broadcast => Some(self.get_read_element().size() / 8),
+                (width, Some(shape)) => Some(width.get_memory_bits(&shape) / 8),
+                _ => None,
+            })
+    }
+    pub fn check_broadcast(&self, operands: &[Operand], count: usize, size: Option<usize>) -> Result<(), String> {
+        // operands as written; `{1toN}` needs the full-width memory source of an EVEX form,
+        // N elements must fill the destination and a `ptr` prefix must be the element size
+        let dst = match operands.first() {
+            Some(Operand::Reg(dst)) => *dst,
+            _ => return Err(format!("{} does not support embedded broadcast", self.opcode)),
+        };
+        let width = operands.iter().position(|operand| matches!(operand, Operand::Mem(_))).map(|i| self.operands[i].width);
+        if !self.evex || width != Some(Width::SameOrMem) || self.operands[0].role == Role::Move {
+            return Err(format!("{} does not support embedded broadcast", self.opcode));
+        }
+        let element = self.get_read_element().size();
+        let lanes = get_vec_reg_size(&dst.get_vector().0) / element;
+        if count != lanes {
+            return Err(format!("Invalid broadcast {{1to{}}} for {} with a {} destination, expected {{1to{}}}",
+                count, self.opcode, get_vec_reg_form(dst.get_vector().0), lanes));
+        }
+        if size.is_some_and(|size| size != element) {
+            return Err(format!("Invalid broadcast element for {}, expected m{}", self.opcode, element));
+        }
+        Ok(())
+    }
+    pub fn check_embedded_rounding(&self, operands: &[Operand]) -> Result<(), String> {
+        // operands as written; `{er}` only rounds the floating point arithmetic and conversions of an EVEX form on zmm registers
+        let is_rounded = match self.semantics {
+            Semantics::Lanes(op, _) => op != LaneOp::DotBF16 && matches!(self.element, ValueType::F16 | ValueType::F32 | ValueType::F64),
+            Semantics::Convert(conversion, _) => conversion != Conversion::F32ToBF16,
+            _ => false,
+        };
+        if !self.evex || !is_rounded {
+            return Err(format!("{} does not support embedded rounding", self.opcode));
+        }
+        let is_zmm = |operand: &Operand| matches!(operand, Operand::Reg(reg) if reg.get_type() == RegType::Vector && reg.get_vector().0 == VecRegName::ZMM);
+        if operands.iter().any(|operand| matches!(operand, Operand::Mem(_))) || !operands.iter().any(is_zmm) {
+            return Err(format!("Embedded rounding of {} needs zmm register operands", self.opcode));
+        }
+        Ok(())
+    }
+}
+
+fn get_form_bits(target: &str) -> usize {
+    match target {
+        "xmm" => 128,
+        "ymm" => 256,
+        "zmm" => 512,
+        _ => target[1..].parse::<usize>().unwrap_or(64),
+    }
+}
+
+impl Width {
+    fn get_form(&self, target: &str) -> String {
+        let bits = get_form_bits(target);
+        let half = if target == "zmm" { "ymm" } else { "xmm" };
+        match self {
+            Width::Gpr | Width::Vector | Width::Same => target.to_string(),
+            Width::GprMemOrImm => format!("{}/m{}/imm{}", target, bits, bits.min(32)),
+            Width::SameOrMem => format!("{}/m{}", target, bits),
+            Width::Fixed(name) => get_vec_reg_form(*name).to_string(),
+            Width::FixedOrMem(name, bits) => format!("{}/m{}", get_vec_reg_form(*name), bits),
+            Width::Half => half.to_string(),
+            Width::HalfOrMem => format!("{}/m{}", half, bits / 2),
+            Width::Mem(bits) => format!("m{}", bits),
+            Width::Imm8 => String::from("imm8"),
+        }
+    }
+    fn get_register_form(&self, target: &str) -> String {
+        self.get_form(target).split('/').next().unwrap_or_default().to_string()
+    }
+    fn get_memory_bits(&self, shape: &Register) -> usize {
+        // shape: the destination register, or the source register of a store
+        match self {
+            Width::GprMemOrImm => Utilities::get_gpr_size(&shape.get_gpr()) as usize,
+            Width::Vector | Width::SameOrMem => get_vec_reg_size(&shape.get_vector().0),
+            Width::FixedOrMem(_, bits) | Width::Mem(bits) => *bits,
+            Width::HalfOrMem => get_vec_reg_size(&shape.get_vector().0) / 2,
+            _ => 0,
+        }
+    }
+}
+
+const DST: OperandDef = OperandDef { role: Role::Dst, width: Width::Vector };
+const MOVE_DST: OperandDef = OperandDef { role: Role::Move, width: Width::Vector };
+const HALF_DST: OperandDef = OperandDef { role: Role::Dst, width: Width::Half };
+const ACC: OperandDef = OperandDef { role: Role::DstSrc, width: Width::Vector };
+const SRC: OperandDef = OperandDef { role: Role::Src, width: Width::Same };
+const VECTOR_SRC: OperandDef = OperandDef { role: Role::Src, width: Width::Vector };
+const HALF_SRC_MEM: OperandDef = OperandDef { role: Role::Src, width: Width::HalfOrMem };
+const SRC_MEM: OperandDef = OperandDef { role: Role::Src, width: Width::SameOrMem };
+const IMM8: OperandDef = OperandDef { role: Role::Src, width: Width::Imm8 };
+const XMM_DST: OperandDef = OperandDef { role: Role::Dst, width: Width::Fixed(VecRegName::XMM) };
+const XMM_ACC: OperandDef = OperandDef { role: Role::DstSrc, width: Width::Fixed(VecRegName::XMM) };
+const XMM_SRC_MEM: OperandDef = OperandDef { role: Role::Src, width: Width::FixedOrMem(VecRegName::XMM, 128) };
+const XMM_SRC_M64: OperandDef = OperandDef { role: Role::Src, width: Width::FixedOrMem(VecRegName::XMM, 64) };
+const YMM_DST: OperandDef = OperandDef { role: Role::Dst, width: Width::Fixed(VecRegName::YMM) };
+const YMM_SRC: OperandDef = OperandDef { role: Role::Src, width: Width::Fixed(VecRegName::YMM) };
+const YMM_SRC_MEM: OperandDef = OperandDef { role: Role::Src, width: Width::FixedOrMem(VecRegName::YMM, 256) };
+const GPR_ACC: OperandDef = OperandDef { role: Role::DstSrc, width: Width::Gpr };
+const GPR_MEM_OR_IMM: OperandDef = OperandDef { role: Role::Src, width: Width::GprMemOrImm };
+const M32_DST: OperandDef = OperandDef { role: Role::Dst, width: Width::Mem(32) };
+const M32_SRC: OperandDef = OperandDef { role: Role::Src, width: Width::Mem(32) };
+
+const fn def(opcode: &'static str, operands: &'static [OperandDef], element: ValueType, semantics: Semantics) -> InstructionDef {
+    InstructionDef { opcode, operands, element, semantics, flags: FlagsEffect::None, mxcsr: MxcsrEffect::None, evex: true }
+}
+
+impl InstructionDef {
+    const fn without_evex(self) -> Self {
+        // Legacy SSE or VEX only
+        InstructionDef { evex: false, ..self }
+    }
+}
+
+fn copy(i: usize, _lanes: usize, _imm8: u64) -> Option<(usize, usize)> {
+    Some((1, i))
+}
+
+fn unpack_low(i: usize, _lanes: usize, _imm8: u64) -> Option<(usize, usize)> {
+    // src1[0], src2[0], src1[1], src2[1] in every 128-bit block
+    Some((1 + i % 2, i / 4 * 4 + i % 4 / 2))
+}
+
+fn unpack_high(i: usize, _lanes: usize, _imm8: u64) -> Option<(usize, usize)> {
+    Some((1 + i % 2, i / 4 * 4 + i % 4 / 2 + 2))
+}
+
+fn shuffle_ps(i: usize, _lanes: usize, imm8: u64) -> Option<(usize, usize)> {
+    // Two dwords from src1 then two from src2, the same selection in every 128-bit block
+    let select = (imm8 >> (2 * (i % 4))) as usize & 0b11;
+    Some((if i % 4 < 2 { 1 } else { 2 }, i / 4 * 4 + select))
+}
+
+fn align(i: usize, lanes: usize, imm8: u64) -> Option<(usize, usize)> {
+    // src1:src2 shifted right by imm8 lanes, only imm8[log2(lanes)-1:0] is used
+    let j = i + (imm8 as usize & (lanes - 1));
+    Some(if j < lanes { (2, j) } else { (1, j - lanes) })
+}
+
+fn permute_128(i: usize, _lanes: usize, imm8: u64) -> Option<(usize, usize)> {
+    let control = (imm8 >> (4 * i)) as usize & 0b1111;
+    if control & 0b1000 != 0 {
+        return None;
+    }
+    Some((1 + (control & 0b11) / 2, control & 1))
+}
+
+fn extract_128(_i: usize, _lanes: usize, imm8: u64) -> Option<(usize, usize)> {
+    Some((1, imm8 as usize & 1))
+}
+
+fn shuffle_pd(i: usize, _lanes: usize, imm8: u64) -> Option<(usize, usize)> {
+    // The target is read: operands are (dst, dst, src, imm8)
+    Some((1 + i, (imm8 as usize >> i) & 1))
+}
+
+fn broadcast(_i: usize, _lanes: usize, _imm8: u64) -> Option<(usize, usize)> {
+    Some((1, 0))
+}
+
+fn pack_halves(i: usize, lanes: usize, _imm8: u64) -> Option<(usize, usize)> {
+    // The low half of the destination from src2, the high half from src1
+    Some(if i < lanes / 2 { (2, i) } else { (1, i - lanes / 2) })
+}
+
+pub static DEFINITIONS: &[InstructionDef] = &[
+    def("vaddps", &[DST, SRC, SRC_MEM], ValueType::F32, Semantics::Lanes(LaneOp::Add, &[1, 2])),
+    def("vpaddd", &[DST, SRC, SRC_MEM], ValueType::U32, Semantics::Lanes(LaneOp::Add, &[1, 2])),
+    def("vmulpd", &[DST, SRC, SRC_MEM], ValueType::F64, Semantics::Lanes(LaneOp::Mul, &[1, 2])),
+    InstructionDef { opcode: "add", operands: &[GPR_ACC, GPR_MEM_OR_IMM], element: ValueType::U64, semantics: Semantics::Lanes(LaneOp::Add, &[1, 2]), flags: FlagsEffect::ZeroFlag, mxcsr: MxcsrEffect::None, evex: false },
+    def("vfmadd213pd", &[ACC, SRC, SRC_MEM], ValueType::F64, Semantics::Lanes(LaneOp::FusedMulAdd, &[2, 1, 3])),
+    def("vaddph", &[DST, SRC, SRC_MEM], ValueType::F16, Semantics::Lanes(LaneOp::Add, &[1, 2])),
+    def("vmulph", &[DST, SRC, SRC_MEM], ValueType::F16, Semantics::Lanes(LaneOp::Mul, &[1, 2])),
+    def("vfmadd132ph", &[ACC, SRC, SRC_MEM], ValueType::F16, Semantics::Lanes(LaneOp::FusedMulAdd, &[1, 3, 2])),
+    def("vfmadd213ph", &[ACC, SRC, SRC_MEM], ValueType::F16, Semantics::Lanes(LaneOp::FusedMulAdd, &[2, 1, 3])),
+    def("vfmadd231ph", &[ACC, SRC, SRC_MEM], ValueType::F16, Semantics::Lanes(LaneOp::FusedMulAdd, &[2, 3, 1])),
+    def("valignd", &[DST, SRC, SRC_MEM, IMM8], ValueType::U32, Semantics::Map(align)),
+    def("vunpcklps", &[DST, SRC, SRC_MEM], ValueType::U32, Semantics::Map(unpack_low)),
+    def("vunpckhps", &[DST, SRC, SRC_MEM], ValueType::U32, Semantics::Map(unpack_high)),
+    def("vshufps", &[DST, SRC, SRC_MEM, IMM8], ValueType::U32, Semantics::Map(shuffle_ps)),
+    def("vperm2f128", &[YMM_DST, YMM_SRC, YMM_SRC_MEM, IMM8], ValueType::U128, Semantics::Map(permute_128)).without_evex(),
+    def("vextractf128", &[XMM_DST, YMM_SRC, IMM8], ValueType::U128, Semantics::Map(extract_128)).without_evex(),
+    def("shufpd", &[XMM_ACC, XMM_SRC_MEM, IMM8], ValueType::U64, Semantics::Map(shuffle_pd)).without_evex(),
+    def("vbroadcastsd", &[YMM_DST, XMM_SRC_M64], ValueType::U64, Semantics::Map(broadcast)),
+    def("vmovapd", &[MOVE_DST, SRC_MEM], ValueType::U64, Semantics::Map(copy)),
+    def("vcvtph2ps", &[DST, HALF_SRC_MEM], ValueType::F32, Semantics::Convert(Conversion::F16ToF32, copy)),
+    // Destination lanes without a source lane are zeroed
+    def("vcvtps2ph", &[HALF_DST, VECTOR_SRC, IMM8], ValueType::F16, Semantics::Convert(Conversion::F32ToF16, copy)),
+    def("vcvtne2ps2bf16", &[DST, SRC, SRC_MEM], ValueType::BF16, Semantics::Convert(Conversion::F32ToBF16, pack_halves)),
+    def("vdpbf16ps", &[ACC, SRC, SRC_MEM], ValueType::F32, Semantics::Lanes(LaneOp::DotBF16, &[1, 2, 3])),
+    InstructionDef { opcode: "ldmxcsr", operands: &[M32_SRC], element: ValueType::U32, semantics: Semantics::None, flags: FlagsEffect::None, mxcsr: MxcsrEffect::Load, evex: false },
+    InstructionDef { opcode: "stmxcsr", operands: &[M32_DST], element: ValueType::U32, semantics: Semantics::None, flags: FlagsEffect::None, mxcsr: MxcsrEffect::Store, evex: false },
+];
+
+pub fn find(opcode: &str) -> Option<&'static InstructionDef> {
+    DEFINITIONS.iter().find(|def| def.opcode == opcode)
+}
+
+fn get_shape(operands: &[Operand]) -> Option<Register> {
+    // The destination register, or the source register of a store
+    match (operands.first(), operands.get(1)) {
+        (Some(Operand::Reg(dst)), _) => Some(*dst),
+        (Some(Operand::Mem(_)), Some(Operand::Reg(src))) => Some(*src),
+        _ => None,
+    }
+}
+
+fn get_lane_count(reg: &Register, element: ValueType) -> usize {
+    match reg.get_type() {
+        RegType::Vector => get_vec_reg_size(&reg.get_vector().0) / element.size(),
+        _ => 1,
+    }
+}
+
+fn mask(bits: usize) -> u128 {
+    if bits >= 128 { u128::MAX } else { (1u128 << bits) - 1 }
+}
+
+macro_rules! read_sections {
+    ($cpu:expr, $operand:expr, $ty:ty, $count:expr) => {
+        read_source!($cpu, $operand, $ty, $count).into_iter().map(|x| x as u128).collect()
+    };
+}
+
+macro_rules! write_sections {
+    ($cpu:expr, $reg:expr, $ty:ty, $lanes:expr) => {
+        $cpu.registers.set_by_sections::<$ty>($reg.get_vector().0, $reg.get_vector().1, $lanes.iter().map(|x| *x as $ty).collect())
+    };
+}
+
+fn read_lanes(cpu: &CPU, operand: &Operand, element: ValueType, count: usize) -> Vec<u128> {
+    // count: lanes an immediate is repeated in or elements read from memory
+    match operand {
+        Operand::Reg(reg) if reg.get_type() == RegType::GPR => vec![cpu.registers.get_gpr_value(reg.get_gpr()) as u128],
+        Operand::Imm(imm) => vec![*imm as u128; count],
+        _ => match element.size() {
+            8 => read_sections!(cpu, operand, u8, count),
+            16 => read_sections!(cpu, operand, u16, count),
+            32 => read_sections!(cpu, operand, u32, count),
+            64 => read_sections!(cpu, operand, u64, count),
+            _ => read_sections!(cpu, operand, u128, count),
+        },
+    }
+}
+
+fn write_lanes(cpu: &mut CPU, reg: &Register, element: ValueType, lanes: Vec<u128>) {
+    if reg.get_type() == RegType::GPR {
+        cpu.registers.set_gpr_value(reg.get_gpr(), (lanes[0] & mask(Utilities::get_gpr_size(&reg.get_gpr()) as usize)) as u64);
+        return;
+    }
+    match element.size() {
+        8 => write_sections!(cpu, reg, u8, lanes),
+        16 => write_sections!(cpu, reg, u16, lanes),
+        32 => write_sections!(cpu, reg, u32, lanes),
+        64 => write_sections!(cpu, reg, u64, lanes),
+        _ => write_sections!(cpu, reg, u128, lanes),
+    }
+}
+
+fn write_memory(cpu: &mut CPU, addr: u64, element: ValueType, lanes: Vec<u128>) {
+    let addr = addr as usize;
+    match element.size() {
+        8 => cpu.memory.write_vec::<u8>(addr, lanes.iter().map(|x| *x as u8).collect()),
+        16 => cpu.memory.write_vec::<u16>(addr, lanes.iter().map(|x| *x as u16).collect()),
+        32 => cpu.memory.write_vec::<u32>(addr, lanes.iter().map(|x| *x as u32).collect()),
+        64 => cpu.memory.write_vec::<u64>(addr, lanes.iter().map(|x| *x as u64).collect()),
+        _ => cpu.memory.write_vec::<u128>(addr, lanes),
+    }
+}
+
+fn convert(conversion: Conversion, x: u128, imm8: u64, mxcsr: &mut Mxcsr) -> u128 {
+    match conversion {
+        Conversion::F16ToF32 => Utilities::f32_to_u32(mxcsr.convert_f16_to_f32(x as u16)) as u128,
+        Conversion::F32ToF16 => {
+            // imm8[2] selects MXCSR.RC, otherwise imm8[1:0] is the rounding mode
+            let rounding = if imm8 & 0b100 != 0 { None } else { Some(RoundingMode::from_rc(imm8 as u32)) };
+            mxcsr.convert_f32_to_f16(Utilities::u32_to_f32(x as u32), rounding) as u128
+        }
+        Conversion::F32ToBF16 => f32_to_bf16_flushed(Utilities::u32_to_f32(x as u32)) as u128,
+    }
+}
+
+fn calc(op: LaneOp, element: ValueType, x: &[u128], mxcsr: &mut Mxcsr) -> u128 {
+    let operand = |i: usize| x.get(i).copied().unwrap_or(0);
+    match element {
+        ValueType::F16 => {
+            // Sums and products of F16 are exact in f64. The sum of an FMA is not,
+            // its TwoSum error is kept so the result is rounded once to F16
+            let v = |i: usize| f16_to_f32(operand(i) as u16) as f64;
+            let (r, error) = match op {
+                LaneOp::Add => (v(0) + v(1), 0.0),
+                LaneOp::Mul => (v(0) * v(1), 0.0),
+                LaneOp::FusedMulAdd => {
+                    let (p, c) = (v(0) * v(1), v(2));
+                    let s = p + c;
+                    let bb = s - p;
+                    (s, if s.is_finite() { (p - (s - bb)) + (c - bb) } else { 0.0 })
+                }
+                LaneOp::DotBF16 => (0.0, 0.0),
+            };
+            mxcsr.round_f16(&x.iter().map(|x| *x as u16).collect::<Vec<u16>>(), r, error) as u128
+        }
+        ValueType::F32 => {
+            let v = |i: usize| Utilities::u32_to_f32(operand(i) as u32);
+            let r = match op {
+                LaneOp::Add => mxcsr.add_f32(v(0), v(1)),
+                LaneOp::Mul => mxcsr.mul_f32(v(0), v(1)),
+                LaneOp::FusedMulAdd => mxcsr.fma_f32(v(0), v(1), v(2)),
+                LaneOp::DotBF16 => {
+                    // The odd pair first, every step rounded to nearest F32 with denormals as zero
+                    let bf16 = |i: usize, high: bool| flush_f32(bf16_to_f32((operand(i) >> if high { 16 } else { 0 }) as u16));
+                    let x = flush_f32(flush_f32(v(0)) + bf16(1, true) * bf16(2, true));
+                    flush_f32(x + bf16(1, false) * bf16(2, false))
+                }
+            };
+            Utilities::f32_to_u32(r) as u128
+        }
+        ValueType::F64 => {
+            let v = |i: usize| Utilities::u64_to_f64(operand(i) as u64);
+            let r = match op {
+                LaneOp::Add => mxcsr.add_f64(v(0), v(1)),
+                LaneOp::Mul => mxcsr.mul_f64(v(0), v(1)),
+                LaneOp::FusedMulAdd => mxcsr.fma_f64(v(0), v(1), v(2)),
+                LaneOp::DotBF16 => 0.0,
+            };
+            Utilities::f64_to_u64(r) as u128
+        }
+        _ => {
+            // Integer lanes wrap around like the hardware
+            let r = match op {
+                LaneOp::Add => operand(0).wrapping_add(operand(1)),
+                LaneOp::Mul => operand(0).wrapping_mul(operand(1)),
+                LaneOp::FusedMulAdd => operand(0).wrapping_mul(operand(1)).wrapping_add(operand(2)),
+                LaneOp::DotBF16 => 0,
+            };
+            r & mask(element.size())
+        }
+    }
+}
+
+pub fn execute(def: &InstructionDef, cpu: Arc<Mutex<CPU>>, mxcsr: &mut Mxcsr, operands: &[Operand], is_broadcast: bool) {
+    let mut cpu = cpu.lock().unwrap();
+    match (def.mxcsr, operands.first()) {
+        (MxcsrEffect::Load, Some(Operand::Mem(addr))) => mxcsr.value = cpu.memory.read::<u32>(*addr as usize),
+        (MxcsrEffect::Store, Some(Operand::Mem(addr))) => cpu.memory.write::<u32>(*addr as usize, mxcsr.value),
+        _ => {}
+    }
+    let shape = match get_shape(operands) {
+        Some(shape) => shape,
+        None => return,
+    };
+    let lanes = get_lane_count(&shape, def.element);
+    let imm8 = operands.iter().find_map(|operand| if let Operand::Imm(imm) = operand { Some(*imm) } else { None }).unwrap_or(0);
+    // A GPR reads as many bytes of memory as it holds
+    let element = if shape.get_type() == RegType::GPR { get_default_value_type(&shape) } else { def.get_read_element() };
+    let values: Vec<Vec<u128>> = def.get_expanded_operands().iter().zip(operands.iter()).map(|(operand_def, operand)| {
+        match operand {
+            // The broadcast element is repeated in every lane
+            Operand::Mem(_) if is_broadcast => vec![read_lanes(&cpu, operand, element, 1).first().copied().unwrap_or(0); lanes],
+            Operand::Mem(_) => read_lanes(&cpu, operand, element, (operand_def.width.get_memory_bits(&shape) / element.size()).max(1)),
+            _ => read_lanes(&cpu, operand, element, lanes),
+        }
+    }).collect();
+    let lane = |operand: usize, i: usize| values.get(operand).and_then(|v| v.get(i)).copied();
+    let result: Vec<u128> = match def.semantics {
+        Semantics::Lanes(op, sources) => (0..lanes).map(|i| {
+            calc(op, def.element, &sources.iter().map(|source| lane(*source, i).unwrap_or(0)).collect::<Vec<u128>>(), mxcsr)
+        }).collect(),
+        Semantics::Map(map) => (0..lanes).map(|i| {
+            map(i, lanes, imm8).and_then(|(operand, j)| lane(operand, j)).unwrap_or(0)
+        }).collect(),
+        Semantics::Convert(conversion, map) => (0..lanes).map(|i| {
+            map(i, lanes, imm8).and_then(|(operand, j)| lane(operand, j)).map_or(0, |x| convert(conversion, x, imm8, mxcsr))
+        }).collect(),
+        Semantics::None => return,
+    };
+    if def.flags == FlagsEffect::ZeroFlag {
+        let width = if shape.get_type() == RegType::GPR { Utilities::get_gpr_size(&shape.get_gpr()) as usize } else { def.element.size() };
+        let mut f = cpu.registers.get_flags_value(FLAGSName::RFLAGS);
+        if result.iter().all(|x| x & mask(width) == 0) {
+            f |= 0b1000000;
+        } else {
+            f &= 0xFFFFFFBF;
+        }
+        cpu.registers.set_flags_value(FLAGSName::RFLAGS, f);
+    }
+    match operands[0] {
+        Operand::Reg(dst) => write_lanes(&mut cpu, &dst, def.element, result),
+        Operand::Mem(addr) => write_memory(&mut cpu, addr, def.element, result),
+        Operand::Imm(_) => {}
+    }
+}
+
+fn get_row(operand: &(Operand, LayoutLocation, (usize, usize))) -> usize {
+    if operand.1 == LayoutLocation::TOP { operand.2.0 } else { operand.2.1 }
+}
+
+fn get_memory_value(addr: u64, index: usize, value_type: ValueType, cpu: &Arc<Mutex<CPU>>) -> Option<(usize, Value)> {
+    let cpu = cpu.lock().unwrap();
+    let address = addr as usize + index * value_type.size() / 8;
+    get_values_from_memory(address, value_type, 1, &cpu).pop().map(|value| (address, value))
+}
+
+fn get_memory_element(dst: &Register, addr: u64, index: usize, cpu: &Arc<Mutex<CPU>>, vrt: &HashMap<(VecRegName, usize), ValueType>) -> Option<(usize, Value)> {
+    // Element `index` of a memory source as the destination displays it, with its address
+    match dst.get_type() {
+        RegType::Vector => get_memory_value(addr, index, *vrt.get(&dst.get_vector())?, cpu),
+        _ => {
+            let cpu = cpu.lock().unwrap();
+            let bytes = cpu.memory.read_vec::<u8>(addr as usize, Utilities::get_gpr_size(&dst.get_gpr()) as usize / 8);
+            Some((addr as usize, create_value(bytes.iter().rev().fold(0u64, |value, byte| value << 8 | *byte as u64))))
+        }
+    }
+}
+
+fn format_lane_op(op: LaneOp, values: &[String]) -> String {
+    match (op, values.len()) {
+        (LaneOp::FusedMulAdd, 2) => format!("({} * {})", values[0], values[1]),
+        (LaneOp::FusedMulAdd, _) => format!("({} * {}) + {}", values[0], values[1], values[2]),
+        (LaneOp::Add, _) => values.join(" + "),
+        (LaneOp::Mul, _) => values.join(" * "),
+        (LaneOp::DotBF16, 2) => format!("{} + {} . _", values[0], values[1]),
+        (LaneOp::DotBF16, _) => format!("{} + {} . {}", values[0], values[1], values[2]),
+    }
+}
+
+fn lane_op_stages(op: LaneOp, sources: &[usize], element: ValueType, source_element: ValueType, odd: &[(Operand, LayoutLocation, (usize, usize))], cpu: Arc<Mutex<CPU>>, vrt: &HashMap<(VecRegName, usize), ValueType>, num: usize, broadcast: Option<usize>) -> Vec<(Vec<ElementAnimationData>, bool)> {
+    // One stage per register or memory source, the label grows with every operand that has arrived.
+    // broadcast: displayed elements per lane of a broadcast memory source, it is already in the destination
+    let target = odd[0].clone();
+    let dst = match target.0 {
+        Operand::Reg(dst) => dst,
+        _ => return vec![],
+    };
+    // Sources of a dot product hold several source elements per destination element
+    let is_packed = source_element != element && broadcast.is_none();
+    let packed_count = get_lane_count(&dst, element) * element.size() / source_element.size();
+    let values: Vec<Vec<String>> = sources.iter().map(|source| match &odd[*source].0 {
+        Operand::Reg(reg) => get_values_from_register(*reg, cpu.clone(), vrt.clone()).iter().map(|v| format!("{}", v)).collect(),
+        Operand::Imm(imm) => vec![format!("{}", imm); num],
+        Operand::Mem(addr) if is_packed => (0..packed_count).map(|i| {
+            get_memory_value(*addr, i, source_element, &cpu).map(|(_, v)| format!("{}", v)).unwrap_or_default()
+        }).collect(),
+        Operand::Mem(addr) => (0..num).map(|i| {
+            let index = broadcast.map_or(i, |ratio| i % ratio);
+            get_memory_element(&dst, *addr, index, &cpu, vrt).map(|(_, v)| format!("{}", v)).unwrap_or_default()
+        }).collect(),
+    }).collect();
+    // Source elements flying into each destination element, shown as a tuple in the label
+    let per = |k: usize| (values[k].len() / num.max(1)).max(1);
+    let lane_value = |k: usize, i: usize| match per(k) {
+        1 => values[k].get(i).cloned().unwrap_or_default(),
+        n => format!("({})", (i * n..(i + 1) * n).filter_map(|j| values[k].get(j).cloned()).collect::<Vec<String>>().join(", ")),
+    };
+    // Immediates and broadcast elements are not moved, they show up in the label of the operand before them
+    let is_in_place = |operand: &Operand| match operand {
+        Operand::Imm(_) => true,
+        Operand::Mem(_) => broadcast.is_some(),
+        Operand::Reg(_) => false,
+    };
+    let mut stages = vec![];
+    for (k, source_index) in sources.iter().enumerate() {
+        let source = odd[*source_index].clone();
+        if is_in_place(&source.0) {
+            continue;
+        }
+        let mut known = k + 1;
+        while known < sources.len() && is_in_place(&odd[sources[known]].0) {
+            known += 1;
+        }
+        let mut group = vec![];
+        for i in 0..num.min(values[k].len()) {
+            for j in i * per(k)..(i + 1) * per(k) {
+                let label = if known == 1 { None } else {
+                    Some(format_lane_op(op, &(0..known).map(|k| lane_value(k, i)).collect::<Vec<String>>()))
+                };
+                let callback = move |e: &mut Element| if let Some(label) = label { e.set_string(label) };
+                match source.0 {
+                    Operand::Reg(src) => {
+                        add_animation_data!(group; src, source.1, get_row(&source), j, dst, target.1, get_row(&target), i, callback);
+                    }
+                    Operand::Mem(addr) => {
+                        let value = if is_packed { get_memory_value(addr, j, source_element, &cpu) } else { get_memory_element(&dst, addr, j, &cpu, vrt) };
+                        if let Some((address, value)) = value {
+                            group.push(ElementAnimationData::from_memory(address, value, (dst, target.1, get_row(&target), i), callback));
+                        }
+                    }
+                    Operand::Imm(_) => {}
+                }
+            }
+        }
+        stages.push((group, false));
+    }
+    stages
+}
+
+fn lane_map_stages(map: fn(usize, usize, u64) -> Option<(usize, usize)>, element: ValueType, source_element: ValueType, odd: &[(Operand, LayoutLocation, (usize, usize))], cpu: Arc<Mutex<CPU>>, vrt: &HashMap<(VecRegName, usize), ValueType>, is_broadcast: bool) -> Vec<(Vec<ElementAnimationData>, bool)> {
+    let target = odd[0].clone();
+    let dst = match target.0 {
+        Operand::Reg(dst) => dst,
+        _ => return vec![],
+    };
+    // Displayed elements per instruction lane, None if a lane does not cover whole displayed elements
+    let ratio = |reg: &Register, element: ValueType| {
+        let displayed = get_values_from_register(*reg, cpu.clone(), vrt.clone()).len();
+        let lanes = get_lane_count(reg, element);
+        if lanes > 0 && displayed % lanes == 0 { Some(displayed / lanes) } else { None }
+    };
+    let dst_ratio = match ratio(&dst, element) {
+        Some(r) => r,
+        None => return vec![],
+    };
+    let lanes = get_lane_count(&dst, element);
+    let imm8 = odd.iter().find_map(|operand| if let Operand::Imm(imm) = operand.0 { Some(imm) } else { None }).unwrap_or(0);
+    // A source element can only fly once per group, repeated reads go to later groups
+    let mut used: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut stages: Vec<(Vec<ElementAnimationData>, bool)> = vec![];
+    for i in 0..lanes {
+        let (operand, lane) = match map(i, lanes, imm8) {
+            Some(source) => source,
+            None => continue,
+        };
+        let source = match odd.get(operand) {
+            Some(source) => source.clone(),
+            None => continue,
+        };
+        // Memory is displayed like the destination, or as its own elements when converted.
+        // A broadcast element is already in every lane
+        let source_ratio = match source.0 {
+            Operand::Reg(src) => ratio(&src, source_element),
+            Operand::Mem(_) if is_broadcast => None,
+            Operand::Mem(_) if source_element != element => Some(1),
+            Operand::Mem(_) => Some(dst_ratio),
+            Operand::Imm(_) => None,
+        };
+        if source_ratio != Some(dst_ratio) { continue; }
+        for k in 0..dst_ratio {
+            let from = lane * dst_ratio + k;
+            let count = used.entry((operand, from)).or_insert(0);
+            if stages.len() <= *count {
+                stages.push((vec![], false));
+            }
+            match source.0 {
+                Operand::Reg(src) => {
+                    add_animation_data!(stages[*count].0; src, source.1, get_row(&source), from,
+                        dst, target.1, get_row(&target), i * dst_ratio + k, |_| {});
+                }
+                Operand::Mem(addr) => {
+                    let value = if source_element != element { get_memory_value(addr, from, source_element, &cpu) } else { get_memory_element(&dst, addr, from, &cpu, vrt) };
+                    if let Some((address, value)) = value {
+                        stages[*count].0.push(ElementAnimationData::from_memory(address, value,
+                            (dst, target.1, get_row(&target), i * dst_ratio + k), |_| {}));
+                    }
+                }
+                Operand::Imm(_) => {}
+            }
+            *count += 1;
+        }
+    }
+    stages
+}
+
+fn broadcast_stage(odd: &[(Operand, LayoutLocation, (usize, usize))], dst: Register, ratio: usize, num: usize, cpu: &Arc<Mutex<CPU>>, vrt: &HashMap<(VecRegName, usize), ValueType>) -> Vec<ElementAnimationData> {
+    // The memory element is copied into every lane of the destination
+    let target = odd[0].clone();
+    let addr = match odd.iter().find_map(|operand| if let Operand::Mem(addr) = operand.0 { Some(addr) } else { None }) {
+        Some(addr) => addr,
+        None => return vec![],
+    };
+    (0..num).filter_map(|i| {
+        get_memory_element(&dst, addr, i % ratio, cpu, vrt).map(|(address, value)| {
+            ElementAnimationData::from_memory(address, value, (dst, target.1, get_row(&target), i), |_| {})
+        })
+    }).collect()
+}
+
+pub fn animate(def: &InstructionDef, odd: Vec<(Operand, LayoutLocation, (usize, usize))>, cpu: Arc<Mutex<CPU>>, vrt: HashMap<(VecRegName, usize), ValueType>, is_broadcast: bool) -> Vec<(Vec<ElementAnimationData>, bool)> {
+    let target = odd[0].clone();
+    let dst = match target.0 {
+        Operand::Reg(dst) => dst,
+        _ => return vec![(vec![], false)],
+    };
+    let num = get_values_from_register(dst, cpu.clone(), vrt.clone()).len();
+    // A broadcast element is first copied into every lane, which needs whole displayed elements per lane
+    let lanes = get_lane_count(&dst, def.element);
+    let broadcast = if is_broadcast && num % lanes == 0 { Some(num / lanes) } else { None };
+    let mut stages = vec![];
+    if is_broadcast == broadcast.is_some() {
+        if let Some(ratio) = broadcast {
+            stages.push((broadcast_stage(&odd, dst, ratio, num, &cpu, &vrt), false));
+        }
+        stages.extend(match def.semantics {
+            Semantics::Lanes(op, sources) => lane_op_stages(op, sources, def.element, def.get_source_element(), &odd, cpu, &vrt, num, broadcast),
+            Semantics::Map(map) => lane_map_stages(map, def.element, def.element, &odd, cpu, &vrt, is_broadcast),
+            Semantics::Convert(conversion, map) => lane_map_stages(map, def.element, conversion.get_source(), &odd, cpu, &vrt, is_broadcast),
+            Semantics::None => vec![],
+        });
+    }
+    let mut last = vec![];
+    for i in 0..num {
+        add_animation_data!(last; dst, target.1, get_row(&target), i, dst, LayoutLocation::None, 0, i, |_| {});
+    }
+    stages.push((last, false));
+    stages
+}
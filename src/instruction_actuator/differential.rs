@@ -0,0 +1,351 @@
+use std::arch::x86_64::*;
+use std::sync::{Arc, Mutex};
+use cpulib::CPU;
+use super::{apply, decode, parse_register, split_instruction};
+use crate::simulator::MachineState;
+use crate::mxcsr::{MXCSR_DAZ, MXCSR_DEFAULT, MXCSR_FLAGS_MASK, MXCSR_FTZ, MXCSR_RC_SHIFT};
+use crate::scenario::splitmix64;
+use crate::value_editor::{self, EditLocation};
+
+// Differential tests: random inputs go through the simulator and through the same instruction on the host CPU,
+// the destination and the MXCSR flags must match bit for bit. Cases whose CPU features the host lacks are skipped.
+// AVX-512, FP16 and BF16 intrinsics are not stable in std::arch yet, so zmm forms, valignd, the *ph and bf16
+// instructions are only covered by the golden vectors.
+
+const ITERATIONS: usize = 64;
+
+// Every case runs under each MXCSR: round to nearest, DAZ and FTZ, the directed roundings, and round toward zero with DAZ and FTZ
+const MODES: [u32; 6] = [
+    MXCSR_DEFAULT,
+    MXCSR_DEFAULT | MXCSR_DAZ | MXCSR_FTZ,
+    MXCSR_DEFAULT | 1 << MXCSR_RC_SHIFT,
+    MXCSR_DEFAULT | 2 << MXCSR_RC_SHIFT,
+    MXCSR_DEFAULT | 3 << MXCSR_RC_SHIFT,
+    MXCSR_DEFAULT | 3 << MXCSR_RC_SHIFT | MXCSR_DAZ | MXCSR_FTZ,
+];
+
+#[derive(Copy, Clone)]
+enum Input {
+    Bits,          // random, e.g. any half with its NaNs and subnormals
+    F32(i32, i32), // unbiased exponent range of the normal values
+    F64(i32, i32),
+}
+
+struct Case {
+    line: &'static str,
+    inputs: &'static [(&'static str, Input)], // registers set before the instruction, passed to `host` in order
+    feature: &'static str,
+    host: unsafe fn(&[Vec<u8>]) -> Vec<u8>,
+}
+
+macro_rules! case {
+    ($line:literal, [$(($reg:literal, $input:expr)),*], $feature:literal, $host:expr) => {
+        Case {
+            line: $line,
+            inputs: &[$(($reg, $input)),*],
+            feature: $feature,
+            host: $host,
+        }
+    };
+}
+
+fn is_supported(feature: &str) -> Result<bool, String> {
+    // A misspelled feature must not turn into a silently skipped case
+    feature.split(',').filter(|f| !f.is_empty()).try_fold(true, |supported, f| Ok(supported && match f {
+        "avx" => is_x86_feature_detected!("avx"),
+        "avx2" => is_x86_feature_detected!("avx2"),
+        "fma" => is_x86_feature_detected!("fma"),
+        "f16c" => is_x86_feature_detected!("f16c"),
+        _ => return Err(format!("Unknown CPU feature: {}", f)),
+    }))
+}
+
+unsafe fn load<T>(bytes: &[u8]) -> T {
+    assert!(bytes.len() >= std::mem::size_of::<T>());
+    std::ptr::read_unaligned(bytes.as_ptr() as *const T)
+}
+
+fn store<T>(value: T) -> Vec<u8> {
+    let size = std::mem::size_of::<T>();
+    let mut bytes = vec![0u8; size];
+    unsafe { std::ptr::copy_nonoverlapping(&value as *const T as *const u8, bytes.as_mut_ptr(), size) };
+    bytes
+}
+
+#[target_feature(enable = "avx")]
+unsafe fn vaddps_xmm(x: &[Vec<u8>]) -> Vec<u8> {
+    store(_mm_add_ps(load(&x[0]), load(&x[1])))
+}
+
+#[target_feature(enable = "avx")]
+unsafe fn vaddps_ymm(x: &[Vec<u8>]) -> Vec<u8> {
+    store(_mm256_add_ps(load(&x[0]), load(&x[1])))
+}
+
+#[target_feature(enable = "avx")]
+unsafe fn vpaddd_xmm(x: &[Vec<u8>]) -> Vec<u8> {
+    store(_mm_add_epi32(load(&x[0]), load(&x[1])))
+}
+
+#[target_feature(enable = "avx2")]
+unsafe fn vpaddd_ymm(x: &[Vec<u8>]) -> Vec<u8> {
+    store(_mm256_add_epi32(load(&x[0]), load(&x[1])))
+}
+
+#[target_feature(enable = "avx")]
+unsafe fn vunpcklps_xmm(x: &[Vec<u8>]) -> Vec<u8> {
+    store(_mm_unpacklo_ps(load(&x[0]), load(&x[1])))
+}
+
+#[target_feature(enable = "avx")]
+unsafe fn vunpcklps_ymm(x: &[Vec<u8>]) -> Vec<u8> {
+    store(_mm256_unpacklo_ps(load(&x[0]), load(&x[1])))
+}
+
+#[target_feature(enable = "avx")]
+unsafe fn vunpckhps_xmm(x: &[Vec<u8>]) -> Vec<u8> {
+    store(_mm_unpackhi_ps(load(&x[0]), load(&x[1])))
+}
+
+#[target_feature(enable = "avx")]
+unsafe fn vunpckhps_ymm(x: &[Vec<u8>]) -> Vec<u8> {
+    store(_mm256_unpackhi_ps(load(&x[0]), load(&x[1])))
+}
+
+#[target_feature(enable = "avx")]
+unsafe fn vshufps_xmm<const IMM: i32>(x: &[Vec<u8>]) -> Vec<u8> {
+    store(_mm_shuffle_ps::<IMM>(load(&x[0]), load(&x[1])))
+}
+
+#[target_feature(enable = "avx")]
+unsafe fn vshufps_ymm<const IMM: i32>(x: &[Vec<u8>]) -> Vec<u8> {
+    store(_mm256_shuffle_ps::<IMM>(load(&x[0]), load(&x[1])))
+}
+
+#[target_feature(enable = "avx")]
+unsafe fn vperm2f128<const IMM: i32>(x: &[Vec<u8>]) -> Vec<u8> {
+    store(_mm256_permute2f128_ps::<IMM>(load(&x[0]), load(&x[1])))
+}
+
+#[target_feature(enable = "avx")]
+unsafe fn vextractf128<const IMM: i32>(x: &[Vec<u8>]) -> Vec<u8> {
+    store(_mm256_extractf128_ps::<IMM>(load(&x[0])))
+}
+
+unsafe fn shufpd<const IMM: i32>(x: &[Vec<u8>]) -> Vec<u8> {
+    // SSE2 is part of x86-64
+    store(_mm_shuffle_pd::<IMM>(load(&x[0]), load(&x[1])))
+}
+
+#[target_feature(enable = "avx")]
+unsafe fn vmulpd_xmm(x: &[Vec<u8>]) -> Vec<u8> {
+    store(_mm_mul_pd(load(&x[0]), load(&x[1])))
+}
+
+#[target_feature(enable = "avx")]
+unsafe fn vmulpd_ymm(x: &[Vec<u8>]) -> Vec<u8> {
+    store(_mm256_mul_pd(load(&x[0]), load(&x[1])))
+}
+
+#[target_feature(enable = "avx")]
+unsafe fn vmovapd_ymm(x: &[Vec<u8>]) -> Vec<u8> {
+    store(_mm256_loadu_pd(x[0].as_ptr() as *const f64))
+}
+
+#[target_feature(enable = "avx,fma")]
+unsafe fn vfmadd213pd_xmm(x: &[Vec<u8>]) -> Vec<u8> {
+    // dst = src2 * dst + src3
+    store(_mm_fmadd_pd(load(&x[1]), load(&x[0]), load(&x[2])))
+}
+
+#[target_feature(enable = "avx,fma")]
+unsafe fn vfmadd213pd_ymm(x: &[Vec<u8>]) -> Vec<u8> {
+    store(_mm256_fmadd_pd(load(&x[1]), load(&x[0]), load(&x[2])))
+}
+
+#[target_feature(enable = "avx2")]
+unsafe fn vbroadcastsd(x: &[Vec<u8>]) -> Vec<u8> {
+    store(_mm256_broadcastsd_pd(load(&x[0])))
+}
+
+#[target_feature(enable = "avx,f16c")]
+unsafe fn vcvtph2ps_xmm(x: &[Vec<u8>]) -> Vec<u8> {
+    store(_mm_cvtph_ps(load(&x[0])))
+}
+
+#[target_feature(enable = "avx,f16c")]
+unsafe fn vcvtph2ps_ymm(x: &[Vec<u8>]) -> Vec<u8> {
+    store(_mm256_cvtph_ps(load(&x[0])))
+}
+
+#[target_feature(enable = "avx,f16c")]
+unsafe fn vcvtps2ph_xmm<const IMM: i32>(x: &[Vec<u8>]) -> Vec<u8> {
+    store(_mm_cvtps_ph::<IMM>(load(&x[0])))
+}
+
+#[target_feature(enable = "avx,f16c")]
+unsafe fn vcvtps2ph_ymm<const IMM: i32>(x: &[Vec<u8>]) -> Vec<u8> {
+    store(_mm256_cvtps_ph::<IMM>(load(&x[0])))
+}
+
+fn get_cases() -> Vec<Case> {
+    use Input::*;
+    vec![
+        case!("vaddps xmm0, xmm1, xmm2", [("xmm1", F32(-20, 20)), ("xmm2", F32(-20, 20))], "avx", vaddps_xmm),
+        case!("vaddps ymm0, ymm1, ymm2", [("ymm1", F32(-20, 20)), ("ymm2", F32(-20, 20))], "avx", vaddps_ymm),
+        case!("vpaddd xmm0, xmm1, xmm2", [("xmm1", Bits), ("xmm2", Bits)], "avx", vpaddd_xmm),
+        case!("vpaddd ymm0, ymm1, ymm2", [("ymm1", Bits), ("ymm2", Bits)], "avx2", vpaddd_ymm),
+        case!("vunpcklps xmm0, xmm1, xmm2", [("xmm1", Bits), ("xmm2", Bits)], "avx", vunpcklps_xmm),
+        case!("vunpcklps ymm0, ymm1, ymm2", [("ymm1", Bits), ("ymm2", Bits)], "avx", vunpcklps_ymm),
+        case!("vunpckhps xmm0, xmm1, xmm2", [("xmm1", Bits), ("xmm2", Bits)], "avx", vunpckhps_xmm),
+        case!("vunpckhps ymm0, ymm1, ymm2", [("ymm1", Bits), ("ymm2", Bits)], "avx", vunpckhps_ymm),
+        case!("vshufps xmm0, xmm1, xmm2, 27", [("xmm1", Bits), ("xmm2", Bits)], "avx", vshufps_xmm::<27>),
+        case!("vshufps xmm0, xmm1, xmm2, 0x72", [("xmm1", Bits), ("xmm2", Bits)], "avx", vshufps_xmm::<0x72>),
+        case!("vshufps ymm0, ymm1, ymm2, 0x1B", [("ymm1", Bits), ("ymm2", Bits)], "avx", vshufps_ymm::<0x1B>),
+        case!("vshufps ymm0, ymm1, ymm2, 0xD8", [("ymm1", Bits), ("ymm2", Bits)], "avx", vshufps_ymm::<0xD8>),
+        case!("vperm2f128 ymm0, ymm1, ymm2, 0x20", [("ymm1", Bits), ("ymm2", Bits)], "avx", vperm2f128::<0x20>),
+        case!("vperm2f128 ymm0, ymm1, ymm2, 0x31", [("ymm1", Bits), ("ymm2", Bits)], "avx", vperm2f128::<0x31>),
+        case!("vperm2f128 ymm0, ymm1, ymm2, 0x08", [("ymm1", Bits), ("ymm2", Bits)], "avx", vperm2f128::<0x08>),
+        case!("vperm2f128 ymm0, ymm1, ymm2, 0x83", [("ymm1", Bits), ("ymm2", Bits)], "avx", vperm2f128::<0x83>),
+        case!("vextractf128 xmm0, ymm1, 0", [("ymm1", Bits)], "avx", vextractf128::<0>),
+        case!("vextractf128 xmm0, ymm1, 1", [("ymm1", Bits)], "avx", vextractf128::<1>),
+        case!("shufpd xmm0, xmm1, 1", [("xmm0", Bits), ("xmm1", Bits)], "", shufpd::<1>),
+        case!("shufpd xmm0, xmm1, 2", [("xmm0", Bits), ("xmm1", Bits)], "", shufpd::<2>),
+        case!("vmulpd xmm0, xmm1, xmm2", [("xmm1", F64(-60, 60)), ("xmm2", F64(-60, 60))], "avx", vmulpd_xmm),
+        case!("vmulpd ymm0, ymm1, ymm2", [("ymm1", F64(-60, 60)), ("ymm2", F64(-60, 60))], "avx", vmulpd_ymm),
+        // Products around the smallest normal double, for FTZ and underflow
+        case!("vmulpd xmm0, xmm1, xmm2", [("xmm1", F64(-530, -495)), ("xmm2", F64(-530, -495))], "avx", vmulpd_xmm),
+        case!("vaddps xmm0, xmm1, xmm2", [("xmm1", F32(-126, -120)), ("xmm2", F32(-126, -120))], "avx", vaddps_xmm),
+        case!("vmovapd ymm0, ymm1", [("ymm1", Bits)], "avx", vmovapd_ymm),
+        case!("vfmadd213pd xmm0, xmm1, xmm2", [("xmm0", F64(-30, 30)), ("xmm1", F64(-30, 30)), ("xmm2", F64(-60, 60))], "avx,fma", vfmadd213pd_xmm),
+        case!("vfmadd213pd ymm0, ymm1, ymm2", [("ymm0", F64(-30, 30)), ("ymm1", F64(-30, 30)), ("ymm2", F64(-60, 60))], "avx,fma", vfmadd213pd_ymm),
+        case!("vbroadcastsd ymm0, xmm1", [("xmm1", Bits)], "avx2", vbroadcastsd),
+        case!("vcvtph2ps xmm0, xmm1", [("xmm1", Bits)], "avx,f16c", vcvtph2ps_xmm),
+        case!("vcvtph2ps ymm0, xmm1", [("xmm1", Bits)], "avx,f16c", vcvtph2ps_ymm),
+        // Exponents from half subnormals to past the largest half
+        case!("vcvtps2ph xmm0, xmm1, 0", [("xmm1", F32(-28, 17))], "avx,f16c", vcvtps2ph_xmm::<0>),
+        case!("vcvtps2ph xmm0, xmm1, 1", [("xmm1", F32(-28, 17))], "avx,f16c", vcvtps2ph_xmm::<1>),
+        case!("vcvtps2ph xmm0, xmm1, 2", [("xmm1", F32(-28, 17))], "avx,f16c", vcvtps2ph_xmm::<2>),
+        case!("vcvtps2ph xmm0, xmm1, 3", [("xmm1", F32(-28, 17))], "avx,f16c", vcvtps2ph_xmm::<3>),
+        case!("vcvtps2ph xmm0, xmm1, 4", [("xmm1", F32(-28, 17))], "avx,f16c", vcvtps2ph_xmm::<4>),
+        case!("vcvtps2ph xmm0, ymm1, 0", [("ymm1", F32(-28, 17))], "avx,f16c", vcvtps2ph_ymm::<0>),
+    ]
+}
+
+fn get_float(r: u64, mantissa: u64, exponent: u64, exponent_bits: u32, mantissa_bits: u32, nan: bool) -> u64 {
+    // One element in sixteen each is a signed zero, a subnormal and a NaN with a random payload, quiet or signaling
+    let mantissa = mantissa & ((1 << mantissa_bits) - 1);
+    let (exponent, mantissa) = match (r >> 16) & 0b1111 {
+        0 => (0, 0),
+        1 => (0, mantissa.max(1)),
+        2 if nan => ((1 << exponent_bits) - 1, mantissa.max(1)),
+        _ => (exponent, mantissa),
+    };
+    (r >> 24 & 1) << (exponent_bits + mantissa_bits) | exponent << mantissa_bits | mantissa
+}
+
+fn generate(input: Input, size: usize, nan: bool, state: &mut u64) -> Vec<u8> {
+    let mut bytes = vec![];
+    while bytes.len() < size {
+        let r = splitmix64(state);
+        match input {
+            Input::Bits => bytes.extend(r.to_le_bytes()),
+            Input::F32(min, max) => (0..2).for_each(|i| {
+                let r = r >> (32 * i);
+                let exponent = (min + (r & 0xFF) as i32 % (max - min + 1) + 127) as u64;
+                let bits = get_float(r, splitmix64(state), exponent, 8, 23, nan) as u32;
+                bytes.extend(bits.to_le_bytes());
+            }),
+            Input::F64(min, max) => {
+                let exponent = (min + (r & 0xFFFF) as i32 % (max - min + 1) + 1023) as u64;
+                bytes.extend(get_float(r, splitmix64(state), exponent, 11, 52, nan).to_le_bytes());
+            }
+        }
+    }
+    bytes.truncate(size);
+    bytes
+}
+
+fn get_size(name: &str) -> usize {
+    value_editor::get_location_size(&EditLocation::Register(parse_register(name).unwrap()))
+}
+
+#[allow(deprecated)]
+unsafe fn run_host(case: &Case, values: &[Vec<u8>], mode: u32) -> (Vec<u8>, u32) {
+    // Only the host instruction runs under `mode`, the simulator's own float arithmetic needs the default MXCSR
+    let saved = _mm_getcsr();
+    _mm_setcsr(mode);
+    let result = (case.host)(values);
+    let flags = _mm_getcsr() & MXCSR_FLAGS_MASK;
+    _mm_setcsr(saved);
+    (result, flags)
+}
+
+fn simulate(case: &Case, values: &[Vec<u8>], mode: u32) -> Result<(Vec<u8>, u32), String> {
+    let cpu = Arc::new(Mutex::new(CPU::default()));
+    {
+        let mut cpu = cpu.lock().unwrap();
+        case.inputs.iter().zip(values).for_each(|((name, _), bytes)| {
+            value_editor::write_bytes(&mut cpu, &EditLocation::Register(parse_register(name).unwrap()), 0, bytes);
+        });
+    }
+    let decoded = decode(case.line, cpu.clone())?;
+    let mut state = MachineState::default();
+    state.mxcsr.value = mode;
+    apply(cpu.clone(), &mut state, &decoded);
+    let target = split_instruction(case.line).1[0].clone();
+    let location = EditLocation::Register(parse_register(&target).ok_or(format!("Invalid register: {}", target))?);
+    let cpu = cpu.lock().unwrap();
+    Ok((value_editor::read_bytes(&cpu, &location, 0, value_editor::get_location_size(&location)), state.mxcsr.value & MXCSR_FLAGS_MASK))
+}
+
+fn format_bytes(bytes: &[u8]) -> String {
+    // 32-bit lanes, lowest first
+    bytes.chunks(4).map(|lane| format!("{:08X}", u32::from_le_bytes(lane.try_into().unwrap()))).collect::<Vec<String>>().join(" ")
+}
+
+#[test]
+fn matches_host_cpu() {
+    let mut failures = vec![];
+    let mut skipped = vec![];
+    let cases = get_cases();
+    for (i, case) in cases.iter().enumerate() {
+        match is_supported(case.feature) {
+            Ok(true) => {}
+            Ok(false) => {
+                skipped.push(format!("`{}`: the host has no {}", case.line, case.feature));
+                continue;
+            }
+            Err(e) => {
+                failures.push(format!("`{}`\n    {}", case.line, e));
+                continue;
+            }
+        }
+        let mut state = i as u64;
+        'modes: for mode in MODES {
+            for _ in 0..ITERATIONS {
+                // NaNs only in the first input: the compiler may swap the operands of a commutative host instruction,
+                // which changes the NaN it propagates
+                let values: Vec<Vec<u8>> = case.inputs.iter().enumerate()
+                    .map(|(j, (name, input))| generate(*input, get_size(name), j == 0, &mut state)).collect();
+                let (expected, expected_flags) = unsafe { run_host(case, &values, mode) };
+                let result = simulate(case, &values, mode).and_then(|(actual, flags)| {
+                    if actual == expected && flags == expected_flags {
+                        Ok(())
+                    } else {
+                        Err(format!("expected {} flags {:06b}\n    actual   {} flags {:06b}", format_bytes(&expected), expected_flags, format_bytes(&actual), flags))
+                    }
+                });
+                // One counterexample per case is enough
+                if let Err(e) = result {
+                    let inputs: Vec<String> = case.inputs.iter().zip(&values).map(|((name, _), bytes)| format!("{} = {}", name, format_bytes(bytes))).collect();
+                    failures.push(format!("`{}` with MXCSR {:04X}\n    {}\n    {}", case.line, mode, inputs.join("\n    "), e));
+                    break 'modes;
+                }
+            }
+        }
+    }
+    println!("{} of {} cases skipped", skipped.len(), cases.len());
+    skipped.iter().for_each(|s| println!("    {}", s));
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
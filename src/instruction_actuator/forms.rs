@@ -0,0 +1,74 @@
+use cpulib::{Utilities, VecRegName};
+use crate::utilities::RegType;
+use super::{definition, Operand};
+
+// Legal operand forms in the notation of the Intel SDM, e.g. `ymm, ymm, ymm/m256, imm8`.
+// An operand matches one of the `/` separated alternatives of its position.
+
+const CMP_FORMS: [&str; 8] = ["r64, r64", "r32, r32", "r16, r16", "r8, r8", "r64, imm32", "r32, imm32", "r16, imm16", "r8, imm8"];
+
+pub fn get_vec_reg_form(name: VecRegName) -> &'static str {
+    match name {
+        VecRegName::XMM => "xmm",
+        VecRegName::YMM => "ymm",
+        VecRegName::ZMM => "zmm",
+    }
+}
+
+pub fn get_forms(opcode: &str) -> Vec<String> {
+    if let Some(def) = definition::find(opcode) {
+        return def.get_forms();
+    }
+    match opcode {
+        "cmp" => CMP_FORMS.iter().map(|form| form.to_string()).collect(),
+        "vzeroupper" | "vzeroall" => vec![String::new()],
+        _ => vec![],
+    }
+}
+
+fn describe(operand: &Operand, size: Option<usize>) -> String {
+    match operand {
+        Operand::Reg(reg) if reg.get_type() == RegType::Vector => get_vec_reg_form(reg.get_vector().0).to_string(),
+        Operand::Reg(reg) => format!("r{}", Utilities::get_gpr_size(&reg.get_gpr())),
+        Operand::Mem(_) => size.map_or(String::from("m"), |bits| format!("m{}", bits)),
+        Operand::Imm(_) => String::from("imm"),
+    }
+}
+
+fn matches(alternative: &str, operand: &Operand, size: Option<usize>) -> bool {
+    match operand {
+        Operand::Reg(_) => describe(operand, None) == alternative,
+        // A size prefix like `dword ptr` must be the size of the form, without one any memory operand fits
+        Operand::Mem(_) => match alternative.strip_prefix('m').and_then(|bits| bits.parse::<usize>().ok()) {
+            Some(bits) => size.map_or(true, |size| size == bits),
+            None => false,
+        },
+        Operand::Imm(imm) => match alternative.strip_prefix("imm").and_then(|bits| bits.parse::<u32>().ok()) {
+            Some(bits) => bits >= 64 || *imm < 1u64 << bits,
+            None => false,
+        },
+    }
+}
+
+fn matches_form(form: &str, operands: &[Operand], sizes: &[Option<usize>]) -> bool {
+    let positions: Vec<&str> = if form.is_empty() { vec![] } else { form.split(',').map(|position| position.trim()).collect() };
+    positions.len() == operands.len() && positions.iter().zip(operands.iter()).zip(sizes.iter()).all(|((position, operand), size)| {
+        position.split('/').any(|alternative| matches(alternative, operand, *size))
+    })
+}
+
+pub fn check(opcode: &str, operands: &[Operand], sizes: &[Option<usize>]) -> Result<(), String> {
+    // operands as written, before the target is repeated for instructions that read it;
+    // sizes: the bits of their `ptr` prefixes
+    let forms = get_forms(opcode);
+    if forms.iter().any(|form| matches_form(form, operands, sizes)) {
+        return Ok(());
+    }
+    let given = if operands.is_empty() {
+        String::from("no operands")
+    } else {
+        operands.iter().zip(sizes.iter()).map(|(operand, size)| describe(operand, *size)).collect::<Vec<String>>().join(", ")
+    };
+    let expected = forms.iter().map(|form| if form.is_empty() { "no operands" } else { form.as_str() }).collect::<Vec<&str>>().join(" | ");
+    Err(format!("Invalid operands for {} ({}), legal forms: {}", opcode, given, expected))
+}
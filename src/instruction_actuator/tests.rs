@@ -0,0 +1,215 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use cpulib::CPU;
+use super::{decode, get_memory_operands};
+use super::definition::{DEFINITIONS, Role, Semantics, Width};
+use crate::scenario::Scenario;
+use crate::simulator::Simulator;
+use crate::utilities::get_vec_reg_size;
+
+// Golden vectors: tests/golden/<opcode>.asm holds cases like
+//   case ymm, imm8[3] zeroes the low half
+//   state ymm1 u32 iota 0 1
+//   vperm2f128 ymm0, ymm1, ymm2, 0x08 ; expect ymm0.u32 = [...]
+// `state` lines use the initial state format of the CLI, the rest is the program with its `; expect` comments.
+
+// Opcodes handled outside the table and the definitions, `jne` is covered by cmp.asm
+const SPECIAL_OPCODES: [&str; 3] = ["cmp", "vzeroupper", "vzeroall"];
+
+struct Case {
+    name: String,
+    line: usize,
+    state: Vec<String>,
+    program: Vec<(usize, String)>, // file line, source
+}
+
+fn get_path(opcode: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(format!("{}.asm", opcode))
+}
+
+fn parse_cases(text: &str) -> Vec<Case> {
+    // Lines before the first `case` document the file
+    let mut cases: Vec<Case> = vec![];
+    text.lines().enumerate().for_each(|(i, line)| {
+        if let Some(name) = line.strip_prefix("case ") {
+            cases.push(Case { name: name.trim().into(), line: i + 1, state: vec![], program: vec![] });
+        } else if let Some(case) = cases.last_mut() {
+            match line.strip_prefix("state ") {
+                Some(state) => case.state.push(state.into()),
+                None => case.program.push((i + 1, line.into())),
+            }
+        }
+    });
+    cases
+}
+
+fn run_case(case: &Case) -> Result<(), String> {
+    let cpu = Scenario::parse(&case.state.join("\n"))?.build()?;
+    let code = case.program.iter().map(|(_, line)| line.as_str()).collect::<Vec<&str>>().join("\n");
+    let mut simulator = Simulator::new(cpu, &code);
+    simulator.run(1000)?;
+    if simulator.outcomes.is_empty() {
+        return Err("no `; expect` was checked".into());
+    }
+    let failures: Vec<String> = simulator.get_failures().iter().map(|outcome| {
+        format!("line {}: {}", case.program[outcome.line - 1].0, outcome.result.as_ref().unwrap_err())
+    }).collect();
+    if failures.is_empty() { Ok(()) } else { Err(failures.join("\n  ")) }
+}
+
+fn run_golden(opcode: &str) {
+    let path = get_path(opcode);
+    let text = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("Cannot read {}: {}", path.display(), e));
+    let cases = parse_cases(&text);
+    assert!(!cases.is_empty(), "{}: no cases", path.display());
+    let failures: Vec<String> = cases.iter().filter_map(|case| {
+        run_case(case).err().map(|e| format!("{}:{} {}\n  {}", path.display(), case.line, case.name, e))
+    }).collect();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+macro_rules! golden {
+    ($($opcode:ident),* $(,)?) => {
+        $(
+            #[test]
+            fn $opcode() {
+                run_golden(stringify!($opcode));
+            }
+        )*
+        #[test]
+        fn every_opcode_has_golden_vectors() {
+            let tested = [$(stringify!($opcode)),*];
+            let mut missing: Vec<String> = DEFINITIONS.iter().map(|def| def.opcode.to_string())
+                .chain(SPECIAL_OPCODES.iter().map(|op| op.to_string()))
+                .filter(|opcode| !tested.contains(&opcode.as_str()) || !get_path(opcode).exists())
+                .collect();
+            missing.sort();
+            assert!(missing.is_empty(), "No golden vectors for {:?}", missing);
+        }
+    };
+}
+
+golden!(
+    vaddps, vpaddd, valignd, vunpcklps, vunpckhps, vshufps, vperm2f128, vextractf128, shufpd, vmulpd, vmovapd, add,
+    vfmadd213pd, vbroadcastsd, vcvtph2ps, vcvtps2ph, vcvtne2ps2bf16, vdpbf16ps, vaddph, vmulph,
+    vfmadd132ph, vfmadd213ph, vfmadd231ph, ldmxcsr, stmxcsr, cmp, vzeroupper, vzeroall,
+);
+
+#[test]
+fn definitions_only_read_their_sources() {
+    // Every lane reads a source operand of the expanded list, never the destination itself or an immediate
+    for def in DEFINITIONS {
+        let expanded = def.get_expanded_operands();
+        let is_source = |i: usize| i > 0 && i < expanded.len() && expanded[i].role != Role::Dst && expanded[i].width != Width::Imm8;
+        let widths = match def.operands[0].width {
+            Width::Fixed(name) => vec![get_vec_reg_size(&name)],
+            Width::Vector => vec![128, 256, 512],
+            Width::Half => vec![128, 256],
+            _ => vec![def.element.size()],
+        };
+        match def.semantics {
+            Semantics::Lanes(_, sources) => {
+                assert!(sources.iter().all(|i| is_source(*i)), "{}: bad sources {:?}", def.opcode, sources);
+            }
+            Semantics::Map(map) | Semantics::Convert(_, map) => {
+                for lanes in widths.iter().map(|width| width / def.element.size()) {
+                    for imm8 in 0..256 {
+                        (0..lanes).filter_map(|i| map(i, lanes, imm8)).for_each(|(operand, _)| {
+                            assert!(is_source(operand), "{}: lane read from operand {}", def.opcode, operand);
+                        });
+                    }
+                }
+            }
+            Semantics::None => {}
+        }
+    }
+}
+
+#[test]
+fn wrong_operand_forms_are_rejected() {
+    let cpu = Arc::new(Mutex::new(CPU::default()));
+    let check = |line: &str| decode(line, cpu.clone()).map(|_| ());
+    assert!(check("vaddps ymm0, ymm1, ymm2").is_ok());
+    assert!(check("vbroadcastsd ymm0, xmm1").is_ok());
+    assert!(check("add eax, 0x7FFFFFFF").is_ok());
+    assert!(check("vmovapd [0x1000], ymm0").is_ok());
+    assert!(check("vaddps ymm0, ymm1, [rsi+rax*4]").is_ok());
+    assert!(check("add eax, [rsi]").is_ok());
+    assert!(check("vaddps ymm0, ymm1, ymmword ptr [rsi]").is_ok());
+    assert!(check("add eax, dword ptr [rsi]").is_ok());
+    assert!(check("vmovapd ymm0, ymmword ptr [rsi]").is_ok());
+    assert!(check("ldmxcsr dword ptr [0x1000]").is_ok());
+    assert!(check("vzeroupper").is_ok());
+    assert_eq!(check("vaddps ymm0, ymm1, xmm2"),
+        Err(String::from("Invalid operands for vaddps (ymm, ymm, xmm), legal forms: xmm, xmm, xmm/m128 | ymm, ymm, ymm/m256 | zmm, zmm, zmm/m512")));
+    assert_eq!(check("vextractf128 xmm0, ymm1"),
+        Err(String::from("Invalid operands for vextractf128 (xmm, ymm), legal forms: xmm, ymm, imm8")));
+    assert!(check("vbroadcastsd zmm0, xmm1").is_err());
+    assert!(check("add rax, ebx").is_err());
+    assert!(check("add al, 256").is_err());
+    assert!(check("vshufps xmm0, xmm1, xmm2, 0x100").is_err());
+    assert!(check("ldmxcsr eax").is_err());
+    assert!(check("vzeroall xmm0").is_err());
+    assert!(check("vaddps ymm0, [rsi], ymm1").is_err());
+    assert!(check("vextractf128 xmm0, [rsi], 1").is_err());
+    assert_eq!(check("vaddps ymm0, ymm1, xmmword ptr [rsi]"),
+        Err(String::from("Invalid operands for vaddps (ymm, ymm, m128), legal forms: xmm, xmm, xmm/m128 | ymm, ymm, ymm/m256 | zmm, zmm, zmm/m512")));
+    assert_eq!(check("vmovapd ymm0, xmmword ptr [rsi]"),
+        Err(String::from("Invalid operands for vmovapd (ymm, m128), legal forms: xmm, xmm/m128 | ymm, ymm/m256 | zmm, zmm/m512 | m128, xmm | m256, ymm | m512, zmm")));
+    assert!(check("add rax, dword ptr [rsi]").is_err());
+    assert!(check("ldmxcsr qword ptr [0x1000]").is_err());
+    assert!(check("vmovapd xmmword ptr [0x1000], ymm0").is_err());
+}
+
+#[test]
+fn malformed_operands_are_rejected() {
+    let cpu = Arc::new(Mutex::new(CPU::default()));
+    let check = |line: &str| decode(line, cpu.clone()).map(|_| ());
+    assert_eq!(check("add rax, -1"), Err(String::from("Invalid operand: -1")));
+    assert_eq!(check("vaddps ymm0, ymm1, foo"), Err(String::from("Invalid operand: FOO")));
+    assert_eq!(check("vaddps xmm, xmm1, xmm2"), Err(String::from("Invalid operand: XMM")));
+    assert_eq!(check("vaddps xmm32, xmm1, xmm2"), Err(String::from("Invalid operand: XMM32")));
+    assert_eq!(check("add rax, 0xZZ"), Err(String::from("Invalid immediate: 0XZZ")));
+    assert_eq!(check("vaddps ymm0, ymm1, [rsi+foo]"), Err(String::from("Invalid GPR: FOO")));
+    assert_eq!(check("vaddps ymm0, ymm1, [0x1G]"), Err(String::from("Invalid hex number: 0X1G")));
+    assert_eq!(check("vaddps ymm0, ymm1, fooword ptr [rsi]"), Err(String::from("Invalid size prefix: fooword ptr")));
+    assert!(get_memory_operands("vaddps ymm0, ymm1, [rsi+foo]", cpu.clone()).is_empty());
+}
+
+#[test]
+fn embedded_broadcasts_must_fill_the_destination() {
+    let cpu = Arc::new(Mutex::new(CPU::default()));
+    let check = |line: &str| decode(line, cpu.clone()).map(|_| ());
+    assert!(check("vaddps zmm0, zmm1, dword ptr [rax]{1to16}").is_ok());
+    assert!(check("vmulpd xmm0, xmm1, [rax]{1to2}").is_ok());
+    assert_eq!(check("vaddps zmm0, zmm1, dword ptr [rax]{1to8}"),
+        Err(String::from("Invalid broadcast {1to8} for vaddps with a zmm destination, expected {1to16}")));
+    assert_eq!(check("vaddps zmm0, zmm1, [rax]{2to16}"), Err(String::from("Invalid broadcast: [rax]{2to16}")));
+    assert!(check("vperm2f128 ymm0, ymm1, [rax]{1to4}, 0").is_err());
+    assert!(check("vcvtph2ps ymm0, [rax]{1to8}").is_err());
+    assert!(check("add rax, [rax]{1to1}").is_err());
+    assert!(check("vcvtne2ps2bf16 zmm0, zmm1, dword ptr [rax]{1to16}").is_ok());
+    assert!(check("vdpbf16ps ymm0, ymm1, dword ptr [rax]{1to8}").is_ok());
+    assert_eq!(check("vmulpd xmm0, xmm1, dword ptr [rax]{1to2}"), Err(String::from("Invalid broadcast element for vmulpd, expected m64")));
+    assert_eq!(check("vaddps xmm0, xmm1, dword ptr [rax]{1to8}"),
+        Err(String::from("Invalid broadcast {1to8} for vaddps with a xmm destination, expected {1to4}")));
+    assert_eq!(check("vcvtne2ps2bf16 zmm0, zmm1, [rax]{1to32}"),
+        Err(String::from("Invalid broadcast {1to32} for vcvtne2ps2bf16 with a zmm destination, expected {1to16}")));
+    assert_eq!(check("shufpd xmm0, [rax]{1to2}, 0"), Err(String::from("shufpd does not support embedded broadcast")));
+    assert!(check("vaddps zmm0, zmm1, zmm2{1to16}").is_err());
+}
+
+#[test]
+fn embedded_rounding_needs_an_evex_register_form() {
+    let cpu = Arc::new(Mutex::new(CPU::default()));
+    let check = |line: &str| decode(line, cpu.clone()).map(|_| ());
+    assert!(check("vaddps zmm0, zmm1, zmm2, {rz-sae}").is_ok());
+    assert!(check("vfmadd213pd zmm0, zmm1, zmm2, {ru-sae}").is_ok());
+    assert!(check("vcvtps2ph ymm0, zmm1, 4, {rd-sae}").is_ok());
+    assert_eq!(check("vaddps ymm0, ymm1, ymm2, {rz-sae}"), Err(String::from("Embedded rounding of vaddps needs zmm register operands")));
+    assert_eq!(check("vaddps zmm0, zmm1, [rax], {rz-sae}"), Err(String::from("Embedded rounding of vaddps needs zmm register operands")));
+    assert_eq!(check("vpaddd zmm0, zmm1, zmm2, {rn-sae}"), Err(String::from("vpaddd does not support embedded rounding")));
+    assert!(check("vcvtne2ps2bf16 zmm0, zmm1, zmm2, {rn-sae}").is_err());
+    assert!(check("valignd zmm0, zmm1, zmm2, 1, {rn-sae}").is_err());
+    assert!(check("cmp rax, rbx, {rz-sae}").is_err());
+}
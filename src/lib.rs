@@ -0,0 +1,29 @@
+use cpulib::{CPU, Utilities, u256, u512};
+use eframe::egui::{self, Vec2, Pos2, Context, Ui, Id, Sense, LayerId, Order, Rect, epaint, Color32};
+use std::sync::Arc;
+
+pub mod reg_visualizer;
+pub mod visualizer_setting;
+pub mod utilities;
+pub mod reg_visualizer_data;
+pub mod animation_fsm;
+pub mod instruction_actuator;
+pub mod mem_visualizer;
+pub mod mxcsr;
+pub mod float16;
+pub mod theme;
+pub mod provenance;
+pub mod value_editor;
+pub mod scenario;
+pub mod expression;
+pub mod debugger;
+pub mod history;
+pub mod trace;
+pub mod simulator;
+pub mod expectation;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cli;
+
+use reg_visualizer::ElementAnimationData;
+use utilities::*;
+use reg_visualizer_data::RegVisualizerData;
@@ -1,44 +1,60 @@
 // DO NOT REMOVE - hide console window on Windows in release
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use cpulib::{CPU, Utilities, u256, u512, VecRegName, GPRName, FLAGSName};
-use egui_code_editor::{CodeEditor, ColorTheme, Syntax};
+use egui_code_editor::{CodeEditor, Syntax};
 use eframe::{App, Frame};
-use eframe::egui::{self, Vec2, Pos2, Context,  CentralPanel, Window, SidePanel, TopBottomPanel, Ui, Id, Sense, CursorIcon, LayerId, Order, InnerResponse, Shape, Rect, epaint, Label, Slider, ComboBox, Color32};
+use eframe::egui::{self, Pos2, Context,  CentralPanel, Window, SidePanel, TopBottomPanel, Slider, Color32};
 use std::sync::{Arc, Mutex};
 
-mod reg_visualizer;
-mod visualizer_setting;
-mod utilities;
-mod reg_visualizer_data;
-mod animation_fsm;
-mod instruction_actuator;
-mod mem_visualizer;
-
-use reg_visualizer::{RegVisualizer, LayoutLocation, ElementAnimationData};
-use visualizer_setting::{VisualizerSetting};
-use utilities::*;
-use reg_visualizer_data::RegVisualizerData;
-use crate::animation_fsm::{AnimationFSM};
-use instruction_actuator::*;
-use mem_visualizer::{MemVisualizer};
+use simd_asm_code_visualization::{mxcsr, expectation};
+use simd_asm_code_visualization::reg_visualizer::RegVisualizer;
+use simd_asm_code_visualization::visualizer_setting::{VisualizerSetting};
+use simd_asm_code_visualization::utilities::*;
+use simd_asm_code_visualization::reg_visualizer_data::RegVisualizerData;
+use simd_asm_code_visualization::animation_fsm::{AnimationFSM};
+use simd_asm_code_visualization::instruction_actuator::*;
+use simd_asm_code_visualization::mem_visualizer::{MemVisualizer};
+use simd_asm_code_visualization::value_editor::{EditHistory, ValueEdit};
+use simd_asm_code_visualization::scenario::{Scenario, Generator};
+use simd_asm_code_visualization::debugger::Debugger;
+use simd_asm_code_visualization::history::History;
+use simd_asm_code_visualization::trace::TraceViewer;
+use simd_asm_code_visualization::simulator::{Simulator, Step};
 
 struct APP {
     // Data
-    cpu: Arc<Mutex<CPU>>,
+    simulator: Simulator,
     reg_visualizer_data: RegVisualizerData,
     // Windows
     register_visualizer: Arc<Mutex<RegVisualizer>>,
     visualizer_setting: VisualizerSetting,
     animation_fsm: AnimationFSM,
     memory_visualizer: MemVisualizer,
+    edit_history: EditHistory,
+    scenario: Scenario,
+    scenario_error: Option<String>, // why the initial state could not be built
+    debugger: Debugger,
+    history: History,
+    trace_viewer: TraceViewer,
     // Code Editor
     code: String,
-    highlight: usize,
+    cursor_line: usize,
+    // Execution
+    running: bool,
+    run_with_animation: bool,
+    run_rate: f32,
+    run_timer: f32,
+    run_to: Option<usize>,
+    resumed: bool, // the first line of a run is not checked, so Run continues from a breakpoint
+    check_pending: bool,
+    error: Option<String>, // why the last step failed
     // Layout
     show_sidebar: bool,
     show_preference: bool,
     show_settings: bool,
+    show_scenario: bool,
+    show_debugger: bool,
+    show_trace: bool,
     show_visualizer: bool,
     show_memory: bool,
     show_wip: bool,
@@ -50,20 +66,38 @@ impl Default for APP {
     fn default() -> Self {
         Self {
             // Data
-            cpu: Arc::new(Mutex::new(CPU::default())),
+            simulator: Simulator::default(),
             reg_visualizer_data: RegVisualizerData::default(),
             // Windows
             register_visualizer: Arc::new(Mutex::new(RegVisualizer::default())),
             visualizer_setting: VisualizerSetting::default(),
             animation_fsm: AnimationFSM::default(),
             memory_visualizer: MemVisualizer::default(),
+            edit_history: EditHistory::default(),
+            scenario: Scenario::default(),
+            scenario_error: None,
+            debugger: Debugger::default(),
+            history: History::default(),
+            trace_viewer: TraceViewer::default(),
             // Code Editor
             code: "".into(),
-            highlight: 0,
+            cursor_line: 1,
+            // Execution
+            running: false,
+            run_with_animation: true,
+            run_rate: 2.0,
+            run_timer: 0.0,
+            run_to: None,
+            resumed: false,
+            check_pending: false,
+            error: None,
             // Layout
             show_sidebar: true,
             show_preference: false,
             show_settings: false,
+            show_scenario: false,
+            show_debugger: false,
+            show_trace: false,
             show_visualizer: false,
             show_memory: false,
             show_wip: false,
@@ -73,46 +107,150 @@ impl Default for APP {
     }
 }
 
+fn apply_edits(simulator: &Simulator, history: &mut EditHistory, edits: Vec<ValueEdit>) -> Result<(), String> {
+    let mut cpu = simulator.cpu.lock().unwrap();
+    let mut state = simulator.state.lock().unwrap();
+    edits.iter().try_for_each(|edit| history.apply(&mut cpu, &mut state.provenance, edit))
+}
+
 impl APP {
+    fn reset(&mut self) {
+        // Rebuild the CPU from the initial state
+        match self.scenario.build() {
+            Ok(cpu) => {
+                self.scenario_error = None;
+                self.simulator.reset(cpu);
+                self.edit_history.clear();
+                self.history.clear();
+                let mut register_visualizer = self.register_visualizer.lock().unwrap();
+                register_visualizer.reset_colors();
+                register_visualizer.reset_highlight();
+                drop(register_visualizer);
+                self.scenario.apply_display(&mut self.reg_visualizer_data);
+                self.running = false;
+                self.check_pending = false;
+                self.error = None;
+                self.debugger.last_hit = None;
+                self.debugger.update_watches(&self.simulator.cpu.lock().unwrap(), &self.reg_visualizer_data.vector_regs_type);
+            }
+            Err(e) => {
+                // Keep the current CPU, the panel shows why
+                self.scenario_error = Some(e);
+                self.show_scenario = true;
+            }
+        }
+    }
     fn step(&mut self, ctx: &Context, with_animation: bool) {
-        if self.highlight < self.code.lines().count() {
-            self.highlight += 1;
-            if self.highlight > 0 {
-                let instruction = self.code.lines().collect::<Vec<&str>>()[self.highlight - 1];
-                let s: String = instruction.into();
-                if s.ends_with(":") {
-                    // Do nothing
-                } else if s.starts_with("jne ") {
-                    let cpu = self.cpu.lock().unwrap();
-                    let flag = cpu.registers.get_flags_value(FLAGSName::RFLAGS);
-                    drop(cpu);
-                    let ctrl = (flag >> 6) & 0b1; // Get ZF
-                    if ctrl == 0 {
-                        let parts: Vec<&str> = s.split_whitespace().collect();
-                        if parts.len() == 2 {
-                            let label: String = (*parts.get(1).unwrap()).into();
-                            self.code.lines().enumerate().for_each(|(index, tmp)| {
-                                let mut tmp: String = tmp.into();
-                                tmp.pop();
-                                if label == tmp {
-                                    self.highlight = index + 1;
-                                }
-                            });
-                        }
-                    }
-                } else {
-                    execute(self.register_visualizer.clone(), self.cpu.clone(), &mut self.animation_fsm, &self.reg_visualizer_data, ctx, instruction, with_animation);
-                }
+        if self.simulator.finished {
+            return;
+        }
+        self.check_pending = true;
+        self.error = None;
+        self.simulator.set_program(&self.code);
+        if let Some(line) = self.simulator.next_instruction() {
+            // Snapshot for Step Back, with the memory the next line may write
+            let (code, _) = split_comment(line);
+            let touched = if code.ends_with(':') || code.starts_with("jne ") { vec![] } else { get_memory_operands(line, self.simulator.cpu.clone()) };
+            self.history.record(&self.simulator.cpu.lock().unwrap(), &self.simulator.state.lock().unwrap(), touched, self.simulator.line, self.simulator.finished, &self.simulator.outcomes);
+        }
+        match self.simulator.advance() {
+            Some(Step::Instruction(decoded)) => {
+                execute(self.register_visualizer.clone(), self.simulator.cpu.clone(), self.simulator.state.clone(), &mut self.animation_fsm, &self.reg_visualizer_data, ctx, decoded, with_animation);
+            }
+            Some(Step::Error(e)) => {
+                self.error = Some(format!("Line {}: {}", self.simulator.line, e));
+                self.running = false;
+            }
+            _ => {}
+        }
+        if self.simulator.finished {
+            self.running = false;
+        }
+    }
+    fn restore(&mut self, index: usize) {
+        let mut cpu = self.simulator.cpu.lock().unwrap();
+        let mut state = self.simulator.state.lock().unwrap();
+        match self.history.restore(&mut cpu, &mut state, index, self.simulator.line, self.simulator.finished, &self.simulator.outcomes) {
+            Some(snapshot) => {
+                self.simulator.line = snapshot.highlight;
+                self.simulator.finished = snapshot.finished;
+                self.simulator.outcomes = snapshot.outcomes.clone();
             }
+            None => return,
+        }
+        // Edits were made to the state that was left, undoing them would overwrite the restored one
+        self.edit_history.clear();
+        self.running = false;
+        self.check_pending = false;
+        self.error = None;
+        self.debugger.last_hit = None;
+        self.debugger.update_watches(&cpu, &self.reg_visualizer_data.vector_regs_type);
+        drop(state);
+        drop(cpu);
+        self.register_visualizer.lock().unwrap().reset_highlight();
+    }
+    fn before_step(&mut self) -> bool {
+        // Breakpoints and Run to Cursor stop before their line executes, true on a hit
+        if std::mem::take(&mut self.resumed) || self.simulator.finished {
+            return false;
+        }
+        let line = self.simulator.line + 1;
+        if self.run_to == Some(line) {
+            return true;
+        }
+        let cpu = self.simulator.cpu.lock().unwrap();
+        let hit = self.debugger.check(line, &cpu, &self.reg_visualizer_data.vector_regs_type);
+        drop(cpu);
+        if hit.is_some() {
+            self.debugger.last_hit = hit;
+            true
         } else {
-            self.highlight = 0;
+            false
+        }
+    }
+    fn after_step(&mut self) {
+        // Watches and `; expect` comments see the state after the instruction
+        self.check_pending = false;
+        self.simulator.check_expectations();
+        let cpu = self.simulator.cpu.lock().unwrap();
+        self.debugger.update_watches(&cpu, &self.reg_visualizer_data.vector_regs_type);
+    }
+    fn run(&mut self, ctx: &Context) {
+        // Continuous stepping, at most one animated step in flight
+        let with_animation = self.run_with_animation && self.show_visualizer;
+        if !self.animation_fsm.is_idle() {
+            ctx.request_repaint();
+            return;
+        }
+        self.run_timer += ctx.input(|input| input.unstable_dt);
+        let interval = 1.0 / self.run_rate;
+        while self.running && self.run_timer >= interval {
+            self.run_timer = if with_animation { 0.0 } else { self.run_timer - interval };
+            if self.before_step() {
+                self.running = false;
+                break;
+            }
+            self.step(ctx, with_animation);
+            if !with_animation {
+                self.after_step();
+            }
+            if with_animation {
+                break;
+            }
         }
+        ctx.request_repaint();
     }
 }
 
 impl App for APP {
     fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
-        ctx.set_visuals(egui::Visuals::dark()); // Use dark mode
+        ctx.set_visuals(self.reg_visualizer_data.theme.get_visuals());
+        if self.check_pending && self.animation_fsm.is_idle() {
+            self.after_step();
+        }
+        if self.running {
+            self.run(ctx);
+        }
         TopBottomPanel::top("top_panel").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
                 if ui.button("Visualizer").triple_clicked() {
@@ -159,6 +297,15 @@ impl App for APP {
                         if ui.add_sized([button_width, 0.0], egui::SelectableLabel::new(self.show_settings, "Settings")).clicked() {
                             self.show_settings = !self.show_settings;
                         }
+                        if ui.add_sized([button_width, 0.0], egui::SelectableLabel::new(self.show_scenario, "Initial State")).clicked() {
+                            self.show_scenario = !self.show_scenario;
+                        }
+                        if ui.add_sized([button_width, 0.0], egui::SelectableLabel::new(self.show_debugger, "Breakpoints")).clicked() {
+                            self.show_debugger = !self.show_debugger;
+                        }
+                        if ui.add_sized([button_width, 0.0], egui::SelectableLabel::new(self.show_trace, "Trace")).clicked() {
+                            self.show_trace = !self.show_trace;
+                        }
                         if ui.add_sized([button_width, 0.0], egui::SelectableLabel::new(self.show_visualizer, "Visualizer")).clicked() {
                             self.show_visualizer = !self.show_visualizer;
                         }
@@ -168,12 +315,74 @@ impl App for APP {
                     });
                     ui.label("Debug Options:");
                     let button_width = ui.available_width();
+                    if ui.add_sized([button_width, 0.0], egui::Button::new("Reset")).clicked() {
+                        self.reset();
+                    }
                     if ui.add_sized([button_width, 0.0], egui::Button::new("Step with Animation")).clicked() {
                         self.step(ctx, true);
                     }
                     if ui.add_sized([button_width, 0.0], egui::Button::new("Step without Animation")).clicked() {
                         self.step(ctx, false);
                     }
+                    let can_go_back = self.history.position() > 0 && !self.running && self.animation_fsm.is_idle();
+                    ui.horizontal(|ui| {
+                        if ui.add_enabled(can_go_back, egui::Button::new("Step Back")).clicked() {
+                            self.restore(self.history.position() - 1);
+                        }
+                        if ui.add_enabled(can_go_back, egui::Button::new("Replay")).on_hover_text("Step back and run the last step again with animation").clicked() {
+                            self.restore(self.history.position() - 1);
+                            self.step(ctx, true);
+                        }
+                    });
+                    let mut position = self.history.position();
+                    let enabled = !self.history.is_empty() && !self.running && self.animation_fsm.is_idle();
+                    if ui.add_enabled(enabled, Slider::new(&mut position, 0..=self.history.len() - 1).text("History")).changed() {
+                        self.restore(position);
+                    }
+                    ui.horizontal(|ui| {
+                        if self.running {
+                            if ui.button("Pause").clicked() {
+                                self.running = false;
+                            }
+                        } else if ui.add_enabled(!self.simulator.finished, egui::Button::new("Run")).clicked() {
+                            self.running = true;
+                            self.resumed = true;
+                            self.debugger.last_hit = None;
+                            self.run_to = None;
+                            self.run_timer = 0.0;
+                        }
+                        if ui.add_enabled(!self.simulator.finished && !self.running, egui::Button::new("Run to Cursor")).clicked() {
+                            self.running = true;
+                            self.resumed = true;
+                            self.debugger.last_hit = None;
+                            self.run_to = Some(self.cursor_line);
+                            self.run_timer = 0.0;
+                        }
+                    });
+                    ui.checkbox(&mut self.run_with_animation, "Run with Animation");
+                    ui.add(Slider::new(&mut self.run_rate, 0.5..=1000.0).logarithmic(true).text("Steps/s"));
+                    if let Some(hit) = &self.debugger.last_hit {
+                        ui.colored_label(Color32::from_rgb(220, 50, 50), hit);
+                    }
+                    if let Some(error) = &self.error {
+                        ui.colored_label(Color32::from_rgb(220, 50, 50), error);
+                    }
+                    let failures = self.simulator.get_failures().len();
+                    if !self.simulator.outcomes.is_empty() {
+                        let text = format!("Expectations: {} passed, {} failed", self.simulator.outcomes.len() - failures, failures);
+                        if failures > 0 {
+                            ui.colored_label(Color32::from_rgb(220, 50, 50), text);
+                        } else {
+                            ui.label(text);
+                        }
+                    }
+                    if self.simulator.finished {
+                        ui.label("Program finished");
+                    } else if self.running {
+                        ui.label(format!("Running: line {}", self.simulator.line));
+                    } else {
+                        ui.label(format!("Line {}, cursor at line {}", self.simulator.line, self.cursor_line));
+                    }
                     ui.label("DEMO:");
                     if ui.add_sized([button_width, 0.0], egui::Button::new("Prefix Sum")).clicked() {
                         self.code = "valignd zmm1, zmm0, zmm2, 15
@@ -184,21 +393,11 @@ valignd zmm1, zmm0, zmm2, 12
 vpaddd zmm0, zmm0, zmm1
 valignd zmm1, zmm0, zmm2, 8
 vpaddd zmm0, zmm0, zmm1".into();
-                        self.cpu = Arc::new(Mutex::new(CPU::default()));
-                        let mut cpu = self.cpu.lock().unwrap();
-                        cpu.registers.set_by_sections::<u32>(VecRegName::ZMM, 0, vec![
-                            1u32, 2u32, 3u32, 4u32, 5u32, 6u32, 7u32, 8u32,
-                            9u32, 10u32, 11u32, 12u32, 13u32, 14u32, 15u32, 16u32
-                        ]);
-                        drop(cpu);
-                        self.reg_visualizer_data.registers[0].clear();
-                        (0..3).for_each(|i| {
-                            self.reg_visualizer_data.registers[0].push(Register::vector(VecRegName::ZMM, i));
-                        });
-                        self.reg_visualizer_data.vector_regs_type.clear();
-                        (0..3).for_each(|i| {
-                            self.reg_visualizer_data.vector_regs_type.insert((VecRegName::ZMM, i), ValueType::U32);
-                        });
+                        self.scenario = Scenario::default()
+                            .with_register("zmm0", ValueType::U32, Generator::Iota { start: 1.0, step: 1.0 }, Some(0))
+                            .with_register("zmm1", ValueType::U32, Generator::Constant(0.0), Some(0))
+                            .with_register("zmm2", ValueType::U32, Generator::Constant(0.0), Some(0));
+                        self.reset();
                     }
                     if ui.add_sized([button_width, 0.0], egui::Button::new("Matrix Transpose")).clicked() {
                         self.code = "vunpcklps ymm8, ymm0, ymm1
@@ -225,25 +424,15 @@ vperm2f128 ymm12, ymm4, ymm6, 0x20
 vperm2f128 ymm13, ymm5, ymm7, 0x20
 vperm2f128 ymm14, ymm4, ymm6, 0x31
 vperm2f128 ymm15, ymm5, ymm7, 0x31".into();
-                        self.cpu = Arc::new(Mutex::new(CPU::default()));
-                        let mut cpu = self.cpu.lock().unwrap();
-                        (0u32..16u32).for_each(|i| {
-                            let vec = if i < 8 {
-                                ((i * 8 + 1)..(i * 8 + 9)).map(|x| x).collect()
+                        self.scenario = (0..16).fold(Scenario::default(), |scenario, i| {
+                            let generator = if i < 8 {
+                                Generator::Iota { start: (i * 8 + 1) as f64, step: 1.0 }
                             } else {
-                                vec![0; 8]
+                                Generator::Constant(0.0)
                             };
-                            cpu.registers.set_by_sections::<u32>(VecRegName::YMM, i as usize, vec);
-                        });
-                        drop(cpu);
-                        self.reg_visualizer_data.registers[0].clear();
-                        (0..16).for_each(|i| {
-                            self.reg_visualizer_data.registers[0].push(Register::vector(VecRegName::YMM, i));
-                        });
-                        self.reg_visualizer_data.vector_regs_type.clear();
-                        (0..16).for_each(|i| {
-                            self.reg_visualizer_data.vector_regs_type.insert((VecRegName::YMM, i), ValueType::U32);
+                            scenario.with_register(&format!("ymm{}", i), ValueType::U32, generator, Some(i / 8))
                         });
+                        self.reset();
                     }
                     if ui.add_sized([button_width, 0.0], egui::Button::new("Matrix Multiplication")).clicked() {
                         self.code = "loop:
@@ -264,52 +453,44 @@ vmovapd [0x40000080 + rdi], ymm4
 add rdi, 32
 cmp rdi, 128
 jne loop".into();
-                        self.cpu = Arc::new(Mutex::new(CPU::default()));
-                        let mut cpu = self.cpu.lock().unwrap();
-                        cpu.registers.set_by_sections::<u64>(VecRegName::YMM, 0, Utilities::f64vec_to_u64vec(vec![
-                            16f64, 15f64, 14f64, 13f64,
-                        ]));
-                        cpu.registers.set_by_sections::<u64>(VecRegName::YMM, 1, Utilities::f64vec_to_u64vec(vec![
-                            12f64, 11f64, 10f64, 9f64,
-                        ]));
-                        cpu.registers.set_by_sections::<u64>(VecRegName::YMM, 2, Utilities::f64vec_to_u64vec(vec![
-                            8f64, 7f64, 6f64, 5f64,
-                        ]));
-                        cpu.registers.set_by_sections::<u64>(VecRegName::YMM, 3, Utilities::f64vec_to_u64vec(vec![
-                            4f64, 3f64, 2f64, 1f64,
-                        ]));
-                        cpu.memory.write_vec::<u64>(0x40000000, Utilities::f64vec_to_u64vec(vec![
-                            1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0, 13.0, 14.0, 15.0, 16.0,
-                        ]));
-                        drop(cpu);
-                        self.reg_visualizer_data.registers[0].clear();
-                        (0..8).for_each(|i| {
-                            self.reg_visualizer_data.registers[0].push(Register::vector(VecRegName::YMM, i));
-                        });
-                        self.reg_visualizer_data.registers[0].push(Register::vector(VecRegName::YMM, 15));
-                        self.reg_visualizer_data.registers[0].push(Register::vector(VecRegName::XMM, 14));
-                        self.reg_visualizer_data.registers[0].push(Register::gpr(GPRName::RDI));
-                        self.reg_visualizer_data.vector_regs_type.clear();
-                        (0..8).for_each(|i| {
-                            self.reg_visualizer_data.vector_regs_type.insert((VecRegName::YMM, i), ValueType::F64);
-                        });
-                        self.reg_visualizer_data.vector_regs_type.insert((VecRegName::YMM, 15), ValueType::F64);
-                        self.reg_visualizer_data.vector_regs_type.insert((VecRegName::XMM, 14), ValueType::F64);
+                        self.scenario = (0..8).fold(Scenario::default(), |scenario, i| {
+                            let generator = if i < 4 {
+                                Generator::Iota { start: (16 - i * 4) as f64, step: -1.0 }
+                            } else {
+                                Generator::Constant(0.0)
+                            };
+                            scenario.with_register(&format!("ymm{}", i), ValueType::F64, generator, Some(0))
+                        })
+                            .with_register("ymm15", ValueType::F64, Generator::Constant(0.0), Some(0))
+                            .with_register("xmm14", ValueType::F64, Generator::Constant(0.0), Some(0))
+                            .with_register("rdi", ValueType::U64, Generator::Constant(0.0), Some(0))
+                            .with_memory(0x40000000, ValueType::F64, 16, Generator::Iota { start: 1.0, step: 1.0 });
+                        self.reset();
                     }
                 });
             });
         }
         CentralPanel::default()
             .show(ctx, |ui| {
-                // show a code editor on central panel
-                CodeEditor::default()
-                    .id_source("code_editor")
-                    .with_rows(24)
-                    .with_fontsize(14.0)
-                    .with_theme(ColorTheme::GRUVBOX)
-                    .with_syntax(Syntax::asm())
-                    .with_numlines(true)
-                    .show(ui, &mut self.code, &mut self.highlight);
+                // show a code editor on central panel, breakpoint and expectation gutters on its left
+                ui.horizontal_top(|ui| {
+                    self.debugger.show_gutter(ui, self.code.lines().count(), 14.0, self.simulator.line);
+                    expectation::show_gutter(ui, self.code.lines().count(), 14.0, &self.simulator.outcomes);
+                    CodeEditor::default()
+                        .id_source("code_editor")
+                        .with_rows(24)
+                        .with_fontsize(14.0)
+                        .with_theme(self.reg_visualizer_data.theme.get_code_theme())
+                        .with_syntax(Syntax::asm())
+                        .with_numlines(true)
+                        .show(ui, &mut self.code, &mut self.simulator.line);
+                    // Remember where the caret is for Run to Cursor
+                    if let Some(state) = ui.memory(|mem| mem.focus()).and_then(|id| egui::TextEdit::load_state(ui.ctx(), id)) {
+                        if let Some(range) = state.ccursor_range() {
+                            self.cursor_line = self.code.chars().take(range.primary.index).filter(|c| *c == '\n').count() + 1;
+                        }
+                    }
+                });
             });
         Window::new("Preference")
             .default_pos(Pos2::new(ctx.available_rect().right() - 200.0, ctx.available_rect().top() + 20.0))
@@ -323,6 +504,31 @@ jne loop".into();
             .show(ctx, |ui| {
                 self.visualizer_setting.show(ui, &mut self.reg_visualizer_data);
             });
+        let mut reset = false;
+        Window::new("Initial State")
+            .default_pos(Pos2::new(ctx.available_rect().right() - 200.0, ctx.available_rect().top() + 20.0))
+            .open(&mut self.show_scenario)
+            .show(ctx, |ui| {
+                if self.scenario.show(ui, self.scenario_error.as_deref()) {
+                    reset = true;
+                }
+            });
+        if reset {
+            self.reset();
+        }
+        Window::new("Breakpoints")
+            .default_pos(Pos2::new(ctx.available_rect().right() - 200.0, ctx.available_rect().top() + 20.0))
+            .open(&mut self.show_debugger)
+            .show(ctx, |ui| {
+                let cpu = self.simulator.cpu.lock().unwrap();
+                self.debugger.show(ui, &cpu, &self.reg_visualizer_data.vector_regs_type);
+            });
+        Window::new("Trace")
+            .default_pos(Pos2::new(ctx.available_rect().right() - 200.0, ctx.available_rect().top() + 20.0))
+            .open(&mut self.show_trace)
+            .show(ctx, |ui| {
+                self.trace_viewer.show(ui, self.simulator.state.lock().unwrap().trace.get_entries());
+            });
         Window::new("Visualizer")
             .default_pos(Pos2::new(ctx.available_rect().right() - 200.0, ctx.available_rect().top() + 20.0))
             .open(&mut self.show_visualizer)
@@ -333,26 +539,45 @@ jne loop".into();
                 });
                 let mut register_visualizer = self.register_visualizer.lock().unwrap();
                 register_visualizer.update(delta_time, self.reg_visualizer_data.factor, self.reg_visualizer_data.min_speed, self.reg_visualizer_data.max_speed);
-                let cpu = self.cpu.lock().unwrap();
-                register_visualizer.show(ui, ctx, &self.reg_visualizer_data, &cpu);
+                let cpu = self.simulator.cpu.lock().unwrap();
+                let state = self.simulator.state.lock().unwrap();
+                register_visualizer.show(ui, ctx, &self.reg_visualizer_data, &cpu, &state.provenance);
+                drop(state);
                 drop(cpu);
                 register_visualizer.move_animation_sequence(ctx);
                 register_visualizer.move_animation_finish(ctx);
                 if register_visualizer.is_animating() {
                     ctx.request_repaint();
                 }
+                let edits = register_visualizer.take_edits();
+                if !edits.is_empty() {
+                    register_visualizer.finish_edit(apply_edits(&self.simulator, &mut self.edit_history, edits));
+                }
                 drop(register_visualizer);
+                if ui.add_enabled(!self.edit_history.is_empty(), egui::Button::new("Undo Edit")).clicked() {
+                    self.edit_history.undo(&mut self.simulator.cpu.lock().unwrap(), &mut self.simulator.state.lock().unwrap().provenance);
+                }
+                ui.separator();
+                mxcsr::show_mxcsr(ui, &mut self.simulator.state.lock().unwrap().mxcsr);
                 // Run Animation FSM
                 self.animation_fsm.run();
             });
+        // Memory operands fly out of the cells drawn here, none while the window is closed
+        let mut memory_cells = vec![];
         Window::new("Memory")
             .default_pos(Pos2::new(ctx.available_rect().right() - 200.0, ctx.available_rect().top() + 20.0))
             .open(&mut self.show_memory)
             .show(ctx, |ui| {
-                let cpu = self.cpu.lock().unwrap();
+                let cpu = self.simulator.cpu.lock().unwrap();
                 self.memory_visualizer.show(ui, ctx, &cpu);
                 drop(cpu);
+                memory_cells = self.memory_visualizer.get_cells();
+                let edits = self.memory_visualizer.take_edits();
+                if !edits.is_empty() {
+                    self.memory_visualizer.finish_edit(apply_edits(&self.simulator, &mut self.edit_history, edits));
+                }
             });
+        self.register_visualizer.lock().unwrap().set_memory_cells(memory_cells);
         Window::new("About")
             .default_pos(Pos2::new(ctx.available_rect().right() - 200.0, ctx.available_rect().top() + 20.0))
             .open(&mut self.show_about)
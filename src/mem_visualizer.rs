@@ -1,8 +1,15 @@
 use super::*;
+use crate::value_editor::{self, ValueEdit, EditLocation};
 
 pub struct MemVisualizer {
     addr: usize,
     data_type: ValueType,
+    // Editing - (row address, lane, text, position) of the value being edited
+    editing: Option<(usize, usize, String, Pos2)>,
+    edits: Vec<ValueEdit>,
+    edit_error: Option<String>, // the popup stays open until the edit is applied
+    // (address, size in bytes, screen rect) of every cell drawn in the last frame
+    cells: Vec<(usize, usize, Rect)>,
 }
 
 impl Default for MemVisualizer {
@@ -10,12 +17,16 @@ impl Default for MemVisualizer {
         Self {
             addr: 0x40000000,
             data_type: ValueType::U8,
+            editing: None,
+            edits: vec![],
+            edit_error: None,
+            cells: vec![],
         }
     }
 }
 
 impl MemVisualizer {
-    pub fn show(&mut self, ui: &mut Ui, _ctx: &Context, cpu: &CPU) {
+    pub fn show(&mut self, ui: &mut Ui, ctx: &Context, cpu: &CPU) {
         ui.horizontal(|ui| {
             ui.label("Data Type:");
             ui.radio_value(&mut self.data_type, ValueType::U8, "U8");
@@ -24,6 +35,12 @@ impl MemVisualizer {
             ui.radio_value(&mut self.data_type, ValueType::F32, "F32");
             ui.radio_value(&mut self.data_type, ValueType::U64, "U64");
             ui.radio_value(&mut self.data_type, ValueType::F64, "F64");
+            ui.radio_value(&mut self.data_type, ValueType::F16, "F16");
+            ui.radio_value(&mut self.data_type, ValueType::BF16, "BF16");
+            ui.radio_value(&mut self.data_type, ValueType::I8, "I8");
+            ui.radio_value(&mut self.data_type, ValueType::I16, "I16");
+            ui.radio_value(&mut self.data_type, ValueType::I32, "I32");
+            ui.radio_value(&mut self.data_type, ValueType::I64, "I64");
         });
         let size = match self.data_type {
             ValueType::U8 => {Vec2::new(20.0, 20.0)}
@@ -32,8 +49,16 @@ impl MemVisualizer {
             ValueType::U64 => {Vec2::new(160.0, 20.0)}
             ValueType::F32 => {Vec2::new(80.0, 20.0)}
             ValueType::F64 => {Vec2::new(160.0, 20.0)}
+            ValueType::F16 => {Vec2::new(40.0, 20.0)}
+            ValueType::BF16 => {Vec2::new(40.0, 20.0)}
+            ValueType::I8 => {Vec2::new(20.0, 20.0)}
+            ValueType::I16 => {Vec2::new(40.0, 20.0)}
+            ValueType::I32 => {Vec2::new(80.0, 20.0)}
+            ValueType::I64 => {Vec2::new(160.0, 20.0)}
             _ => {Vec2::new(0.0, 0.0)}
         };
+        let bytes = self.data_type.size() / 8;
+        self.cells.clear();
         let mut max_width = 0.0;
         for row in 0..16 {
             let addr = self.addr + row * 8;
@@ -62,11 +87,22 @@ impl MemVisualizer {
                         ValueType::U64 => {create_values(cpu.memory.read_vec::<u64>(addr, 64 / 64))}
                         ValueType::F32 => {create_values(Utilities::u32vec_to_f32vec(cpu.memory.read_vec::<u32>(addr, 64 / 32)))}
                         ValueType::F64 => {create_values(Utilities::u64vec_to_f64vec(cpu.memory.read_vec::<u64>(addr, 64 / 64)))}
+                        ValueType::F16 => {create_values_f16(cpu.memory.read_vec::<u16>(addr, 64 / 16))}
+                        ValueType::BF16 => {create_values_bf16(cpu.memory.read_vec::<u16>(addr, 64 / 16))}
+                        ValueType::I8 => {create_values(cpu.memory.read_vec::<u8>(addr, 64 / 8).into_iter().map(|x| x as i8).collect::<Vec<i8>>())}
+                        ValueType::I16 => {create_values(cpu.memory.read_vec::<u16>(addr, 64 / 16).into_iter().map(|x| x as i16).collect::<Vec<i16>>())}
+                        ValueType::I32 => {create_values(cpu.memory.read_vec::<u32>(addr, 64 / 32).into_iter().map(|x| x as i32).collect::<Vec<i32>>())}
+                        ValueType::I64 => {create_values(cpu.memory.read_vec::<u64>(addr, 64 / 64).into_iter().map(|x| x as i64).collect::<Vec<i64>>())}
                         _ => {create_values(vec![0u8; 1])}
                     };
-                    values.iter().for_each(|value| {
+                    values.iter().enumerate().for_each(|(lane, value)| {
                         let text = format!("{}", value);
-                        let (layout_rect, _response) = ui.allocate_exact_size(size, Sense::hover());
+                        let (layout_rect, response) = ui.allocate_exact_size(size, Sense::click());
+                        self.cells.push((addr + lane * bytes, bytes, layout_rect));
+                        if response.clicked() {
+                            self.editing = Some((addr, lane, text.clone(), layout_rect.min));
+                            self.edit_error = None;
+                        }
                         if ui.is_rect_visible(layout_rect) {
                             ui.painter().rect_filled(layout_rect, 0.0, Color32::GRAY);
                             let mut font_size = 20f32;
@@ -98,6 +134,13 @@ impl MemVisualizer {
                 });
             });
         });
+        if let Some((addr, lane, text, position)) = self.editing.as_mut() {
+            match value_editor::show_edit_popup(ctx, "memory_visualizer_edit", *position, text, self.edit_error.as_deref()) {
+                Some(true) => self.edits.push(ValueEdit { location: EditLocation::Memory(*addr), value_type: self.data_type, lane: *lane, text: text.clone() }),
+                Some(false) => self.editing = None,
+                None => {}
+            }
+        }
         ui.horizontal(|ui| {
             if ui.button("Previous").clicked() {
                 self.addr -= 16 * 8;
@@ -107,4 +150,16 @@ impl MemVisualizer {
             }
         });
     }
+    pub fn take_edits(&mut self) -> Vec<ValueEdit> {
+        std::mem::take(&mut self.edits)
+    }
+    pub fn finish_edit(&mut self, result: Result<(), String>) {
+        match result {
+            Ok(()) => self.editing = None,
+            Err(e) => self.edit_error = Some(e),
+        }
+    }
+    pub fn get_cells(&self) -> Vec<(usize, usize, Rect)> {
+        self.cells.clone()
+    }
 }
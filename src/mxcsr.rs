@@ -0,0 +1,390 @@
+use eframe::egui::{Ui, ComboBox};
+use crate::float16::{f16_to_f32, f64_to_f16, f64_to_f16_with_tail};
+
+// MXCSR bits
+pub const MXCSR_IE: u32 = 1 << 0;  // Invalid Operation Flag
+pub const MXCSR_DE: u32 = 1 << 1;  // Denormal Flag
+pub const MXCSR_ZE: u32 = 1 << 2;  // Divide-by-Zero Flag
+pub const MXCSR_OE: u32 = 1 << 3;  // Overflow Flag
+pub const MXCSR_UE: u32 = 1 << 4;  // Underflow Flag
+pub const MXCSR_PE: u32 = 1 << 5;  // Precision (Inexact) Flag
+pub const MXCSR_DAZ: u32 = 1 << 6; // Denormals Are Zeros
+pub const MXCSR_FTZ: u32 = 1 << 15; // Flush To Zero
+pub const MXCSR_RC_SHIFT: u32 = 13;
+pub const MXCSR_RC_MASK: u32 = 0b11 << MXCSR_RC_SHIFT;
+pub const MXCSR_FLAGS_MASK: u32 = 0b111111;
+pub const MXCSR_DEFAULT: u32 = 0x1F80; // All exceptions masked, round to nearest
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum RoundingMode {
+    Nearest,
+    Down,
+    Up,
+    TowardZero,
+}
+
+impl RoundingMode {
+    pub fn from_rc(rc: u32) -> Self {
+        match rc & 0b11 {
+            0 => RoundingMode::Nearest,
+            1 => RoundingMode::Down,
+            2 => RoundingMode::Up,
+            _ => RoundingMode::TowardZero,
+        }
+    }
+    pub fn to_rc(&self) -> u32 {
+        match self {
+            RoundingMode::Nearest => 0,
+            RoundingMode::Down => 1,
+            RoundingMode::Up => 2,
+            RoundingMode::TowardZero => 3,
+        }
+    }
+    pub fn from_embedded(operand: &str) -> Option<Self> {
+        // AVX-512 embedded rounding, e.g. `{rz-sae}`
+        match operand.trim().to_lowercase().as_str() {
+            "{rn-sae}" => Some(RoundingMode::Nearest),
+            "{rd-sae}" => Some(RoundingMode::Down),
+            "{ru-sae}" => Some(RoundingMode::Up),
+            "{rz-sae}" => Some(RoundingMode::TowardZero),
+            _ => None,
+        }
+    }
+}
+
+pub fn get_exception_string(flags: u32) -> String {
+    let mut s = String::new();
+    [(MXCSR_IE, 'I'), (MXCSR_DE, 'D'), (MXCSR_ZE, 'Z'), (MXCSR_OE, 'O'), (MXCSR_UE, 'U'), (MXCSR_PE, 'P')].iter().for_each(|(bit, c)| {
+        if flags & bit != 0 {
+            s.push(*c);
+        }
+    });
+    s
+}
+
+#[derive(Clone)]
+pub struct Mxcsr {
+    pub value: u32,
+    embedded_rounding: Option<RoundingMode>,
+    lane_exceptions: Vec<(usize, u32)>, // (element size, flags) for every calculated lane
+}
+
+impl Default for Mxcsr {
+    fn default() -> Self {
+        Self {
+            value: MXCSR_DEFAULT,
+            embedded_rounding: None,
+            lane_exceptions: vec![],
+        }
+    }
+}
+
+macro_rules! soft_float {
+    ($ft:ident, $next_up:ident, $round:ident, $add:ident, $mul:ident, $size:expr, $quiet_bit:expr, $tiny_scale:expr) => {
+        fn $next_up(x: $ft) -> $ft {
+            if x.is_nan() || x == $ft::INFINITY {
+                x
+            } else if x == 0.0 {
+                $ft::from_bits(1)
+            } else if x > 0.0 {
+                $ft::from_bits(x.to_bits() + 1)
+            } else {
+                $ft::from_bits(x.to_bits() - 1)
+            }
+        }
+
+        fn $round(&mut self, sources: &[$ft], result: $ft, error: $ft) -> $ft {
+            let (rounding, ftz, _) = self.get_control();
+            let mut flags = 0u32;
+            // A NaN operand takes priority over the denormal operand exception
+            if sources.iter().any(|x| x.is_subnormal()) && !sources.iter().any(|x| x.is_nan()) {
+                flags |= MXCSR_DE;
+            }
+            // Invalid: NaN produced from non-NaN inputs, or a signaling NaN input. It hides the denormal operand exception
+            let is_snan = |x: &$ft| x.is_nan() && x.to_bits() & $quiet_bit == 0;
+            if result.is_nan() {
+                let invalid = !sources.iter().any(|x| x.is_nan()) || sources.iter().any(is_snan);
+                self.record_exceptions($size, if invalid { MXCSR_IE } else { flags });
+                // The first NaN operand is propagated, quieted
+                let result = sources.iter().find(|x| x.is_nan()).copied().unwrap_or(result);
+                return $ft::from_bits(result.to_bits() | $quiet_bit);
+            }
+            // Overflow
+            if result.is_infinite() && sources.iter().all(|x| x.is_finite()) {
+                let positive = result > 0.0;
+                let result = match (rounding, positive) {
+                    (RoundingMode::Nearest, _) => result,
+                    (RoundingMode::Up, true) | (RoundingMode::Down, false) => result,
+                    (_, true) => $ft::MAX,
+                    (_, false) => $ft::MIN,
+                };
+                self.record_exceptions($size, flags | MXCSR_OE | MXCSR_PE);
+                return result;
+            }
+            // Directed rounding from the exact error of the round-to-nearest result
+            let mut result = result;
+            if error != 0.0 && !error.is_nan() {
+                flags |= MXCSR_PE;
+                result = match rounding {
+                    RoundingMode::Nearest => result,
+                    RoundingMode::Up => if error > 0.0 { Self::$next_up(result) } else { result },
+                    RoundingMode::Down => if error < 0.0 { -Self::$next_up(-result) } else { result },
+                    RoundingMode::TowardZero => {
+                        if result > 0.0 && error < 0.0 {
+                            -Self::$next_up(-result)
+                        } else if result < 0.0 && error > 0.0 {
+                            Self::$next_up(result)
+                        } else {
+                            result
+                        }
+                    }
+                };
+                // Rounded away from ±MAX
+                if result.is_infinite() {
+                    flags |= MXCSR_OE;
+                }
+                // Underflow: tiny and inexact
+                if result.is_subnormal() || result == 0.0 {
+                    flags |= MXCSR_UE;
+                }
+            }
+            // FTZ flushes exact tiny results as well
+            if ftz && result.is_subnormal() {
+                result = if result.is_sign_negative() { -0.0 } else { 0.0 };
+                flags |= MXCSR_UE | MXCSR_PE;
+            }
+            self.record_exceptions($size, flags);
+            result
+        }
+
+        pub fn $add(&mut self, a: $ft, b: $ft) -> $ft {
+            let (rounding, _, daz) = self.get_control();
+            let a = if daz && a.is_subnormal() { if a.is_sign_negative() { -0.0 } else { 0.0 } } else { a };
+            let b = if daz && b.is_subnormal() { if b.is_sign_negative() { -0.0 } else { 0.0 } } else { b };
+            // TwoSum: exact error of the rounded sum
+            let s = a + b;
+            let bb = s - a;
+            let error = (a - (s - bb)) + (b - bb);
+            // x + -x is -0 when rounding down
+            let s = if s == 0.0 && a.to_bits() != b.to_bits() && rounding == RoundingMode::Down { -0.0 } else { s };
+            self.$round(&[a, b], s, error)
+        }
+
+        pub fn $mul(&mut self, a: $ft, b: $ft) -> $ft {
+            let (_, _, daz) = self.get_control();
+            let a = if daz && a.is_subnormal() { if a.is_sign_negative() { -0.0 } else { 0.0 } } else { a };
+            let b = if daz && b.is_subnormal() { if b.is_sign_negative() { -0.0 } else { 0.0 } } else { b };
+            // FMA: exact error of the rounded product
+            let p = a * b;
+            let error = if p == 0.0 && a != 0.0 && b != 0.0 && a.is_finite() && b.is_finite() {
+                $ft::from_bits(1).copysign(p)
+            } else if p.abs() < $ft::MIN_POSITIVE / $ft::EPSILON * 4.0 && a.is_finite() && b.is_finite() {
+                // The error of a product this small is below the smallest subnormal, its sign is taken from the operands scaled up
+                let (small, large) = if a.abs() < b.abs() { (a, b) } else { (b, a) };
+                let error = (small * $tiny_scale).mul_add(large, -p * $tiny_scale);
+                if error == 0.0 { 0.0 } else { $ft::from_bits(1).copysign(error) }
+            } else {
+                a.mul_add(b, -p)
+            };
+            self.$round(&[a, b], p, error)
+        }
+    };
+}
+
+impl Mxcsr {
+    pub fn get_rounding(&self) -> RoundingMode {
+        // MXCSR.RC, without embedded rounding
+        RoundingMode::from_rc((self.value & MXCSR_RC_MASK) >> MXCSR_RC_SHIFT)
+    }
+
+    pub fn set_embedded_rounding(&mut self, mode: Option<RoundingMode>) {
+        self.embedded_rounding = mode;
+    }
+
+    pub fn clear_lane_exceptions(&mut self) {
+        self.lane_exceptions.clear();
+    }
+
+    pub fn take_lane_exceptions(&mut self, width: usize) -> Vec<(usize, usize, u32)> {
+        // (bit offset, element size, flags) inside a destination of `width` bits
+        let mut offset = 0;
+        std::mem::take(&mut self.lane_exceptions).into_iter().map(|(size, flags)| {
+            let lane = (offset % width, size, flags);
+            offset += size;
+            lane
+        }).collect()
+    }
+
+    fn record_exceptions(&mut self, size: usize, flags: u32) {
+        if self.embedded_rounding.is_some() {
+            // SAE: suppress all exceptions
+            self.lane_exceptions.push((size, 0));
+        } else {
+            self.value |= flags & MXCSR_FLAGS_MASK;
+            self.lane_exceptions.push((size, flags));
+        }
+    }
+
+    fn get_control(&self) -> (RoundingMode, bool, bool) {
+        let rounding = self.embedded_rounding.unwrap_or_else(|| self.get_rounding());
+        (rounding, self.value & MXCSR_FTZ != 0, self.value & MXCSR_DAZ != 0)
+    }
+
+    soft_float!(f32, next_up_f32, round_f32, add_f32, mul_f32, 32, 1u32 << 22, f32::from_bits(227 << 23));  // 2^100
+    soft_float!(f64, next_up_f64, round_f64, add_f64, mul_f64, 64, 1u64 << 51, f64::from_bits(1623 << 52)); // 2^600
+
+    pub fn fma_f32(&mut self, a: f32, b: f32, c: f32) -> f32 {
+        // a * b + c rounded once, like the FMA instructions
+        let (rounding, _, daz) = self.get_control();
+        let flush = |x: f32| if daz && x.is_subnormal() { if x.is_sign_negative() { -0.0 } else { 0.0 } } else { x };
+        let (a, b, c) = (flush(a), flush(b), flush(c));
+        let r = a.mul_add(b, c);
+        // The product is exact in f64, TwoSum with c leaves the exact tail of the sum
+        let (p, c64) = (a as f64 * b as f64, c as f64);
+        let s = p + c64;
+        let bb = s - p;
+        let tail = (p - (s - bb)) + (c64 - bb);
+        let error = if r.is_finite() && s.is_finite() { (s - r as f64) + tail } else { 0.0 };
+        // An error below the smallest f32 keeps its sign
+        let error = if error == 0.0 { 0.0 } else { (error as f32).abs().max(f32::from_bits(1)).copysign(error as f32) };
+        let r = if error == 0.0 && is_negative_zero_sum(rounding, r as f64, a as f64, b as f64, c as f64) { -0.0 } else { r };
+        self.round_f32(&[a, b, c], r, error)
+    }
+
+    pub fn fma_f64(&mut self, a: f64, b: f64, c: f64) -> f64 {
+        // a * b + c rounded once, like the FMA instructions
+        let (rounding, _, daz) = self.get_control();
+        let flush = |x: f64| if daz && x.is_subnormal() { if x.is_sign_negative() { -0.0 } else { 0.0 } } else { x };
+        let (a, b, c) = (flush(a), flush(b), flush(c));
+        let r = a.mul_add(b, c);
+        let p = a * b;
+        let error = if !r.is_finite() || !p.is_finite() {
+            0.0
+        } else if p.abs() >= f64::MIN_POSITIVE / f64::EPSILON * 4.0 || a == 0.0 || b == 0.0 {
+            get_fma_error(a, b, c, r)
+        } else if c.abs().max(r.abs()) >= f64::from_bits(623 << 52) {
+            // A product below 2^-968 is less than half an ulp of a c above 2^-400, r is c
+            f64::from_bits(1).copysign(p)
+        } else {
+            // The product error would be below the smallest subnormal: the same with a, b scaled by 2^600 and c, r by 2^1200
+            let scale = f64::from_bits(1623 << 52);
+            let error = get_fma_error(a * scale, b * scale, c * scale * scale, r * scale * scale);
+            if error == 0.0 { 0.0 } else { f64::from_bits(1).copysign(error) }
+        };
+        let r = if error == 0.0 && is_negative_zero_sum(rounding, r, a, b, c) { -0.0 } else { r };
+        self.round_f64(&[a, b, c], r, error)
+    }
+
+    pub fn convert_f16_to_f32(&mut self, x: u16) -> f32 {
+        // Exact, a signaling NaN is quieted. Half denormals are neither flushed by DAZ nor reported
+        let mut flags = 0u32;
+        let mut result = f16_to_f32(x);
+        if result.is_nan() && x & 0x200 == 0 {
+            flags |= MXCSR_IE;
+            result = f32::from_bits(result.to_bits() | 1u32 << 22);
+        }
+        self.record_exceptions(32, flags);
+        result
+    }
+
+    pub fn convert_f32_to_f16(&mut self, x: f32, rounding: Option<RoundingMode>) -> u16 {
+        // rounding: an explicit mode like the imm8 of VCVTPS2PH, None for MXCSR.RC; embedded rounding overrides both
+        // A tiny half result is not flushed by FTZ
+        let (control, _, daz) = self.get_control();
+        let rounding = if self.embedded_rounding.is_some() { control } else { rounding.unwrap_or(control) };
+        let mut flags = 0u32;
+        let x = if x.is_subnormal() {
+            if daz { 0.0f32.copysign(x) } else { flags |= MXCSR_DE; x }
+        } else {
+            x
+        };
+        let result = f64_to_f16(x as f64, rounding);
+        if x.is_nan() {
+            if x.to_bits() & 1u32 << 22 == 0 {
+                flags |= MXCSR_IE;
+            }
+        } else {
+            flags |= get_f16_flags(x as f64, 0.0, result, rounding);
+        }
+        self.record_exceptions(16, flags);
+        result
+    }
+
+    pub fn round_f16(&mut self, sources: &[u16], value: f64, error: f64) -> u16 {
+        // value + error rounded once to F16. FP16 instructions ignore DAZ and FTZ and never report DE
+        let (rounding, _, _) = self.get_control();
+        let is_nan = |x: &u16| x & 0x7C00 == 0x7C00 && x & 0x3FF != 0;
+        let is_snan = |x: &u16| is_nan(x) && x & 0x200 == 0;
+        let result = f64_to_f16_with_tail(value, error, rounding);
+        let flags = if sources.iter().any(is_snan) || (value.is_nan() && !sources.iter().any(is_nan)) {
+            MXCSR_IE
+        } else {
+            get_f16_flags(value, error, result, rounding)
+        };
+        self.record_exceptions(16, flags);
+        result
+    }
+}
+
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    // a + b = s + e exactly
+    let s = a + b;
+    let bb = s - a;
+    (s, (a - (s - bb)) + (b - bb))
+}
+
+fn get_fma_error(a: f64, b: f64, c: f64, r: f64) -> f64 {
+    // a * b + c - r from error-free transformations, close enough for its sign
+    let p = a * b;
+    let (s, se) = two_sum(p, c);
+    let (t, te) = two_sum(se, a.mul_add(b, -p));
+    let (u, ue) = two_sum(s - r, t);
+    u + (ue + te)
+}
+
+fn is_negative_zero_sum(rounding: RoundingMode, r: f64, a: f64, b: f64, c: f64) -> bool {
+    // An exact zero a * b + c is -0 when rounding down, unless both terms are zeros of the same sign
+    let is_negative_product = a.is_sign_negative() != b.is_sign_negative();
+    r == 0.0 && rounding == RoundingMode::Down && !((a == 0.0 || b == 0.0) && c == 0.0 && is_negative_product == c.is_sign_negative())
+}
+
+fn get_f16_flags(value: f64, error: f64, result: u16, rounding: RoundingMode) -> u32 {
+    // Overflow and precision of value + error rounded to the F16 `result`, an overflow clamped to ±MAX included
+    if value.is_finite() && (result & 0x7FFF == 0x7C00 || value.abs() >= 65536.0) {
+        MXCSR_OE | MXCSR_PE
+    } else if value.is_finite() && (f16_to_f32(result) as f64 != value || error != 0.0) {
+        // Underflow: tiny and inexact. Tiny is below 2^-14 after rounding with an unbounded exponent, here scaled by 2^10
+        let is_tiny = f64_to_f16_with_tail(value * 1024.0, error * 1024.0, rounding) & 0x7FFF < 0x2C00;
+        MXCSR_PE | if is_tiny { MXCSR_UE } else { 0 }
+    } else {
+        0
+    }
+}
+
+pub fn show_mxcsr(ui: &mut Ui, mxcsr: &mut Mxcsr) {
+    let mut value = mxcsr.value;
+    ui.horizontal(|ui| {
+        ui.label(format!("MXCSR: 0x{:04X}", value));
+        let mut rounding = RoundingMode::from_rc((value & MXCSR_RC_MASK) >> MXCSR_RC_SHIFT);
+        ComboBox::from_label("RC")
+            .selected_text(format!("{:?}", rounding))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut rounding, RoundingMode::Nearest, "Nearest");
+                ui.selectable_value(&mut rounding, RoundingMode::Down, "Down");
+                ui.selectable_value(&mut rounding, RoundingMode::Up, "Up");
+                ui.selectable_value(&mut rounding, RoundingMode::TowardZero, "TowardZero");
+            });
+        value = (value & !MXCSR_RC_MASK) | (rounding.to_rc() << MXCSR_RC_SHIFT);
+        let mut ftz = value & MXCSR_FTZ != 0;
+        let mut daz = value & MXCSR_DAZ != 0;
+        ui.checkbox(&mut ftz, "FTZ");
+        ui.checkbox(&mut daz, "DAZ");
+        value = if ftz { value | MXCSR_FTZ } else { value & !MXCSR_FTZ };
+        value = if daz { value | MXCSR_DAZ } else { value & !MXCSR_DAZ };
+        ui.label(format!("Flags: {}", get_exception_string(value)));
+        if ui.button("Clear Flags").clicked() {
+            value &= !MXCSR_FLAGS_MASK;
+        }
+    });
+    mxcsr.value = value;
+}
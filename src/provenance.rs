@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use cpulib::VecRegName;
+use crate::utilities::{Register, RegType, get_gpr_alias_info, get_reg_name};
+
+// Shadow state: where every byte of the registers and memory originally came from
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Location {
+    Register(Register, usize), // full-width register (ZMM or 64-bit GPR), byte offset
+    Memory(u64),
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Origin {
+    Initial(Location),  // initial register lane or memory byte
+    Computation(usize), // computation node
+    Zero,
+}
+
+#[derive(Clone)]
+pub struct ComputationNode {
+    pub instruction: String,
+    pub step: usize,
+}
+
+#[derive(Default, Clone)]
+pub struct Provenance {
+    origins: HashMap<Location, Origin>,
+    nodes: Vec<ComputationNode>,
+    step: usize,
+}
+
+pub fn get_register_locations(reg: &Register) -> Vec<Location> {
+    // Bytes of the full-width register covered by `reg`
+    match reg.get_type() {
+        RegType::Vector => {
+            let (name, index) = reg.get_vector();
+            let size = match name {
+                VecRegName::XMM => 16,
+                VecRegName::YMM => 32,
+                _ => 64,
+            };
+            (0..size).map(|i| Location::Register(Register::vector(VecRegName::ZMM, index), i)).collect()
+        }
+        RegType::GPR => {
+            let (full, offset) = get_gpr_alias_info(&reg.get_gpr());
+            let size = cpulib::Utilities::get_gpr_size(&reg.get_gpr()) as usize / 8;
+            (0..size).map(|i| Location::Register(Register::gpr(full), offset / 8 + i)).collect()
+        }
+        RegType::None => vec![],
+    }
+}
+
+impl Provenance {
+    pub fn get_origin(&self, location: &Location) -> Origin {
+        *self.origins.get(location).unwrap_or(&Origin::Initial(*location))
+    }
+
+    pub fn set_origins(&mut self, origins: Vec<(Location, Origin)>) {
+        origins.into_iter().for_each(|(location, origin)| {
+            if origin == Origin::Initial(location) {
+                self.origins.remove(&location);
+            } else {
+                self.origins.insert(location, origin);
+            }
+        });
+    }
+
+    pub fn next_step(&mut self) {
+        self.step += 1;
+    }
+
+    pub fn add_node(&mut self, instruction: &str) -> usize {
+        self.nodes.push(ComputationNode { instruction: instruction.to_string(), step: self.step });
+        self.nodes.len() - 1
+    }
+
+    pub fn get_node(&self, id: usize) -> Option<ComputationNode> {
+        self.nodes.get(id).cloned()
+    }
+
+    pub fn get_lane_origins(&self, reg: &Register, lane_bytes: usize) -> Vec<Vec<Origin>> {
+        let locations = get_register_locations(reg);
+        locations.chunks(lane_bytes.max(1)).map(|lane| lane.iter().map(|location| self.get_origin(location)).collect()).collect()
+    }
+
+    pub fn describe_lane(&self, reg: &Register, origins: &[Origin]) -> String {
+        // e.g. "YMM2[3]", "[0x40000008]", "vpaddd zmm0, zmm0, zmm1 (step 4)"; registers use the naming of `reg`
+        let size = origins.len();
+        let mut parts: Vec<String> = vec![];
+        origins.iter().enumerate().for_each(|(i, origin)| {
+            let part = match origin {
+                Origin::Initial(Location::Register(src, byte)) => {
+                    match src.get_type() {
+                        RegType::Vector if reg.get_type() == RegType::Vector => {
+                            let name = get_reg_name(&Register::vector(reg.get_vector().0, src.get_vector().1));
+                            if byte % size == i { format!("{}[{}]", name, byte / size) } else { format!("{} byte {}", name, byte) }
+                        }
+                        _ => format!("{} byte {}", get_reg_name(src), byte),
+                    }
+                }
+                Origin::Initial(Location::Memory(addr)) => format!("[0x{:X}]", addr - i as u64),
+                Origin::Computation(id) => match self.get_node(*id) {
+                    Some(node) => format!("{} (step {})", node.instruction, node.step),
+                    None => "?".into(),
+                },
+                Origin::Zero => "zero".into(),
+            };
+            if !parts.contains(&part) {
+                parts.push(part);
+            }
+        });
+        parts.join(", ")
+    }
+}
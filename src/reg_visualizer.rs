@@ -3,13 +3,51 @@ use std::hash::Hash;
 use std::sync::Mutex;
 use eframe::egui::{self, Vec2, Pos2, Ui, Color32, Stroke};
 use super::*;
+use crate::theme::{ColorScheme, get_register_color, get_gradient_color, get_heatmap_color, get_origin_color};
+use crate::provenance::Provenance;
+use crate::value_editor::{self, ValueEdit, EditLocation};
 use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
 
 fn get_y() -> f32 {
     25f32
 }
 
-pub fn get_size_from_value(value: &Value) -> Vec2 {
+fn get_bracket_height() -> f32 {
+    16f32
+}
+
+fn get_bit_width() -> f32 {
+    8f32
+}
+
+pub fn get_bit_grid_size(value: &Value) -> Vec2 {
+    Vec2::new(value.size() as f32 * get_bit_width(), get_y())
+}
+
+fn get_bit_color(value: &Value, bit: usize) -> Color32 {
+    // `bit` counts from the most significant bit; sign/exponent/mantissa fields for floats
+    let exponent_bits = match value {
+        Value::F32(_) => 8,
+        Value::F64(_) => 11,
+        Value::F16(_) => 5,
+        Value::BF16(_) => 8,
+        _ => return Color32::from_rgb(200, 200, 200),
+    };
+    if bit == 0 {
+        Color32::from_rgb(230, 120, 120)
+    } else if bit <= exponent_bits {
+        Color32::from_rgb(120, 200, 120)
+    } else {
+        Color32::from_rgb(120, 160, 230)
+    }
+}
+
+pub fn get_size_from_value(value: &Value, radix: Radix) -> Vec2 {
+    match radix {
+        Radix::Binary => return Vec2::new(value.size() as f32 * 4.0, get_y()),
+        Radix::Char => return Vec2::new((value.size() / 8 * 12).max(15) as f32, get_y()),
+        _ => {}
+    }
     match value {
         Value::U8(_) => {Vec2::new(15.0, get_y())}
         Value::U16(_) => {Vec2::new(30.0, get_y())}
@@ -20,6 +58,12 @@ pub fn get_size_from_value(value: &Value) -> Vec2 {
         Value::U512(_) => {Vec2::new(920.0, get_y())}
         Value::F32(_) => {Vec2::new(60.0, get_y())}
         Value::F64(_) => {Vec2::new(120.0, get_y())}
+        Value::F16(_) => {Vec2::new(30.0, get_y())}
+        Value::BF16(_) => {Vec2::new(30.0, get_y())}
+        Value::I8(_) => {Vec2::new(15.0, get_y())}
+        Value::I16(_) => {Vec2::new(30.0, get_y())}
+        Value::I32(_) => {Vec2::new(60.0, get_y())}
+        Value::I64(_) => {Vec2::new(120.0, get_y())}
     }
 }
 
@@ -95,16 +139,31 @@ impl ElementOrder {
     }
 }
 
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum UpperLaneState {
+    None,
+    Zeroed,
+    Kept,
+}
+
 pub struct Element {
     // Data
     value: Value,
     string: Option<String>,
+    radix: Radix,
+    bit_grid: bool,
+    tooltip: Option<String>,
+    previous: Option<Value>,
     // Animation
     display: bool,
     order: ElementOrder,
     color: Color32,
     border_color: Color32,
     is_highlight: bool,
+    is_rewritten: bool,
+    upper_lane_state: UpperLaneState,
+    zeroed_bits: usize,
+    exceptions: u32,
     layout_position: Pos2,
     position: Pos2,
     target_position: Pos2,
@@ -119,12 +178,20 @@ impl Default for Element {
             // Data
             value: Value::default(),
             string: None,
+            radix: Radix::Decimal,
+            bit_grid: false,
+            tooltip: None,
+            previous: None,
             // Animation
             display: true,
             order: ElementOrder::Normal,
             color: Color32::TRANSPARENT,
             border_color: Color32::TRANSPARENT,
             is_highlight: false,
+            is_rewritten: false,
+            upper_lane_state: UpperLaneState::None,
+            zeroed_bits: 0,
+            exceptions: 0,
             layout_position: Pos2::new(0f32, 0f32),
             position: Pos2::new(0f32, 0f32),
             target_position: Pos2::new(0f32, 0f32),
@@ -142,6 +209,18 @@ impl Element {
             ..self
         }
     }
+    fn with_radix(self, radix: Radix) -> Self {
+        Self {
+            radix,
+            ..self
+        }
+    }
+    fn with_bit_grid(self, bit_grid: bool) -> Self {
+        Self {
+            bit_grid,
+            ..self
+        }
+    }
     fn with_position(self, position: Pos2) -> Self {
         Self {
             layout_position: position,
@@ -181,7 +260,7 @@ impl Element {
 
 impl Element {
     fn show(&self, ui: &mut Ui) {
-        let rect_size = get_size_from_value(&self.value);
+        let rect_size = self.get_rect().size();
         // Link
         let start = self.position + Vec2::new(rect_size.x / 2f32, rect_size.y / 2f32);
         let end = self.target_position + Vec2::new(rect_size.x / 2f32, rect_size.y / 2f32);
@@ -213,46 +292,121 @@ impl Element {
             0.0,
             self.color,
         );
+        // Bit Grid
+        let bits = self.get_bits();
+        if let Some(bits) = &bits {
+            self.show_bits(ui, rect_size, bits);
+        }
         // Display Border
         ui.painter().rect_stroke(
             Rect::from_min_size(self.position, rect_size),
             0.0,
-            egui::Stroke::new(2.0, if self.is_highlight {Color32::RED} else if self.animating {Color32::KHAKI} else {self.border_color}),
+            egui::Stroke::new(2.0, if self.is_highlight {Color32::RED} else if self.animating {Color32::KHAKI} else if self.is_rewritten {Color32::from_rgb(200, 120, 255)} else {
+                match self.upper_lane_state {
+                    UpperLaneState::Zeroed => Color32::YELLOW,
+                    UpperLaneState::Kept => Color32::GREEN,
+                    UpperLaneState::None => self.border_color,
+                }
+            }),
         );
-        // Adaptive Text Size
-        let mut font_size = 20f32;
-        let mut text_size;
-        loop {
+        // Zeroed upper bits inside the element, drawn over its high-order (left) part
+        if self.zeroed_bits > 0 {
+            let width = rect_size.x * self.zeroed_bits as f32 / self.value.size() as f32;
+            ui.painter().rect_stroke(
+                Rect::from_min_size(self.position, Vec2::new(width, rect_size.y)).shrink(2.0),
+                0.0,
+                egui::Stroke::new(2.0, Color32::YELLOW),
+            );
+        }
+        if bits.is_none() {
+            // Adaptive Text Size
+            let mut font_size = 20f32;
+            let mut text_size;
+            loop {
+                let galley = ui.painter().layout_no_wrap(
+                    if let Some(text) = &self.string {
+                        format!("{}", text)
+                    } else {
+                        format_value(&self.value, self.radix)
+                    },
+                    egui::FontId::new(font_size, egui::FontFamily::Monospace),
+                    Color32::BLACK,
+                );
+                text_size = galley.size().x;
+                if text_size < rect_size.x - 4.0 { // Subtract a small margin
+                    break;
+                }
+                font_size -= 1.0;
+                if font_size <= 1.0 { // Minimum font size
+                    break;
+                }
+            }
+            // Display Text
+            let text_pos = self.position + rect_size / 2.0 - Vec2::new(text_size / 2.0, font_size / 2.0);
             let galley = ui.painter().layout_no_wrap(
                 if let Some(text) = &self.string {
                     format!("{}", text)
                 } else {
-                    format!("{}", self.value)
+                    format_value(&self.value, self.radix)
                 },
                 egui::FontId::new(font_size, egui::FontFamily::Monospace),
                 Color32::BLACK,
             );
-            text_size = galley.size().x;
-            if text_size < rect_size.x - 4.0 { // Subtract a small margin
-                break;
-            }
-            font_size -= 1.0;
-            if font_size <= 1.0 { // Minimum font size
-                break;
-            }
+            ui.painter().galley(text_pos, galley, Color32::TRANSPARENT);
         }
-        // Display Text
-        let text_pos = self.position + rect_size / 2.0 - Vec2::new(text_size / 2.0, font_size / 2.0);
-        let galley = ui.painter().layout_no_wrap(
-            if let Some(text) = &self.string {
-                format!("{}", text)
-            } else {
-                format!("{}", self.value)
-            },
-            egui::FontId::new(font_size, egui::FontFamily::Monospace),
-            Color32::BLACK,
-        );
-        ui.painter().galley(text_pos, galley, Color32::TRANSPARENT);
+        // Ghost of the previous value
+        if let Some(previous) = &self.previous {
+            ui.painter().text(self.position + Vec2::new(2.0, 1.0), egui::Align2::LEFT_TOP, format_value(previous, self.radix),
+                              egui::FontId::new(8.0, egui::FontFamily::Monospace), Color32::from_black_alpha(110));
+        }
+        // Floating-point Exception Badge
+        if self.exceptions != 0 {
+            let badge = ui.painter().layout_no_wrap(
+                mxcsr::get_exception_string(self.exceptions),
+                egui::FontId::new(9.0, egui::FontFamily::Monospace),
+                Color32::WHITE,
+            );
+            let badge_rect = Rect::from_min_size(
+                self.position + Vec2::new(rect_size.x - badge.size().x - 2.0, 0.0),
+                badge.size(),
+            );
+            ui.painter().rect_filled(badge_rect, 2.0, Color32::DARK_RED);
+            ui.painter().galley(badge_rect.min, badge, Color32::TRANSPARENT);
+        }
+    }
+    fn get_rect(&self) -> Rect {
+        Rect::from_min_size(self.position, if self.bit_grid {
+            get_bit_grid_size(&self.value)
+        } else {
+            get_size_from_value(&self.value, self.radix)
+        })
+    }
+    fn get_bits(&self) -> Option<String> {
+        if !self.bit_grid {
+            return None;
+        }
+        // A moved-in value arrives as a binary string, other annotations are shown as text
+        match &self.string {
+            Some(s) if s.len() == self.value.size() && s.chars().all(|c| c == '0' || c == '1') => Some(s.clone()),
+            Some(_) => None,
+            None => Some(format_value(&self.value, Radix::Binary)),
+        }
+    }
+    fn show_bits(&self, ui: &mut Ui, rect_size: Vec2, bits: &str) {
+        let cell = Vec2::new(rect_size.x / bits.len() as f32, rect_size.y);
+        bits.chars().enumerate().for_each(|(i, c)| {
+            let min = self.position + Vec2::new(i as f32 * cell.x, 0.0);
+            let color = get_bit_color(&self.value, i);
+            let (fill, text_color) = if c == '1' {(color, Color32::BLACK)} else {(color.gamma_multiply(0.3), Color32::GRAY)};
+            ui.painter().rect_filled(Rect::from_min_size(min, cell), 0.0, fill);
+            // Nibble and byte boundaries
+            if i != 0 && i % 4 == 0 {
+                let width = if i % 8 == 0 {1.0} else {0.5};
+                ui.painter().line_segment([min, min + Vec2::new(0.0, cell.y)], Stroke::new(width, Color32::DARK_GRAY));
+            }
+            ui.painter().text(min + cell / 2.0, egui::Align2::CENTER_CENTER, c,
+                              egui::FontId::new(cell.x.min(12.0), egui::FontFamily::Monospace), text_color);
+        });
     }
     fn update(&mut self, delta_time: f32, factor: f32, min_speed: f32, max_speed: f32) {
         let direction = self.target_position - self.position;
@@ -304,6 +458,11 @@ impl Element {
     }
     pub fn reset_highlight(&mut self) {
         self.is_highlight = false;
+        self.is_rewritten = false;
+        self.previous = None;
+        self.upper_lane_state = UpperLaneState::None;
+        self.zeroed_bits = 0;
+        self.exceptions = 0;
     }
 }
 
@@ -315,6 +474,16 @@ pub struct RegVisualizer {
     animation_config: HashMap<Register, RegAnimationConfig>,
     animation_layout_data: HashMap<(Register, LayoutLocation), Vec<Vec<(Pos2, Vec2)>>>,
     animation_elements: HashMap<(Register, LayoutLocation), Vec<Vec<Element>>>,
+    // Memory operands - cells of the memory window and the elements pulled out of them
+    memory_cells: Vec<(usize, usize, Rect)>,
+    memory_elements: Vec<Element>,
+    // Colour Data - source colours of the lanes, kept across instructions
+    color_scheme: ColorScheme,
+    lane_colors: HashMap<Register, Vec<Color32>>,
+    // Editing - (register, lane, type, text, position) of the element being edited
+    editing: Option<(Register, usize, ValueType, String, Pos2)>,
+    edits: Vec<ValueEdit>,
+    edit_error: Option<String>, // the popup stays open until the edit is applied
     // Animation Sequence
     sender: Sender<AnimationControlMsg>,
     receiver: Receiver<AnimationControlMsg>,
@@ -339,6 +508,16 @@ impl Default for RegVisualizer {
             animation_config: HashMap::new(),
             animation_layout_data: HashMap::new(),
             animation_elements: HashMap::new(),
+            // Memory operands
+            memory_cells: vec![],
+            memory_elements: vec![],
+            // Colour Data
+            color_scheme: ColorScheme::Uniform,
+            lane_colors: HashMap::new(),
+            // Editing
+            editing: None,
+            edits: vec![],
+            edit_error: None,
             // Animation Sequence
             sender,
             receiver,
@@ -355,7 +534,8 @@ impl Default for RegVisualizer {
 impl RegVisualizer {
     pub fn is_animating(&self) -> bool {
         self.elements.values().any(|vec| vec.iter().any(|els| els.iter().any(|el| el.animating))) ||
-            self.animation_elements.values().any(|vec| vec.iter().any(|els| els.iter().any(|el| el.animating)))
+            self.animation_elements.values().any(|vec| vec.iter().any(|els| els.iter().any(|el| el.animating))) ||
+            self.memory_elements.iter().any(|el| el.animating)
     }
 }
 
@@ -407,6 +587,9 @@ impl RegVisualizer {
                 });
             });
         });
+        self.memory_elements.iter_mut().for_each(|element| {
+            element.update(delta_time, factor, min_speed, max_speed);
+        });
     }
 
     fn create_layout<T: Hash + Clone + Eq + PartialEq>(ui: &mut Ui, size: Vec2, key: &T, data_size: usize, repeat_number: usize, layout_data: &mut HashMap<T, Vec<Vec<(Pos2, Vec2)>>>) {
@@ -429,11 +612,41 @@ impl RegVisualizer {
         });
     }
 
-    fn create_elements<T: Hash + Clone + Eq + PartialEq>(values: &Vec<Value>, key: &T, reg: &Register, layout_data: &HashMap<T, Vec<Vec<(Pos2, Vec2)>>>, elements: &mut HashMap<T, Vec<Vec<Element>>>, display: bool) {
+    fn get_lane_colors(reg: &Register, values: &[Value], data: &RegVisualizerData, lane_colors: &HashMap<Register, Vec<Color32>>, provenance: &Provenance) -> Vec<Color32> {
+        match data.color_scheme {
+            ColorScheme::Uniform => vec![get_color(&get_reg_name(reg)); values.len()],
+            ColorScheme::Register => match lane_colors.get(reg) {
+                Some(colors) if colors.len() == values.len() => colors.clone(),
+                _ => vec![get_register_color(reg, data.theme); values.len()],
+            },
+            ColorScheme::LaneGradient => (0..values.len()).map(|i| get_gradient_color(i, values.len(), data.theme)).collect(),
+            ColorScheme::Provenance => {
+                let lanes = provenance.get_lane_origins(reg, values[0].size() / 8);
+                lanes.iter().take(values.len()).map(|origins| get_origin_color(&origins[0], data.theme)).collect()
+            }
+            ColorScheme::Heatmap => {
+                let finite: Vec<f64> = values.iter().map(|v| v.to_f64()).filter(|v| v.is_finite()).collect();
+                let min = finite.iter().cloned().fold(f64::INFINITY, f64::min);
+                let max = finite.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+                values.iter().map(|v| {
+                    let v = v.to_f64();
+                    if !v.is_finite() {
+                        Color32::GRAY
+                    } else if max > min {
+                        get_heatmap_color(((v - min) / (max - min)) as f32, data.theme)
+                    } else {
+                        get_heatmap_color(0.5, data.theme)
+                    }
+                }).collect()
+            }
+        }
+    }
+
+    fn create_elements<T: Hash + Clone + Eq + PartialEq>(values: &Vec<Value>, key: &T, reg: &Register, layout_data: &HashMap<T, Vec<Vec<(Pos2, Vec2)>>>, elements: &mut HashMap<T, Vec<Vec<Element>>>, display: bool, radix: Radix, bit_grid: bool, colors: &[Color32]) {
         let values_changed = if let Some(layout_vec) = layout_data.get(key) {
             if let Some(elements_vec) = elements.get(key) {
                 layout_vec[0].len() != elements_vec[0].len() ||
-                    elements_vec[0].iter().enumerate().any(|(index, element)| element.value != values[index])
+                    elements_vec[0].iter().enumerate().any(|(index, element)| element.value != values[index] || element.radix != radix || element.bit_grid != bit_grid)
             } else {
                 false
             }
@@ -450,8 +663,10 @@ impl RegVisualizer {
                             element_vec.push(Element::default()
                                 .with_display(display)
                                 .with_value(values[index].clone())
+                                .with_radix(radix)
+                                .with_bit_grid(bit_grid)
                                 .with_position(position.clone())
-                                .with_color(get_color(&get_reg_name(reg)))
+                                .with_color(colors[index])
                                 .with_border_color(get_border_color(&get_reg_name(reg))));
                         });
                         element_vecs.push(element_vec);
@@ -462,7 +677,7 @@ impl RegVisualizer {
         }
     }
 
-    pub fn show(&mut self, ui: &mut Ui, ctx: &Context, data: &RegVisualizerData, cpu: &CPU) {
+    pub fn show(&mut self, ui: &mut Ui, ctx: &Context, data: &RegVisualizerData, cpu: &CPU, provenance: &Provenance) {
         // Get Animation Layout Size(Y)
         let mut animation_size_y = get_y();
         match self.destroy_receiver.try_recv() {
@@ -480,70 +695,130 @@ impl RegVisualizer {
             }
             _ => { }
         }
+        if self.color_scheme != data.color_scheme {
+            self.color_scheme = data.color_scheme;
+            self.lane_colors.clear();
+        }
         // Layout & Update Elements
-        ui.vertical(|ui| {
-            data.registers[0].iter().for_each(|reg| {
-                // Load values from CPU
-                let mut values: Vec<Value> = vec![];
-                match reg.get_type() {
-                    RegType::GPR => {
-                        let v = cpu.registers.get_gpr_value(reg.get_gpr());
-                        values = vec![create_value_with_gpr(v, &reg.get_gpr())];
-                    }
-                    RegType::Vector => {
-                        let (reg_type, reg_index) = reg.get_vector();
-                        let value_type = *data.vector_regs_type.get(&reg.get_vector()).unwrap();
-                        values = match value_type {
-                            ValueType::U8 => create_values(cpu.registers.get_by_sections::<u8>(reg_type, reg_index).unwrap()),
-                            ValueType::U16 => create_values(cpu.registers.get_by_sections::<u16>(reg_type, reg_index).unwrap()),
-                            ValueType::U32 => create_values(cpu.registers.get_by_sections::<u32>(reg_type, reg_index).unwrap()),
-                            ValueType::U64 => create_values(cpu.registers.get_by_sections::<u64>(reg_type, reg_index).unwrap()),
-                            ValueType::U128 => create_values(cpu.registers.get_by_sections::<u128>(reg_type, reg_index).unwrap()),
-                            ValueType::U256 => create_values(cpu.registers.get_by_sections::<u256>(reg_type, reg_index).unwrap()),
-                            ValueType::U512 => create_values(cpu.registers.get_by_sections::<u512>(reg_type, reg_index).unwrap()),
-                            ValueType::F32 => create_values(Utilities::u32vec_to_f32vec(cpu.registers.get_by_sections::<u32>(reg_type, reg_index).unwrap())),
-                            ValueType::F64 => create_values(Utilities::u64vec_to_f64vec(cpu.registers.get_by_sections::<u64>(reg_type, reg_index).unwrap())),
-                        }
-                    }
-                    _ => {/*None: Do nothing, there is NO possible to run into here!*/}
-                }
-                // Show UI
+        let all_registers = data.get_all_registers();
+        ui.horizontal_top(|ui| {
+            data.registers.iter().for_each(|column| {
                 ui.vertical(|ui| {
-                    ui.spacing_mut().item_spacing.x = 0.0; // Set spaces between elements to 0(x)
-                    ui.spacing_mut().item_spacing.y = 5.0; // Set spaces between elements to 5(y)
-                    ui.style_mut().spacing.item_spacing = egui::vec2(0.0, 0.0);
-                    ui.label(get_reg_name(reg).clone());
-                    let location = if let Some(config) = self.animation_config.get(reg) {
-                        config.location
-                    } else {
-                        LayoutLocation::None
-                    };
-                    let repeat_number = if let Some(config) = self.animation_config.get(reg) {
-                        config.repeat_numbers
-                    } else {
-                        (0, 0)
-                    };
-                    let size = get_size_from_value(&values[0]);
-                    let animation_size = Vec2::new(size.x, animation_size_y);
-                    // Animation Layout - TOP
-                    if location == LayoutLocation::TOP || location == LayoutLocation::BOTH {
-                        RegVisualizer::create_layout(ui, animation_size, &(reg.clone(), LayoutLocation::TOP), values.len(), repeat_number.0, &mut self.animation_layout_data);
-                        RegVisualizer::create_elements(&values, &(reg.clone(), LayoutLocation::TOP), reg, &self.animation_layout_data, &mut self.animation_elements, false);
-                    }
-                    // Elements Layout
-                    RegVisualizer::create_layout(ui, size, reg, values.len(), 1, &mut self.layout_data);
-                    RegVisualizer::create_elements(&values, reg, reg, &self.layout_data, &mut self.elements, true);
-                    // Animation Layout - BOTTOM
-                    if location == LayoutLocation::BOTTOM || location == LayoutLocation::BOTH {
-                        RegVisualizer::create_layout(ui, animation_size, &(reg.clone(), LayoutLocation::BOTTOM), values.len(), repeat_number.1, &mut self.animation_layout_data);
-                        RegVisualizer::create_elements(&values, &(reg.clone(), LayoutLocation::BOTTOM), reg, &self.animation_layout_data, &mut self.animation_elements, false);
-                    }
+                    column.iter().for_each(|reg| {
+                        // Empty item: keep the row as a spacer so columns can be aligned
+                        if reg.get_type() == RegType::None {
+                            ui.add_space(get_y());
+                            return;
+                        }
+                        // Load values from CPU
+                        let mut values: Vec<Value> = vec![];
+                        match reg.get_type() {
+                            RegType::GPR => {
+                                let v = cpu.registers.get_gpr_value(reg.get_gpr());
+                                values = vec![create_value_with_gpr(v, &reg.get_gpr())];
+                            }
+                            RegType::Vector => {
+                                let (reg_type, reg_index) = reg.get_vector();
+                                let value_type = *data.vector_regs_type.get(&reg.get_vector()).unwrap();
+                                values = match value_type {
+                                    ValueType::U8 => create_values(cpu.registers.get_by_sections::<u8>(reg_type, reg_index).unwrap()),
+                                    ValueType::U16 => create_values(cpu.registers.get_by_sections::<u16>(reg_type, reg_index).unwrap()),
+                                    ValueType::U32 => create_values(cpu.registers.get_by_sections::<u32>(reg_type, reg_index).unwrap()),
+                                    ValueType::U64 => create_values(cpu.registers.get_by_sections::<u64>(reg_type, reg_index).unwrap()),
+                                    ValueType::U128 => create_values(cpu.registers.get_by_sections::<u128>(reg_type, reg_index).unwrap()),
+                                    ValueType::U256 => create_values(cpu.registers.get_by_sections::<u256>(reg_type, reg_index).unwrap()),
+                                    ValueType::U512 => create_values(cpu.registers.get_by_sections::<u512>(reg_type, reg_index).unwrap()),
+                                    ValueType::F32 => create_values(Utilities::u32vec_to_f32vec(cpu.registers.get_by_sections::<u32>(reg_type, reg_index).unwrap())),
+                                    ValueType::F64 => create_values(Utilities::u64vec_to_f64vec(cpu.registers.get_by_sections::<u64>(reg_type, reg_index).unwrap())),
+                                    ValueType::F16 => create_values_f16(cpu.registers.get_by_sections::<u16>(reg_type, reg_index).unwrap()),
+                                    ValueType::BF16 => create_values_bf16(cpu.registers.get_by_sections::<u16>(reg_type, reg_index).unwrap()),
+                                    ValueType::I8 => create_values(cpu.registers.get_by_sections::<u8>(reg_type, reg_index).unwrap().into_iter().map(|x| x as i8).collect::<Vec<i8>>()),
+                                    ValueType::I16 => create_values(cpu.registers.get_by_sections::<u16>(reg_type, reg_index).unwrap().into_iter().map(|x| x as i16).collect::<Vec<i16>>()),
+                                    ValueType::I32 => create_values(cpu.registers.get_by_sections::<u32>(reg_type, reg_index).unwrap().into_iter().map(|x| x as i32).collect::<Vec<i32>>()),
+                                    ValueType::I64 => create_values(cpu.registers.get_by_sections::<u64>(reg_type, reg_index).unwrap().into_iter().map(|x| x as i64).collect::<Vec<i64>>()),
+                                }
+                            }
+                            _ => {/*None: Do nothing, there is NO possible to run into here!*/}
+                        }
+                        // Show UI
+                        ui.vertical(|ui| {
+                            ui.spacing_mut().item_spacing.x = 0.0; // Set spaces between elements to 0(x)
+                            ui.spacing_mut().item_spacing.y = 5.0; // Set spaces between elements to 5(y)
+                            ui.style_mut().spacing.item_spacing = egui::vec2(0.0, 0.0);
+                            ui.label(get_reg_name(reg).clone());
+                            let location = if let Some(config) = self.animation_config.get(reg) {
+                                config.location
+                            } else {
+                                LayoutLocation::None
+                            };
+                            let repeat_number = if let Some(config) = self.animation_config.get(reg) {
+                                config.repeat_numbers
+                            } else {
+                                (0, 0)
+                            };
+                            let radix = *data.registers_radix.get(reg).unwrap_or(&Radix::Decimal);
+                            let bit_grid = data.bit_grid_registers.contains(reg);
+                            let colors = RegVisualizer::get_lane_colors(reg, &values, data, &self.lane_colors, provenance);
+                            let size = if bit_grid {
+                                get_bit_grid_size(&values[0])
+                            } else {
+                                get_size_from_value(&values[0], radix)
+                            };
+                            let animation_size = Vec2::new(size.x, animation_size_y);
+                            // Animation Layout - TOP
+                            if location == LayoutLocation::TOP || location == LayoutLocation::BOTH {
+                                RegVisualizer::create_layout(ui, animation_size, &(reg.clone(), LayoutLocation::TOP), values.len(), repeat_number.0, &mut self.animation_layout_data);
+                                RegVisualizer::create_elements(&values, &(reg.clone(), LayoutLocation::TOP), reg, &self.animation_layout_data, &mut self.animation_elements, false, radix, bit_grid, &colors);
+                            }
+                            // Elements Layout
+                            RegVisualizer::create_layout(ui, size, reg, values.len(), 1, &mut self.layout_data);
+                            RegVisualizer::create_elements(&values, reg, reg, &self.layout_data, &mut self.elements, true, radix, bit_grid, &colors);
+                            // Main elements follow the colour scheme, moving elements keep their source colour
+                            let origins = provenance.get_lane_origins(reg, values[0].size() / 8);
+                            if let Some(vecs) = self.elements.get_mut(reg) {
+                                vecs.iter_mut().for_each(|vec| vec.iter_mut().zip(colors.iter()).zip(origins.iter()).for_each(|((element, color), lane)| {
+                                    element.color = *color;
+                                    element.tooltip = Some(match &element.previous {
+                                        Some(previous) => format!("from {}\nprevious: {}", provenance.describe_lane(reg, lane), format_value(previous, element.radix)),
+                                        None => format!("from {}", provenance.describe_lane(reg, lane)),
+                                    });
+                                }));
+                            }
+                            // Alias Brackets - displayed registers sharing the storage of this register
+                            let mut aliases: Vec<(Register, (usize, usize))> = all_registers.iter()
+                                .filter(|alias| *alias != reg)
+                                .filter_map(|alias| get_alias_range(alias, reg).map(|range| (*alias, range)))
+                                .collect();
+                            aliases.sort_by(|a, b| (b.1.1 - b.1.0).cmp(&(a.1.1 - a.1.0)));
+                            if !aliases.is_empty() {
+                                let total_bits = values.iter().fold(0, |acc, v| acc + v.size());
+                                let (rect, _) = ui.allocate_exact_size(Vec2::new(size.x * values.len() as f32, aliases.len() as f32 * get_bracket_height()), Sense::hover());
+                                aliases.iter().enumerate().for_each(|(depth, (alias, (lo, hi)))| {
+                                    let left = rect.left() + rect.width() * *lo as f32 / total_bits as f32 + 1.0;
+                                    let right = rect.left() + rect.width() * *hi as f32 / total_bits as f32 - 1.0;
+                                    let top = rect.top() + depth as f32 * get_bracket_height() + 2.0;
+                                    let bottom = top + 4.0;
+                                    let stroke = Stroke::new(1.0, Color32::GRAY);
+                                    ui.painter().line_segment([Pos2::new(left, top), Pos2::new(left, bottom)], stroke);
+                                    ui.painter().line_segment([Pos2::new(left, bottom), Pos2::new(right, bottom)], stroke);
+                                    ui.painter().line_segment([Pos2::new(right, top), Pos2::new(right, bottom)], stroke);
+                                    ui.painter().text(Pos2::new((left + right) / 2.0, bottom), egui::Align2::CENTER_TOP, get_reg_name(alias),
+                                                      egui::FontId::new(10.0, egui::FontFamily::Monospace), Color32::GRAY);
+                                });
+                            }
+                            // Animation Layout - BOTTOM
+                            if location == LayoutLocation::BOTTOM || location == LayoutLocation::BOTH {
+                                RegVisualizer::create_layout(ui, animation_size, &(reg.clone(), LayoutLocation::BOTTOM), values.len(), repeat_number.1, &mut self.animation_layout_data);
+                                RegVisualizer::create_elements(&values, &(reg.clone(), LayoutLocation::BOTTOM), reg, &self.animation_layout_data, &mut self.animation_elements, false, radix, bit_grid, &colors);
+                            }
+                        });
+                    });
                 });
             });
         });
         // Clean
-        self.layout_data.retain(|reg_in_layout, _| data.registers[0].iter().any(|reg_in_data| *reg_in_layout == *reg_in_data));
-        self.elements.retain(|reg_in_elements, _| data.registers[0].iter().any(|reg_in_data| *reg_in_elements == *reg_in_data));
+        self.layout_data.retain(|reg_in_layout, _| all_registers.iter().any(|reg_in_data| *reg_in_layout == *reg_in_data));
+        self.elements.retain(|reg_in_elements, _| all_registers.iter().any(|reg_in_data| *reg_in_elements == *reg_in_data));
         self.animation_layout_data.retain(|(reg_in_layout, _), _| self.animation_config.iter().any(|(reg_in_config, _)| *reg_in_layout == *reg_in_config));
         self.animation_elements.retain(|(reg_in_elements, _), _| self.animation_config.iter().any(|(reg_in_config, _)| *reg_in_elements == *reg_in_config));
         // Fix Elements Position
@@ -591,6 +866,35 @@ impl RegVisualizer {
                 });
             });
         });
+        // Click to edit, hover for the origin
+        let mut clicked = None;
+        self.elements.iter().for_each(|(reg, vec)| {
+            vec[0].iter().enumerate().for_each(|(lane, element)| {
+                let id = Id::new(("register_visualizer_element", get_reg_name(reg), lane));
+                let mut response = ui.interact(element.get_rect(), id, Sense::click());
+                if let Some(tooltip) = &element.tooltip {
+                    response = response.on_hover_text(tooltip);
+                }
+                if response.clicked() {
+                    let text = match element.value {
+                        Value::U256(_) | Value::U512(_) => format_value(&element.value, Radix::Hex),
+                        _ => format!("{}", element.value),
+                    };
+                    clicked = Some((*reg, lane, element.value.get_type(), text, element.position));
+                }
+            });
+        });
+        if clicked.is_some() && !self.is_animating() {
+            self.editing = clicked;
+            self.edit_error = None;
+        }
+        if let Some((reg, lane, value_type, text, position)) = self.editing.as_mut() {
+            match value_editor::show_edit_popup(ctx, "register_visualizer_edit", *position, text, self.edit_error.as_deref()) {
+                Some(true) => self.edits.push(ValueEdit { location: EditLocation::Register(*reg), value_type: *value_type, lane: *lane, text: text.clone() }),
+                Some(false) => self.editing = None,
+                None => {}
+            }
+        }
         // Show Animation Elements
         self.animation_elements.iter().for_each(|((reg, loc), vec)| {
             if let Some(config) = self.animation_config.get(reg) {
@@ -626,6 +930,11 @@ impl RegVisualizer {
                 }
             }
         });
+        // Show Memory Elements, they start outside of this window
+        ui.with_layer_id(top_layer_id, |ui| {
+            ui.set_clip_rect(ctx.screen_rect());
+            self.memory_elements.iter().filter(|element| element.display).for_each(|element| element.show(ui));
+        });
         // Destroy Animation Layout
         match self.destroy_receiver.try_recv() {
             Ok(DestroyLayoutMsg::Finish) => {
@@ -703,6 +1012,10 @@ impl RegVisualizer {
                     self.set_target_for_move_animation_finish(source, target);
                     ctx.request_repaint();
                 }
+                Ok(ElementAnimationFinishMsg::SetMemoryTarget(index, target)) => {
+                    self.set_target_for_memory_move_animation_finish(index, target);
+                    ctx.request_repaint();
+                }
                 Err(TryRecvError::Empty) => {
                     break;
                     /* Do nothing */
@@ -781,6 +1094,7 @@ impl RegVisualizer {
                 elements.iter_mut().for_each(|element| element.display = false);
             });
         });
+        self.memory_elements.clear();
         ctx.request_repaint();
         self.destroy_sender.send(DestroyLayoutMsg::Next(0.1f32)).unwrap();
     }
@@ -878,6 +1192,8 @@ impl RegVisualizer {
     pub fn set_target_for_move_animation_finish(&mut self, source: (Register, LayoutLocation, usize, usize), target: (Register, LayoutLocation, usize, usize)) {
         let mut value;
         let mut string;
+        let radix;
+        let bit_grid;
         let mut order;
         let mut color;
         let mut border_color;
@@ -885,6 +1201,8 @@ impl RegVisualizer {
             let e = &mut source_vec[source.2][source.3];
             value = e.value.clone();
             string = e.string.clone();
+            radix = e.radix;
+            bit_grid = e.bit_grid;
             order = e.order.clone();
             color = e.color.clone();
             border_color = e.border_color.clone();
@@ -899,7 +1217,7 @@ impl RegVisualizer {
             if let Some(s) = string {
                 e.set_string(format!("{}", s));
             } else {
-                e.set_string(value.to_string());
+                e.set_string(format_value(&value, if bit_grid {Radix::Binary} else {radix}));
             }
             e.display = true; // ?WTF???????
             e.set_order(order.get_lower());
@@ -907,11 +1225,129 @@ impl RegVisualizer {
             e.set_border_color(border_color);
         }
     }
+    pub fn set_target_for_memory_move_animation_finish(&mut self, index: usize, target: (Register, LayoutLocation, usize, usize)) {
+        let (string, color) = if let Some(e) = self.memory_elements.get_mut(index) {
+            e.display = false;
+            (e.string.clone().unwrap_or_else(|| format_value(&e.value, e.radix)), e.color)
+        } else {
+            return;
+        };
+        if let Some(target_vec) = self.animation_elements.get_mut(&(target.0, target.1)) {
+            let e = &mut target_vec[target.2][target.3];
+            e.set_string(string);
+            e.display = true;
+            e.set_color(color);
+        }
+    }
+    pub fn set_memory_cells(&mut self, cells: Vec<(usize, usize, Rect)>) {
+        self.memory_cells = cells;
+    }
+    pub fn keep_lane_colors(&mut self, reg: &Register, location: LayoutLocation, row: usize) {
+        // Lanes that received a moved element take its colour, the others keep their own
+        let current: Vec<Color32> = self.elements.get(reg).map(|vecs| vecs[0].iter().map(|e| e.color).collect()).unwrap_or_default();
+        if let Some(vec) = self.animation_elements.get(&(*reg, location)).and_then(|vecs| vecs.get(row)) {
+            let colors = vec.iter().enumerate().map(|(i, e)| {
+                if e.display { e.color } else { current.get(i).cloned().unwrap_or(e.color) }
+            }).collect();
+            self.lane_colors.insert(*reg, colors);
+        }
+    }
+    pub fn reset_colors(&mut self) {
+        self.lane_colors.clear();
+    }
+    pub fn get_all_values(&self) -> HashMap<Register, Vec<Value>> {
+        self.elements.iter().map(|(reg, vecs)| (*reg, vecs[0].iter().map(|e| e.value).collect())).collect()
+    }
+    pub fn highlight_diff(&mut self, reg: &Register, previous: Option<&Vec<Value>>) {
+        // Changed lanes get highlighted with a ghost of the old value, lanes written with the same value are marked apart
+        if let Some(elements) = self.elements.get_mut(reg) {
+            elements[0].iter_mut().enumerate().for_each(|(i, element)| {
+                match previous.and_then(|values| values.get(i)) {
+                    Some(value) if *value == element.value => element.is_rewritten = true,
+                    Some(value) => {
+                        element.highlight();
+                        element.previous = Some(*value);
+                    }
+                    None => element.highlight(),
+                }
+            });
+        }
+    }
+    pub fn take_edits(&mut self) -> Vec<ValueEdit> {
+        std::mem::take(&mut self.edits)
+    }
+    pub fn finish_edit(&mut self, result: Result<(), String>) {
+        match result {
+            Ok(()) => self.editing = None,
+            Err(e) => self.edit_error = Some(e),
+        }
+    }
     pub fn highlight(&mut self, reg: &Register) {
         if let Some(elements) = self.elements.get_mut(reg) {
             elements[0].iter_mut().for_each(|element| { element.highlight() });
         }
     }
+    pub fn highlight_upper_lanes(&mut self, reg: &Register, state: UpperLaneState) {
+        // Mark the lanes of every displayed wider alias (e.g. YMM14/ZMM14 for XMM14) above the written width
+        if reg.get_type() != RegType::Vector {
+            return;
+        }
+        let (name, index) = reg.get_vector();
+        let width = get_vec_reg_size(&name);
+        self.elements.iter_mut().for_each(|(alias, elements)| {
+            if alias.get_type() == RegType::Vector && alias.get_vector().1 == index && get_vec_reg_size(&alias.get_vector().0) > width {
+                let mut offset = 0;
+                elements[0].iter_mut().for_each(|element| {
+                    if offset >= width {
+                        element.upper_lane_state = state;
+                    }
+                    offset += element.value.size();
+                });
+            }
+        });
+    }
+    pub fn highlight_aliases(&mut self, reg: &Register) {
+        // Highlight the storage shared with every displayed alias (e.g. the low 128 bits of YMM14 for XMM14)
+        self.elements.iter_mut().for_each(|(alias, elements)| {
+            if *alias == *reg {
+                return;
+            }
+            if let Some((lo, hi)) = get_alias_range(reg, alias) {
+                // Writing a 32-bit GPR zero-extends into the 64-bit register
+                let is_zero_extended = reg.get_type() == RegType::GPR && hi - lo == 32;
+                let mut offset = 0;
+                elements[0].iter_mut().for_each(|element| {
+                    let size = element.value.size();
+                    if offset < hi && offset + size > lo {
+                        element.highlight();
+                    }
+                    if is_zero_extended && offset >= 32 {
+                        element.upper_lane_state = UpperLaneState::Zeroed;
+                    } else if is_zero_extended && offset + size > 32 {
+                        element.zeroed_bits = offset + size - 32;
+                    }
+                    offset += size;
+                });
+            } else if get_alias_range(alias, reg).is_some() {
+                elements[0].iter_mut().for_each(|element| element.highlight());
+            }
+        });
+    }
+    pub fn set_exception_badges(&mut self, reg: &Register, exceptions: &Vec<(usize, usize, u32)>) {
+        // exceptions: (bit offset, element size, flags) of every calculated lane
+        if let Some(elements) = self.elements.get_mut(reg) {
+            let mut offset = 0;
+            elements[0].iter_mut().for_each(|element| {
+                let size = element.value.size();
+                exceptions.iter().for_each(|(lane_offset, lane_size, flags)| {
+                    if *lane_offset < offset + size && lane_offset + lane_size > offset {
+                        element.exceptions |= flags;
+                    }
+                });
+                offset += size;
+            });
+        }
+    }
     pub fn reset_highlight(&mut self) {
         self.elements.values_mut().for_each(|elements| elements[0].iter_mut().for_each(|element| element.reset_highlight()));
     }
@@ -920,6 +1356,7 @@ impl RegVisualizer {
 pub struct ElementAnimationData {
     pub source: (Register, LayoutLocation, usize, usize),
     pub target: (Register, LayoutLocation, usize, usize),
+    pub memory: Option<(usize, Value)>, // (address, value) of a memory source, `source` is not used then
     pub callback: Option<Box<dyn FnOnce(&mut Element) + Send + 'static>>,
 }
 
@@ -932,6 +1369,20 @@ impl ElementAnimationData {
         ElementAnimationData {
             source,
             target,
+            memory: None,
+            callback: Some(Box::new(callback)),
+        }
+    }
+    pub fn from_memory(
+        address: usize,
+        value: Value,
+        target: (Register, LayoutLocation, usize, usize),
+        callback: impl FnOnce(&mut Element) + Send + 'static
+    ) -> Self {
+        ElementAnimationData {
+            source: target,
+            target,
+            memory: Some((address, value)),
             callback: Some(Box::new(callback)),
         }
     }
@@ -944,6 +1395,7 @@ enum AnimationControlMsg {
 
 enum ElementAnimationFinishMsg {
     SetTarget((Register, LayoutLocation, usize, usize), (Register, LayoutLocation, usize, usize)),
+    SetMemoryTarget(usize, (Register, LayoutLocation, usize, usize)),
 }
 
 enum DestroyLayoutMsg {
@@ -1120,6 +1572,38 @@ impl RegVisualizer {
         if error {
             return;
         }
+        // Memory source: a new element leaves the memory cell, or appears above the target if the cell is not shown
+        if let Some((address, value)) = data.memory {
+            let start = self.memory_cells.iter()
+                .find(|(cell, size, _)| address >= *cell && address < cell + size)
+                .map(|(_, _, rect)| rect.min)
+                .unwrap_or(target_data.0 - Vec2::new(0f32, get_y() * 2f32));
+            let radix = self.animation_elements.get(&(data.target.0, data.target.1))
+                .and_then(|elements_vec| elements_vec.get(data.target.2))
+                .and_then(|elements| elements.get(data.target.3))
+                .map_or(Radix::Decimal, |element| element.radix);
+            let mut element = Element::default()
+                .with_value(value)
+                .with_radix(radix)
+                .with_position(start)
+                .with_color(Color32::GRAY)
+                .with_border_color(Color32::DARK_GRAY);
+            element.set_order(ElementOrder::Top);
+            element.set_target_position(target_data.0);
+            let index = self.memory_elements.len();
+            let sender = self.finish_sender.clone();
+            let callback_in_data = data.callback;
+            let target = data.target;
+            element.set_animation_finished_callback(move |element| {
+                if let Some(callback_in_data) = callback_in_data {
+                    callback_in_data(element);
+                }
+                callback();
+                sender.send(ElementAnimationFinishMsg::SetMemoryTarget(index, target)).unwrap();
+            });
+            self.memory_elements.push(element);
+            return;
+        }
         // Next: Move Animation
         if let Some(elements_vec) = self.animation_elements.get_mut(&(data.source.0, data.source.1)) {
             if data.source.2 < elements_vec.len() && data.source.3 < elements_vec[0].len() {
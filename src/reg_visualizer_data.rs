@@ -1,11 +1,17 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use cpulib::{VecRegName};
-use crate::utilities::{Register, ValueType};
+use crate::utilities::{Register, RegType, ValueType, Radix};
+use crate::theme::{Theme, ColorScheme};
 
 pub struct RegVisualizerData {
     // Registers Data
     pub registers: Vec<Vec<Register>>,
     pub vector_regs_type: HashMap<(VecRegName, usize), ValueType>,
+    pub registers_radix: HashMap<Register, Radix>,
+    pub bit_grid_registers: HashSet<Register>,
+    // Appearance
+    pub theme: Theme,
+    pub color_scheme: ColorScheme,
     // Animation Data
     pub factor: f32,
     pub min_speed: f32,
@@ -18,6 +24,11 @@ impl Default for RegVisualizerData {
             // Registers Data
             registers: vec![vec![]],
             vector_regs_type: HashMap::new(),
+            registers_radix: HashMap::new(),
+            bit_grid_registers: HashSet::new(),
+            // Appearance
+            theme: Theme::Dark,
+            color_scheme: ColorScheme::Uniform,
             // Animation Data
             factor: 1.0f32,
             min_speed: 1.0f32,
@@ -25,3 +36,18 @@ impl Default for RegVisualizerData {
         }
     }
 }
+
+impl RegVisualizerData {
+    pub fn get_all_registers(&self) -> Vec<Register> {
+        self.registers.iter().flatten().filter(|reg| reg.get_type() != RegType::None).cloned().collect()
+    }
+    pub fn contains(&self, reg: &Register) -> bool {
+        self.registers.iter().any(|column| column.contains(reg))
+    }
+    pub fn position(&self, reg: &Register) -> Option<(usize, usize)> {
+        // (row, column)
+        self.registers.iter().enumerate().find_map(|(col, column)| {
+            column.iter().position(|r| *r == *reg).map(|row| (row, col))
+        })
+    }
+}
@@ -0,0 +1,328 @@
+use cpulib::CPU;
+use eframe::egui::{Ui, ComboBox, DragValue, TextEdit, Grid};
+use crate::utilities::{RegType, ValueType};
+use crate::reg_visualizer_data::RegVisualizerData;
+use crate::instruction_actuator::parse_register;
+use crate::value_editor::{self, EditLocation};
+
+const VALUE_TYPES: [ValueType; 15] = [
+    ValueType::U8, ValueType::U16, ValueType::U32, ValueType::U64, ValueType::U128, ValueType::U256, ValueType::U512,
+    ValueType::I8, ValueType::I16, ValueType::I32, ValueType::I64,
+    ValueType::F16, ValueType::BF16, ValueType::F32, ValueType::F64,
+];
+
+fn is_float(value_type: ValueType) -> bool {
+    matches!(value_type, ValueType::F16 | ValueType::BF16 | ValueType::F32 | ValueType::F64)
+}
+
+fn format_number(x: f64, value_type: ValueType) -> String {
+    if is_float(value_type) { format!("{}", x) } else { format!("{}", x.floor() as i128) }
+}
+
+fn parse_number(text: &str) -> Option<f64> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(&hex.replace('_', ""), 16).ok().map(|x| x as f64),
+        None => text.parse::<f64>().ok(),
+    }
+}
+
+pub fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_array_file(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_array_file(path: &str) -> Result<String, String> {
+    Err(format!("Cannot read {}: files are not available on the web", path))
+}
+
+#[derive(Clone, PartialEq)]
+pub enum Generator {
+    Iota { start: f64, step: f64 },
+    Constant(f64),
+    Random { seed: u64, min: f64, max: f64 },
+    File(String), // comma or whitespace separated values
+}
+
+impl Generator {
+    pub fn generate(&self, count: usize, value_type: ValueType) -> Result<Vec<String>, String> {
+        // Lane values as text, parsed later like an edit
+        match self {
+            Generator::Iota { start, step } => Ok((0..count).map(|i| format_number(start + step * i as f64, value_type)).collect()),
+            Generator::Constant(x) => Ok(vec![format_number(*x, value_type); count]),
+            Generator::Random { seed, min, max } => {
+                let mut state = *seed;
+                Ok((0..count).map(|_| {
+                    let t = (splitmix64(&mut state) >> 11) as f64 / (1u64 << 53) as f64;
+                    if is_float(value_type) {
+                        format_number(min + (max - min) * t, value_type)
+                    } else {
+                        // Both bounds included
+                        format_number(min.floor() + ((max.floor() - min.floor() + 1.0) * t).floor(), value_type)
+                    }
+                }).collect())
+            }
+            Generator::File(path) => {
+                let content = read_array_file(path)?;
+                let values: Vec<String> = content
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.to_string())
+                    .collect();
+                if values.len() < count {
+                    return Err(format!("{} has {} values, {} needed", path, values.len(), count));
+                }
+                Ok(values.into_iter().take(count).collect())
+            }
+        }
+    }
+    pub fn parse(words: &[&str]) -> Result<Self, String> {
+        // `iota <start> <step>`, `const <x>`, `random <seed> <min> <max>` or `file <path>`
+        let number = |i: usize| words.get(i).and_then(|w| parse_number(w)).ok_or(format!("Invalid generator: {}", words.join(" ")));
+        match words[0].to_lowercase().as_str() {
+            "iota" => Ok(Generator::Iota { start: number(1)?, step: number(2)? }),
+            "const" | "constant" => Ok(Generator::Constant(number(1)?)),
+            "random" => Ok(Generator::Random { seed: number(1)? as u64, min: number(2)?, max: number(3)? }),
+            "file" if words.len() > 1 => Ok(Generator::File(words[1..].join(" "))),
+            _ => Err(format!("Invalid generator: {}", words.join(" "))),
+        }
+    }
+    fn get_name(&self) -> &'static str {
+        match self {
+            Generator::Iota { .. } => "Iota",
+            Generator::Constant(_) => "Constant",
+            Generator::Random { .. } => "Random",
+            Generator::File(_) => "File",
+        }
+    }
+    fn show(&mut self, ui: &mut Ui, id: String) {
+        ComboBox::from_id_source(id)
+            .selected_text(self.get_name())
+            .show_ui(ui, |ui| {
+                ui.style_mut().wrap = Some(false);
+                ui.set_min_width(60.0);
+                let current = self.clone();
+                ui.selectable_value(self, if let Generator::Iota { .. } = current { current.clone() } else { Generator::Iota { start: 1.0, step: 1.0 } }, "Iota");
+                ui.selectable_value(self, if let Generator::Constant(_) = current { current.clone() } else { Generator::Constant(0.0) }, "Constant");
+                ui.selectable_value(self, if let Generator::Random { .. } = current { current.clone() } else { Generator::Random { seed: 0, min: 0.0, max: 100.0 } }, "Random");
+                ui.selectable_value(self, if let Generator::File(_) = current { current.clone() } else { Generator::File("".into()) }, "File");
+            });
+        match self {
+            Generator::Iota { start, step } => {
+                ui.label("start");
+                ui.add(DragValue::new(start).speed(0.1));
+                ui.label("step");
+                ui.add(DragValue::new(step).speed(0.1));
+            }
+            Generator::Constant(x) => {
+                ui.add(DragValue::new(x).speed(0.1));
+            }
+            Generator::Random { seed, min, max } => {
+                ui.label("seed");
+                ui.add(DragValue::new(seed));
+                ui.label("min");
+                ui.add(DragValue::new(min).speed(0.1));
+                ui.label("max");
+                ui.add(DragValue::new(max).speed(0.1));
+            }
+            Generator::File(path) => {
+                ui.add(TextEdit::singleline(path).desired_width(160.0).hint_text("path to values"));
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RegisterInit {
+    pub name: String,
+    pub value_type: ValueType,
+    pub generator: Generator,
+    pub display: Option<usize>, // column in the visualizer
+}
+
+#[derive(Clone)]
+pub struct MemoryInit {
+    pub address: usize,
+    pub value_type: ValueType,
+    pub count: usize,
+    pub generator: Generator,
+}
+
+#[derive(Clone, Default)]
+pub struct Scenario {
+    pub registers: Vec<RegisterInit>,
+    pub memory: Vec<MemoryInit>,
+}
+
+impl Scenario {
+    pub fn with_register(mut self, name: &str, value_type: ValueType, generator: Generator, display: Option<usize>) -> Self {
+        self.registers.push(RegisterInit { name: name.into(), value_type, generator, display });
+        self
+    }
+    pub fn with_memory(mut self, address: usize, value_type: ValueType, count: usize, generator: Generator) -> Self {
+        self.memory.push(MemoryInit { address, value_type, count, generator });
+        self
+    }
+    pub fn parse(text: &str) -> Result<Self, String> {
+        // One entry per line, `#` starts a comment:
+        //   ymm0 f64 iota 1 1
+        //   rdi u64 const 0x40000000
+        //   mem 0x40000000 f32 16 random 7 -1 1
+        let mut scenario = Self::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let error = |message: &str| format!("line {}: {}", i + 1, message);
+            let get_type = |word: &str| ValueType::from_name(word).ok_or(error(&format!("Invalid type: {}", word)));
+            if words[0].eq_ignore_ascii_case("mem") {
+                if words.len() < 5 {
+                    return Err(error("Expected: mem <address> <type> <count> <generator>"));
+                }
+                let address = parse_number(words[1]).ok_or(error(&format!("Invalid address: {}", words[1])))? as usize;
+                let value_type = get_type(words[2])?;
+                let count = words[3].parse::<usize>().map_err(|_| error(&format!("Invalid count: {}", words[3])))?;
+                let generator = Generator::parse(&words[4..]).map_err(|e| error(&e))?;
+                scenario = scenario.with_memory(address, value_type, count, generator);
+            } else {
+                if words.len() < 3 {
+                    return Err(error("Expected: <register> <type> <generator>"));
+                }
+                if parse_register(words[0]).is_none() {
+                    return Err(error(&format!("Invalid register: {}", words[0])));
+                }
+                let value_type = get_type(words[1])?;
+                let generator = Generator::parse(&words[2..]).map_err(|e| error(&e))?;
+                scenario = scenario.with_register(&words[0].to_lowercase(), value_type, generator, Some(0));
+            }
+        }
+        Ok(scenario)
+    }
+    fn get_bytes(generator: &Generator, count: usize, value_type: ValueType) -> Result<Vec<u8>, String> {
+        let mut bytes = vec![];
+        for text in generator.generate(count, value_type)? {
+            match value_editor::parse_value_bytes(&text, value_type) {
+                Some(b) => bytes.extend(b),
+                None => return Err(format!("Invalid value: {}", text)),
+            }
+        }
+        Ok(bytes)
+    }
+    pub fn build(&self) -> Result<CPU, String> {
+        let mut cpu = CPU::default();
+        for init in self.registers.iter() {
+            let reg = parse_register(&init.name).ok_or(format!("Invalid register: {}", init.name))?;
+            let location = EditLocation::Register(reg);
+            let count = value_editor::get_location_size(&location) * 8 / init.value_type.size();
+            if count == 0 {
+                return Err(format!("{} is too wide for {}", init.value_type.get_name(), init.name));
+            }
+            let bytes = Self::get_bytes(&init.generator, count, init.value_type)?;
+            value_editor::write_bytes(&mut cpu, &location, 0, &bytes);
+        }
+        for init in self.memory.iter() {
+            let bytes = Self::get_bytes(&init.generator, init.count, init.value_type)?;
+            value_editor::write_bytes(&mut cpu, &EditLocation::Memory(init.address), 0, &bytes);
+        }
+        Ok(cpu)
+    }
+    pub fn apply_display(&self, data: &mut RegVisualizerData) {
+        let columns = self.registers.iter().filter_map(|init| init.display).max().map_or(1, |col| col + 1);
+        data.registers = vec![vec![]; columns];
+        data.vector_regs_type.clear();
+        self.registers.iter().for_each(|init| {
+            if let (Some(col), Some(reg)) = (init.display, parse_register(&init.name)) {
+                if !data.contains(&reg) {
+                    data.registers[col].push(reg);
+                }
+                if reg.get_type() == RegType::Vector {
+                    data.vector_regs_type.insert(reg.get_vector(), init.value_type);
+                }
+            }
+        });
+    }
+    pub fn show(&mut self, ui: &mut Ui, error: Option<&str>) -> bool {
+        // true: Reset clicked; `error` is why the last Reset failed
+        ui.label("Registers:");
+        let mut remove = None;
+        Grid::new("scenario_registers").striped(true).show(ui, |ui| {
+            for (i, init) in self.registers.iter_mut().enumerate() {
+                let valid = parse_register(&init.name).is_some();
+                ui.add(TextEdit::singleline(&mut init.name).desired_width(60.0).text_color_opt(if valid { None } else { Some(ui.visuals().error_fg_color) }));
+                ComboBox::from_id_source(("scenario_register_type", i))
+                    .selected_text(init.value_type.get_name())
+                    .show_ui(ui, |ui| {
+                        VALUE_TYPES.iter().for_each(|t| {
+                            ui.selectable_value(&mut init.value_type, *t, t.get_name());
+                        });
+                    });
+                ui.horizontal(|ui| init.generator.show(ui, format!("scenario_register_generator_{}", i)));
+                ui.horizontal(|ui| {
+                    let mut display = init.display.is_some();
+                    if ui.checkbox(&mut display, "Show").changed() {
+                        init.display = if display { Some(0) } else { None };
+                    }
+                    if let Some(col) = init.display.as_mut() {
+                        ui.label("column");
+                        ui.add(DragValue::new(col).clamp_range(0..=7));
+                    }
+                });
+                if ui.button("Remove").clicked() {
+                    remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = remove {
+            self.registers.remove(i);
+        }
+        if ui.button("Add Register").clicked() {
+            self.registers.push(RegisterInit { name: "ymm0".into(), value_type: ValueType::U32, generator: Generator::Constant(0.0), display: Some(0) });
+        }
+        ui.separator();
+        ui.label("Memory:");
+        let mut remove = None;
+        Grid::new("scenario_memory").striped(true).show(ui, |ui| {
+            for (i, init) in self.memory.iter_mut().enumerate() {
+                ui.add(DragValue::new(&mut init.address).hexadecimal(8, false, true).prefix("0x"));
+                ComboBox::from_id_source(("scenario_memory_type", i))
+                    .selected_text(init.value_type.get_name())
+                    .show_ui(ui, |ui| {
+                        VALUE_TYPES.iter().for_each(|t| {
+                            ui.selectable_value(&mut init.value_type, *t, t.get_name());
+                        });
+                    });
+                ui.horizontal(|ui| {
+                    ui.label("count");
+                    ui.add(DragValue::new(&mut init.count).clamp_range(1..=4096));
+                });
+                ui.horizontal(|ui| init.generator.show(ui, format!("scenario_memory_generator_{}", i)));
+                if ui.button("Remove").clicked() {
+                    remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = remove {
+            self.memory.remove(i);
+        }
+        if ui.button("Add Memory Block").clicked() {
+            self.memory.push(MemoryInit { address: 0x40000000, value_type: ValueType::U32, count: 16, generator: Generator::Iota { start: 1.0, step: 1.0 } });
+        }
+        ui.separator();
+        if let Some(error) = error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+        ui.button("Reset").clicked()
+    }
+}
@@ -0,0 +1,151 @@
+use std::sync::{Arc, Mutex};
+use cpulib::{CPU, FLAGSName};
+use crate::instruction_actuator::{decode, apply, split_comment, Decoded, StepEvent};
+use crate::trace::{Trace, TraceTarget};
+use crate::expectation::{self, Outcome};
+use crate::mxcsr::Mxcsr;
+use crate::provenance::Provenance;
+
+// Program + CPU without any GUI: control flow and instruction semantics.
+// The GUI calls `advance` and hands the decoded instruction to the animation layer,
+// headless users call `step`/`run` which apply it right away.
+
+pub enum Step {
+    Comment, // blank or comment-only line
+    Label,
+    Branch { taken: bool },
+    Instruction(Decoded),
+    Error(String),
+}
+
+// Machine state outside `CPU`, shared with the animation layer which applies instructions later
+#[derive(Default)]
+pub struct MachineState {
+    pub mxcsr: Mxcsr,
+    pub trace: Trace,
+    pub provenance: Provenance,
+}
+
+pub struct Simulator {
+    pub cpu: Arc<Mutex<CPU>>,
+    pub state: Arc<Mutex<MachineState>>,
+    program: Vec<String>,
+    pub line: usize, // last executed line, 1-based, 0 before the first step
+    pub finished: bool,
+    pub outcomes: Vec<Outcome>, // every checked `; expect`
+    executed: usize, // like `line`, but not moved by a taken branch
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Self {
+            cpu: Arc::new(Mutex::new(CPU::default())),
+            state: Arc::new(Mutex::new(MachineState::default())),
+            program: vec![],
+            line: 0,
+            finished: false,
+            outcomes: vec![],
+            executed: 0,
+        }
+    }
+}
+
+impl Simulator {
+    pub fn new(cpu: CPU, code: &str) -> Self {
+        let mut simulator = Self::default();
+        simulator.reset(cpu);
+        simulator.set_program(code);
+        simulator
+    }
+    pub fn reset(&mut self, cpu: CPU) {
+        self.cpu = Arc::new(Mutex::new(cpu));
+        self.state = Arc::new(Mutex::new(MachineState::default()));
+        self.line = 0;
+        self.finished = false;
+        self.outcomes.clear();
+        self.executed = 0;
+    }
+    pub fn set_program(&mut self, code: &str) {
+        self.program = code.lines().map(|line| line.to_string()).collect();
+    }
+    pub fn next_instruction(&self) -> Option<&str> {
+        if self.finished { None } else { self.program.get(self.line).map(|line| line.as_str()) }
+    }
+    pub fn advance(&mut self) -> Option<Step> {
+        // Move to the next line and resolve labels and branches, instructions are decoded but not applied
+        if self.finished {
+            return None;
+        }
+        let mut step = None;
+        if self.line < self.program.len() {
+            self.line += 1;
+            self.executed = self.line;
+            let instruction = self.program[self.line - 1].clone();
+            let (code, _) = split_comment(&instruction);
+            let mut guard = self.state.lock().unwrap();
+            let state = &mut *guard;
+            state.trace.set_line(self.line);
+            step = Some(if code.is_empty() {
+                Step::Comment
+            } else if code.ends_with(':') {
+                Step::Label
+            } else if code.starts_with("jne ") {
+                state.trace.record(&self.cpu, &mut state.mxcsr, code, vec![], vec![(TraceTarget::Flags, true, false)], |_| {});
+                let flag = self.cpu.lock().unwrap().registers.get_flags_value(FLAGSName::RFLAGS);
+                let taken = (flag >> 6) & 0b1 == 0; // ZF
+                if taken {
+                    let label = format!("{}:", code[4..].trim());
+                    if let Some(index) = self.program.iter().position(|line| split_comment(line).0 == label) {
+                        self.line = index + 1;
+                    }
+                }
+                Step::Branch { taken }
+            } else {
+                match decode(&instruction, self.cpu.clone()) {
+                    Ok(decoded) => Step::Instruction(decoded),
+                    Err(e) => Step::Error(e),
+                }
+            });
+        }
+        if self.line >= self.program.len() {
+            self.finished = true;
+        }
+        step
+    }
+    pub fn step(&mut self) -> Option<(Step, StepEvent)> {
+        let step = self.advance()?;
+        let event = match &step {
+            Step::Instruction(decoded) => apply(self.cpu.clone(), &mut self.state.lock().unwrap(), decoded),
+            _ => StepEvent::default(),
+        };
+        self.check_expectations();
+        Some((step, event))
+    }
+    pub fn check_expectations(&mut self) {
+        // Once the effects of the last executed line are in the CPU
+        let line = self.executed;
+        let source = match line.checked_sub(1).and_then(|i| self.program.get(i)) {
+            Some(source) => source,
+            None => return,
+        };
+        let cpu = self.cpu.lock().unwrap();
+        expectation::check_line(source, &cpu).into_iter().for_each(|result| {
+            self.outcomes.push(Outcome { line, result });
+        });
+    }
+    pub fn get_failures(&self) -> Vec<&Outcome> {
+        self.outcomes.iter().filter(|o| o.result.is_err()).collect()
+    }
+    pub fn run(&mut self, max_steps: usize) -> Result<usize, String> {
+        // Steps taken until the program finishes, the first unsupported instruction is an error
+        let mut steps = 0;
+        while steps < max_steps {
+            match self.step() {
+                Some((Step::Error(e), _)) => return Err(format!("line {}: {}", self.line, e)),
+                Some(_) => steps += 1,
+                None => return Ok(steps),
+            }
+        }
+        if self.finished { Ok(steps) } else { Err(format!("Stopped after {} steps", max_steps)) }
+    }
+}
@@ -0,0 +1,94 @@
+use eframe::egui::{Visuals, Color32, Stroke, ecolor::Hsva};
+use egui_code_editor::ColorTheme;
+use crate::utilities::{Register, RegType, get_gpr_alias_info, get_reg_name};
+use crate::provenance::{Origin, Location};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Theme {
+    Dark,
+    Light,
+    HighContrast,
+}
+
+impl Theme {
+    pub fn get_visuals(&self) -> Visuals {
+        match self {
+            Theme::Dark => Visuals::dark(),
+            Theme::Light => Visuals::light(),
+            Theme::HighContrast => {
+                let mut visuals = Visuals::dark();
+                visuals.override_text_color = Some(Color32::WHITE);
+                visuals.panel_fill = Color32::BLACK;
+                visuals.window_fill = Color32::BLACK;
+                visuals.extreme_bg_color = Color32::BLACK;
+                visuals.window_stroke = Stroke::new(1.0, Color32::WHITE);
+                visuals.widgets.noninteractive.bg_stroke = Stroke::new(1.0, Color32::WHITE);
+                visuals.widgets.inactive.fg_stroke = Stroke::new(1.5, Color32::WHITE);
+                visuals.widgets.hovered.fg_stroke = Stroke::new(2.0, Color32::YELLOW);
+                visuals.widgets.active.fg_stroke = Stroke::new(2.0, Color32::YELLOW);
+                visuals.selection.bg_fill = Color32::from_rgb(0, 90, 200);
+                visuals
+            }
+        }
+    }
+    pub fn get_code_theme(&self) -> ColorTheme {
+        match self {
+            Theme::Dark => ColorTheme::GRUVBOX,
+            Theme::Light => ColorTheme::GRUVBOX_LIGHT,
+            Theme::HighContrast => ColorTheme::GRUVBOX_DARK,
+        }
+    }
+    fn get_saturation_value(&self) -> (f32, f32) {
+        // Element text is black, so keep the fill light
+        match self {
+            Theme::Dark => (0.45, 0.9),
+            Theme::Light => (0.35, 1.0),
+            Theme::HighContrast => (0.8, 1.0),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ColorScheme {
+    Uniform,
+    Register,
+    LaneGradient,
+    Heatmap,
+    Provenance,
+}
+
+pub fn get_register_color(reg: &Register, theme: Theme) -> Color32 {
+    // Spread the registers over the hue circle with the golden ratio
+    let index = match reg.get_type() {
+        RegType::GPR => {
+            // Sub-registers share the colour of their 64-bit register
+            let name = get_reg_name(&Register::gpr(get_gpr_alias_info(&reg.get_gpr()).0));
+            name.bytes().fold(0usize, |acc, b| acc * 31 + b as usize) % 64 + 32
+        }
+        RegType::Vector => reg.get_vector().1,
+        RegType::None => return Color32::TRANSPARENT,
+    };
+    let (s, v) = theme.get_saturation_value();
+    Hsva::new((index as f32 * 0.618034).fract(), s, v, 1.0).into()
+}
+
+pub fn get_gradient_color(index: usize, len: usize, theme: Theme) -> Color32 {
+    let (s, v) = theme.get_saturation_value();
+    Hsva::new(0.8 * index as f32 / len.max(1) as f32, s, v, 1.0).into()
+}
+
+pub fn get_heatmap_color(t: f32, theme: Theme) -> Color32 {
+    // Cold (blue) to hot (red)
+    let (s, v) = theme.get_saturation_value();
+    Hsva::new(0.66 * (1.0 - t.clamp(0.0, 1.0)), s, v, 1.0).into()
+}
+
+pub fn get_origin_color(origin: &Origin, theme: Theme) -> Color32 {
+    let (s, v) = theme.get_saturation_value();
+    match origin {
+        Origin::Initial(Location::Register(reg, _)) => get_register_color(reg, theme),
+        Origin::Initial(Location::Memory(_)) => Hsva::new(0.1, s * 0.6, v, 1.0).into(),
+        Origin::Computation(id) => Hsva::new((*id as f32 * 0.618034 + 0.3).fract(), s * 0.5, v * 0.8, 1.0).into(),
+        Origin::Zero => Color32::from_gray(110),
+    }
+}
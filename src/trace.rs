@@ -0,0 +1,244 @@
+use std::sync::{Arc, Mutex};
+use cpulib::{CPU, FLAGSName};
+use eframe::egui::{Ui, ScrollArea, TextEdit};
+use crate::utilities::{Register, RegType, get_reg_name};
+use crate::mxcsr::Mxcsr;
+
+// Execution trace: every executed instruction with the before/after bytes of what it read and wrote
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum TraceTarget {
+    Register(Register),
+    Memory(u64, usize), // address, bytes
+    Flags,
+    Mxcsr,
+}
+
+impl TraceTarget {
+    pub fn get_name(&self) -> String {
+        match self {
+            TraceTarget::Register(reg) => get_reg_name(reg),
+            TraceTarget::Memory(addr, size) => format!("[0x{:X}]:{}", addr, size),
+            TraceTarget::Flags => "RFLAGS".into(),
+            TraceTarget::Mxcsr => "MXCSR".into(),
+        }
+    }
+    fn read(&self, cpu: &CPU, mxcsr: &Mxcsr) -> Vec<u8> {
+        match self {
+            TraceTarget::Register(reg) => match reg.get_type() {
+                RegType::Vector => cpu.registers.get_by_sections::<u8>(reg.get_vector().0, reg.get_vector().1).unwrap(),
+                RegType::GPR => {
+                    let size = cpulib::Utilities::get_gpr_size(&reg.get_gpr()) as usize / 8;
+                    cpu.registers.get_gpr_value(reg.get_gpr()).to_le_bytes()[..size].to_vec()
+                }
+                RegType::None => vec![],
+            },
+            TraceTarget::Memory(addr, size) => cpu.memory.read_vec::<u8>(*addr as usize, *size),
+            TraceTarget::Flags => cpu.registers.get_flags_value(FLAGSName::RFLAGS).to_le_bytes().to_vec(),
+            TraceTarget::Mxcsr => mxcsr.value.to_le_bytes().to_vec(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Access {
+    pub target: TraceTarget,
+    pub is_read: bool,
+    pub is_written: bool,
+    pub before: Vec<u8>,
+    pub after: Vec<u8>,
+}
+
+#[derive(Clone)]
+pub struct TraceEntry {
+    pub step: usize,
+    pub line: usize,
+    pub instruction: String,
+    pub opcode: String,
+    pub operands: Vec<String>,
+    pub addresses: Vec<u64>,
+    pub accesses: Vec<Access>,
+}
+
+#[derive(Default)]
+pub struct Trace {
+    entries: Vec<TraceEntry>,
+    line: usize,
+}
+
+impl Trace {
+    pub fn set_line(&mut self, line: usize) {
+        // Editor line of the instruction about to execute
+        self.line = line;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.entries.truncate(len);
+    }
+
+    pub fn get_entries(&self) -> &[TraceEntry] {
+        &self.entries
+    }
+
+    pub fn record<F: FnOnce(&mut Mxcsr)>(&mut self, cpu: &Arc<Mutex<CPU>>, mxcsr: &mut Mxcsr, instruction: &str, addresses: Vec<u64>, targets: Vec<(TraceTarget, bool, bool)>, f: F) {
+        // targets: (target, is_read, is_written); `f` performs the instruction
+        let read_all = |mxcsr: &Mxcsr| {
+            let cpu = cpu.lock().unwrap();
+            targets.iter().map(|(target, _, _)| target.read(&cpu, mxcsr)).collect::<Vec<Vec<u8>>>()
+        };
+        let before = read_all(mxcsr);
+        f(mxcsr);
+        let after = read_all(mxcsr);
+        let (opcode, operands) = match instruction.trim().split_once(' ') {
+            Some((opcode, rest)) => (opcode.to_string(), rest.split(',').map(|op| op.trim().to_string()).collect()),
+            None => (instruction.trim().to_string(), vec![]),
+        };
+        let accesses = targets.iter().zip(before).zip(after).map(|(((target, is_read, is_written), before), after)| {
+            Access { target: *target, is_read: *is_read, is_written: *is_written, before, after }
+        }).collect();
+        let entry = TraceEntry { step: self.entries.len() + 1, line: self.line, instruction: instruction.trim().to_string(), opcode, operands, addresses, accesses };
+        self.entries.push(entry);
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    // Little-endian bytes as one number
+    format!("0x{}", bytes.iter().rev().map(|b| format!("{:02X}", b)).collect::<String>())
+}
+
+pub fn escape_json(s: &str) -> String {
+    let mut escaped = String::new();
+    s.chars().for_each(|c| match c {
+        '"' => escaped.push_str("\\\""),
+        '\\' => escaped.push_str("\\\\"),
+        '\n' => escaped.push_str("\\n"),
+        '\r' => escaped.push_str("\\r"),
+        '\t' => escaped.push_str("\\t"),
+        c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+        c => escaped.push(c),
+    });
+    escaped
+}
+
+fn escape_csv(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+pub fn to_json(entries: &[TraceEntry]) -> String {
+    let quote = |s: &str| format!("\"{}\"", escape_json(s));
+    let accesses = |entry: &TraceEntry, written: bool| {
+        entry.accesses.iter().filter(|a| if written { a.is_written } else { a.is_read }).map(|a| {
+            format!("{{\"location\": {}, \"before\": {}, \"after\": {}}}", quote(&a.target.get_name()), quote(&to_hex(&a.before)), quote(&to_hex(&a.after)))
+        }).collect::<Vec<String>>().join(", ")
+    };
+    let lines: Vec<String> = entries.iter().map(|entry| {
+        format!(
+            "  {{\"step\": {}, \"line\": {}, \"instruction\": {}, \"opcode\": {}, \"operands\": [{}], \"addresses\": [{}], \"reads\": [{}], \"writes\": [{}]}}",
+            entry.step,
+            entry.line,
+            quote(&entry.instruction),
+            quote(&entry.opcode),
+            entry.operands.iter().map(|op| quote(op)).collect::<Vec<String>>().join(", "),
+            entry.addresses.iter().map(|addr| quote(&format!("0x{:X}", addr))).collect::<Vec<String>>().join(", "),
+            accesses(entry, false),
+            accesses(entry, true),
+        )
+    }).collect();
+    format!("[\n{}\n]\n", lines.join(",\n"))
+}
+
+pub fn to_csv(entries: &[TraceEntry]) -> String {
+    // One row per access, instructions without any keep a single row
+    let mut csv = String::from("step,line,instruction,opcode,operands,addresses,access,location,before,after\n");
+    entries.iter().for_each(|entry| {
+        let prefix = format!(
+            "{},{},{},{},{},{}",
+            entry.step,
+            entry.line,
+            escape_csv(&entry.instruction),
+            escape_csv(&entry.opcode),
+            escape_csv(&entry.operands.join(", ")),
+            escape_csv(&entry.addresses.iter().map(|addr| format!("0x{:X}", addr)).collect::<Vec<String>>().join(" ")),
+        );
+        if entry.accesses.is_empty() {
+            csv.push_str(&format!("{},,,,\n", prefix));
+        }
+        entry.accesses.iter().for_each(|a| {
+            let access = match (a.is_read, a.is_written) {
+                (true, true) => "read-write",
+                (false, true) => "write",
+                _ => "read",
+            };
+            csv.push_str(&format!("{},{},{},{},{}\n", prefix, access, escape_csv(&a.target.get_name()), to_hex(&a.before), to_hex(&a.after)));
+        });
+    });
+    csv
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save(path: &str, content: &str) -> Result<(), String> {
+    std::fs::write(path, content).map_err(|e| format!("Cannot write {}: {}", path, e))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn save(path: &str, _content: &str) -> Result<(), String> {
+    Err(format!("Cannot write {}: use Copy on the web", path))
+}
+
+pub struct TraceViewer {
+    path: String,
+    message: String,
+}
+
+impl Default for TraceViewer {
+    fn default() -> Self {
+        Self {
+            path: "trace.json".into(),
+            message: "".into(),
+        }
+    }
+}
+
+impl TraceViewer {
+    pub fn show(&mut self, ui: &mut Ui, entries: &[TraceEntry]) {
+        ui.horizontal(|ui| {
+            ui.label(format!("{} instructions", entries.len()));
+            if ui.button("Copy JSON").clicked() {
+                ui.output_mut(|o| o.copied_text = to_json(entries));
+            }
+            if ui.button("Copy CSV").clicked() {
+                ui.output_mut(|o| o.copied_text = to_csv(entries));
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.add(TextEdit::singleline(&mut self.path).desired_width(200.0));
+            if ui.button("Save").on_hover_text("CSV for a .csv path, JSON otherwise").clicked() {
+                let content = if self.path.to_lowercase().ends_with(".csv") { to_csv(entries) } else { to_json(entries) };
+                self.message = match save(&self.path, &content) {
+                    Ok(()) => format!("Saved {}", self.path),
+                    Err(e) => e,
+                };
+            }
+        });
+        if !self.message.is_empty() {
+            ui.label(&self.message);
+        }
+        ui.separator();
+        ScrollArea::vertical().max_height(300.0).stick_to_bottom(true).show(ui, |ui| {
+            entries.iter().for_each(|entry| {
+                ui.monospace(format!("{:>4} L{:<3} {}", entry.step, entry.line, entry.instruction));
+                entry.accesses.iter().filter(|a| a.is_written).for_each(|a| {
+                    ui.monospace(format!("          {} {} -> {}", a.target.get_name(), to_hex(&a.before), to_hex(&a.after)));
+                });
+            });
+        });
+    }
+}
@@ -3,6 +3,7 @@ use std::fmt::{Display, Formatter};
 use std::hash::{Hash, Hasher};
 use cpulib::{VecRegName, GPRName, u256, u512};
 use std::any::type_name;
+use crate::float16::{f16_to_f32, bf16_to_f32};
 
 pub fn type_of<T>(_: &T) -> &str {
     type_name::<T>()
@@ -19,6 +20,12 @@ pub enum ValueType {
     U512,
     F32,
     F64,
+    F16,
+    BF16,
+    I8,
+    I16,
+    I32,
+    I64,
 }
 
 impl ValueType {
@@ -33,8 +40,41 @@ impl ValueType {
             ValueType::U512 => 512,
             ValueType::F32 => 32,
             ValueType::F64 => 64,
+            ValueType::F16 => 16,
+            ValueType::BF16 => 16,
+            ValueType::I8 => 8,
+            ValueType::I16 => 16,
+            ValueType::I32 => 32,
+            ValueType::I64 => 64,
         }
     }
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            ValueType::U8 => "U8",
+            ValueType::U16 => "U16",
+            ValueType::U32 => "U32",
+            ValueType::U64 => "U64",
+            ValueType::U128 => "U128",
+            ValueType::U256 => "U256",
+            ValueType::U512 => "U512",
+            ValueType::F32 => "F32",
+            ValueType::F64 => "F64",
+            ValueType::F16 => "F16",
+            ValueType::BF16 => "BF16",
+            ValueType::I8 => "I8",
+            ValueType::I16 => "I16",
+            ValueType::I32 => "I32",
+            ValueType::I64 => "I64",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<ValueType> {
+        // Case-insensitive, e.g. "u32", "F64"
+        [
+            ValueType::U8, ValueType::U16, ValueType::U32, ValueType::U64, ValueType::U128, ValueType::U256, ValueType::U512,
+            ValueType::F32, ValueType::F64, ValueType::F16, ValueType::BF16,
+            ValueType::I8, ValueType::I16, ValueType::I32, ValueType::I64,
+        ].into_iter().find(|t| t.get_name().eq_ignore_ascii_case(name))
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -48,6 +88,116 @@ pub enum Value {
     U512(u512),
     F32(f32),
     F64(f64),
+    F16(u16),  // binary16 bits
+    BF16(u16), // bfloat16 bits
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+}
+
+impl Value {
+    pub fn size(&self) -> usize {
+        match self {
+            Value::U8(_) => 8,
+            Value::U16(_) => 16,
+            Value::U32(_) => 32,
+            Value::U64(_) => 64,
+            Value::U128(_) => 128,
+            Value::U256(_) => 256,
+            Value::U512(_) => 512,
+            Value::F32(_) => 32,
+            Value::F64(_) => 64,
+            Value::F16(_) => 16,
+            Value::BF16(_) => 16,
+            Value::I8(_) => 8,
+            Value::I16(_) => 16,
+            Value::I32(_) => 32,
+            Value::I64(_) => 64,
+        }
+    }
+    pub fn get_type(&self) -> ValueType {
+        match self {
+            Value::U8(_) => ValueType::U8,
+            Value::U16(_) => ValueType::U16,
+            Value::U32(_) => ValueType::U32,
+            Value::U64(_) => ValueType::U64,
+            Value::U128(_) => ValueType::U128,
+            Value::U256(_) => ValueType::U256,
+            Value::U512(_) => ValueType::U512,
+            Value::F32(_) => ValueType::F32,
+            Value::F64(_) => ValueType::F64,
+            Value::F16(_) => ValueType::F16,
+            Value::BF16(_) => ValueType::BF16,
+            Value::I8(_) => ValueType::I8,
+            Value::I16(_) => ValueType::I16,
+            Value::I32(_) => ValueType::I32,
+            Value::I64(_) => ValueType::I64,
+        }
+    }
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Value::U8(x) => *x as f64,
+            Value::U16(x) => *x as f64,
+            Value::U32(x) => *x as f64,
+            Value::U64(x) => *x as f64,
+            Value::U128(x) => *x as f64,
+            Value::F32(x) => *x as f64,
+            Value::F64(x) => *x,
+            Value::F16(x) => f16_to_f32(*x) as f64,
+            Value::BF16(x) => bf16_to_f32(*x) as f64,
+            Value::I8(x) => *x as f64,
+            Value::I16(x) => *x as f64,
+            Value::I32(x) => *x as f64,
+            Value::I64(x) => *x as f64,
+            _ => self.to_hex_string().chars().fold(0f64, |acc, c| acc * 16.0 + c.to_digit(16).unwrap() as f64),
+        }
+    }
+    pub fn to_hex_string(&self) -> String {
+        // Raw bits as zero-padded hex
+        let s = match self {
+            Value::U8(x) => format!("{:x}", x),
+            Value::U16(x) => format!("{:x}", x),
+            Value::U32(x) => format!("{:x}", x),
+            Value::U64(x) => format!("{:x}", x),
+            Value::U128(x) => format!("{:x}", x),
+            Value::U256(x) => format!("{:x}", x),
+            Value::U512(x) => format!("{:x}", x),
+            Value::F32(x) => format!("{:x}", x.to_bits()),
+            Value::F64(x) => format!("{:x}", x.to_bits()),
+            Value::F16(x) => format!("{:x}", x),
+            Value::BF16(x) => format!("{:x}", x),
+            Value::I8(x) => format!("{:x}", *x as u8),
+            Value::I16(x) => format!("{:x}", *x as u16),
+            Value::I32(x) => format!("{:x}", *x as u32),
+            Value::I64(x) => format!("{:x}", *x as u64),
+        };
+        format!("{:0>width$}", s, width = self.size() / 4)
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Radix {
+    Decimal,
+    Hex,
+    Binary,
+    Char,
+}
+
+pub fn format_value(value: &Value, radix: Radix) -> String {
+    match radix {
+        Radix::Decimal => format!("{}", value),
+        Radix::Hex => format!("0x{}", value.to_hex_string()),
+        Radix::Binary => value.to_hex_string().chars().map(|c| format!("{:04b}", c.to_digit(16).unwrap())).collect(),
+        Radix::Char => {
+            // Lowest byte first, as in memory
+            let hex = value.to_hex_string();
+            (0..hex.len() / 2).rev().map(|i| {
+                let byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap();
+                if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' }
+            }).collect()
+        }
+    }
 }
 
 impl Default for Value {
@@ -68,6 +218,12 @@ impl Display for Value {
             Value::U512(x) => write!(f, "{}", x),
             Value::F32(x) => write!(f, "{}", x),
             Value::F64(x) => write!(f, "{}", x),
+            Value::F16(x) => write!(f, "{}", f16_to_f32(*x)),
+            Value::BF16(x) => write!(f, "{}", bf16_to_f32(*x)),
+            Value::I8(x) => write!(f, "{}", x),
+            Value::I16(x) => write!(f, "{}", x),
+            Value::I32(x) => write!(f, "{}", x),
+            Value::I64(x) => write!(f, "{}", x),
         }
     }
 }
@@ -111,6 +267,30 @@ impl PartialEq<Self> for Value {
                 Value::F64(ov) => v == ov,
                 _ => false,
             },
+            Value::F16(v) => match other {
+                Value::F16(ov) => v == ov,
+                _ => false,
+            },
+            Value::BF16(v) => match other {
+                Value::BF16(ov) => v == ov,
+                _ => false,
+            },
+            Value::I8(v) => match other {
+                Value::I8(ov) => v == ov,
+                _ => false,
+            },
+            Value::I16(v) => match other {
+                Value::I16(ov) => v == ov,
+                _ => false,
+            },
+            Value::I32(v) => match other {
+                Value::I32(ov) => v == ov,
+                _ => false,
+            },
+            Value::I64(v) => match other {
+                Value::I64(ov) => v == ov,
+                _ => false,
+            },
         }
     }
     fn ne(&self, other: &Self) -> bool {
@@ -143,6 +323,10 @@ value_compare!(u256, U256);
 value_compare!(u512, U512);
 value_compare!(f32, F32);
 value_compare!(f64, F64);
+value_compare!(i8, I8);
+value_compare!(i16, I16);
+value_compare!(i32, I32);
+value_compare!(i64, I64);
 
 impl Eq for Value {}
 
@@ -158,6 +342,12 @@ impl Hash for Value {
             Value::U512(v) => v.hash(state),
             Value::F32(v) => v.to_bits().hash(state),
             Value::F64(v) => v.to_bits().hash(state),
+            Value::F16(v) => v.hash(state),
+            Value::BF16(v) => v.hash(state),
+            Value::I8(v) => v.hash(state),
+            Value::I16(v) => v.hash(state),
+            Value::I32(v) => v.hash(state),
+            Value::I64(v) => v.hash(state),
         }
     }
 }
@@ -185,6 +375,10 @@ into_value_rule!(u256, U256);
 into_value_rule!(u512, U512);
 into_value_rule!(f32, F32);
 into_value_rule!(f64, F64);
+into_value_rule!(i8, I8);
+into_value_rule!(i16, I16);
+into_value_rule!(i32, I32);
+into_value_rule!(i64, I64);
 
 pub fn create_value<T: IntoValue>(input: T) -> Value {
     input.into_value()
@@ -194,6 +388,53 @@ pub fn create_values<T: IntoValue>(input: Vec<T>) -> Vec<Value> {
     input.into_iter().map(|x| create_value(x)).collect()
 }
 
+pub fn create_values_f16(input: Vec<u16>) -> Vec<Value> {
+    input.into_iter().map(Value::F16).collect()
+}
+
+pub fn create_values_bf16(input: Vec<u16>) -> Vec<Value> {
+    input.into_iter().map(Value::BF16).collect()
+}
+
+pub fn create_value_from_bytes(bytes: &[u8], value_type: ValueType) -> Option<Value> {
+    // Little-endian bytes, up to 128 bits
+    let mut buffer = [0u8; 16];
+    if bytes.len() != value_type.size() / 8 || bytes.len() > buffer.len() {
+        return None;
+    }
+    buffer[..bytes.len()].copy_from_slice(bytes);
+    let x = u128::from_le_bytes(buffer);
+    Some(match value_type {
+        ValueType::U8 => Value::U8(x as u8),
+        ValueType::U16 => Value::U16(x as u16),
+        ValueType::U32 => Value::U32(x as u32),
+        ValueType::U64 => Value::U64(x as u64),
+        ValueType::U128 => Value::U128(x),
+        ValueType::F32 => Value::F32(f32::from_bits(x as u32)),
+        ValueType::F64 => Value::F64(f64::from_bits(x as u64)),
+        ValueType::F16 => Value::F16(x as u16),
+        ValueType::BF16 => Value::BF16(x as u16),
+        ValueType::I8 => Value::I8(x as i8),
+        ValueType::I16 => Value::I16(x as i16),
+        ValueType::I32 => Value::I32(x as i32),
+        ValueType::I64 => Value::I64(x as i64),
+        ValueType::U256 | ValueType::U512 => return None,
+    })
+}
+
+pub fn get_default_value_type(reg: &Register) -> ValueType {
+    // Unsigned lanes as wide as a GPR, 32-bit lanes for vectors
+    match reg.get_type() {
+        RegType::GPR => match cpulib::Utilities::get_gpr_size(&reg.get_gpr()) {
+            8 => ValueType::U8,
+            16 => ValueType::U16,
+            32 => ValueType::U32,
+            _ => ValueType::U64,
+        },
+        _ => ValueType::U32,
+    }
+}
+
 pub fn create_value_with_gpr(input: u64, reg: &GPRName) -> Value {
     match Utilities::get_gpr_size(reg) {
         64 => {
@@ -210,6 +451,68 @@ pub fn create_value_with_gpr(input: u64, reg: &GPRName) -> Value {
     }
 }
 
+pub fn get_vec_reg_size(reg: &VecRegName) -> usize {
+    match reg {
+        VecRegName::XMM => 128,
+        VecRegName::YMM => 256,
+        VecRegName::ZMM => 512,
+    }
+}
+
+pub fn get_gpr_alias_info(reg: &GPRName) -> (GPRName, usize) {
+    // (64-bit register sharing the storage, bit offset inside it)
+    match reg {
+        GPRName::RAX | GPRName::EAX | GPRName::AX | GPRName::AL => (GPRName::RAX, 0),
+        GPRName::RBX | GPRName::EBX | GPRName::BX | GPRName::BL => (GPRName::RBX, 0),
+        GPRName::RCX | GPRName::ECX | GPRName::CX | GPRName::CL => (GPRName::RCX, 0),
+        GPRName::RDX | GPRName::EDX | GPRName::DX | GPRName::DL => (GPRName::RDX, 0),
+        GPRName::AH => (GPRName::RAX, 8),
+        GPRName::BH => (GPRName::RBX, 8),
+        GPRName::CH => (GPRName::RCX, 8),
+        GPRName::DH => (GPRName::RDX, 8),
+        GPRName::RSI | GPRName::ESI | GPRName::SI | GPRName::SIL => (GPRName::RSI, 0),
+        GPRName::RDI | GPRName::EDI | GPRName::DI | GPRName::DIL => (GPRName::RDI, 0),
+        GPRName::RBP | GPRName::EBP | GPRName::BP | GPRName::BPL => (GPRName::RBP, 0),
+        GPRName::RSP | GPRName::ESP | GPRName::SP | GPRName::SPL => (GPRName::RSP, 0),
+        GPRName::R8 | GPRName::R8D | GPRName::R8W | GPRName::R8B => (GPRName::R8, 0),
+        GPRName::R9 | GPRName::R9D | GPRName::R9W | GPRName::R9B => (GPRName::R9, 0),
+        GPRName::R10 | GPRName::R10D | GPRName::R10W | GPRName::R10B => (GPRName::R10, 0),
+        GPRName::R11 | GPRName::R11D | GPRName::R11W | GPRName::R11B => (GPRName::R11, 0),
+        GPRName::R12 | GPRName::R12D | GPRName::R12W | GPRName::R12B => (GPRName::R12, 0),
+        GPRName::R13 | GPRName::R13D | GPRName::R13W | GPRName::R13B => (GPRName::R13, 0),
+        GPRName::R14 | GPRName::R14D | GPRName::R14W | GPRName::R14B => (GPRName::R14, 0),
+        GPRName::R15 | GPRName::R15D | GPRName::R15W | GPRName::R15B => (GPRName::R15, 0),
+    }
+}
+
+pub fn get_alias_range(reg: &Register, alias: &Register) -> Option<(usize, usize)> {
+    // Bit range [lo, hi) of `reg` inside `alias`, if `reg` is stored within `alias`
+    match (reg.get_type(), alias.get_type()) {
+        (RegType::Vector, RegType::Vector) => {
+            let (name, index) = reg.get_vector();
+            let (alias_name, alias_index) = alias.get_vector();
+            let size = get_vec_reg_size(&name);
+            if index == alias_index && size <= get_vec_reg_size(&alias_name) {
+                Some((0, size))
+            } else {
+                None
+            }
+        }
+        (RegType::GPR, RegType::GPR) => {
+            let (full, offset) = get_gpr_alias_info(&reg.get_gpr());
+            let (alias_full, alias_offset) = get_gpr_alias_info(&alias.get_gpr());
+            let size = Utilities::get_gpr_size(&reg.get_gpr()) as usize;
+            let alias_size = Utilities::get_gpr_size(&alias.get_gpr()) as usize;
+            if full == alias_full && alias_offset <= offset && offset + size <= alias_offset + alias_size {
+                Some((offset - alias_offset, offset - alias_offset + size))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
 pub fn get_vec_reg_name(reg: &VecRegName, reg_index: &usize) -> String {
     format!("{}{}", reg, reg_index)
 }
@@ -0,0 +1,202 @@
+use cpulib::{CPU, VecRegName};
+use eframe::egui::{self, Context, Pos2, Key, TextEdit};
+use crate::utilities::{Register, RegType, ValueType};
+use crate::float16::{f32_to_f16, f32_to_bf16};
+use crate::mxcsr::RoundingMode;
+use crate::provenance::{self, Provenance, Location, Origin};
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum EditLocation {
+    Register(Register),
+    Memory(usize),
+}
+
+pub struct ValueEdit {
+    pub location: EditLocation,
+    pub value_type: ValueType,
+    pub lane: usize,
+    pub text: String,
+}
+
+fn parse_integer(text: &str) -> Option<i128> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        u128::from_str_radix(&hex.replace('_', ""), 16).ok()?
+    } else if let Some(bin) = text.strip_prefix("0b").or_else(|| text.strip_prefix("0B")) {
+        u128::from_str_radix(&bin.replace('_', ""), 2).ok()?
+    } else {
+        text.replace('_', "").parse::<u128>().ok()?
+    };
+    if negative {
+        if value > i128::MAX as u128 + 1 { None } else { Some((value as i128).wrapping_neg()) }
+    } else {
+        Some(value as i128)
+    }
+}
+
+pub fn parse_value_bytes(text: &str, value_type: ValueType) -> Option<Vec<u8>> {
+    // Little-endian bytes of `text` read as `value_type`
+    let text = text.trim();
+    let size = value_type.size() / 8;
+    match value_type {
+        ValueType::F32 => Some(text.parse::<f32>().ok()?.to_le_bytes().to_vec()),
+        ValueType::F64 => Some(text.parse::<f64>().ok()?.to_le_bytes().to_vec()),
+        ValueType::F16 => Some(f32_to_f16(text.parse::<f32>().ok()?, RoundingMode::Nearest).to_le_bytes().to_vec()),
+        ValueType::BF16 => Some(f32_to_bf16(text.parse::<f32>().ok()?).to_le_bytes().to_vec()),
+        ValueType::U256 | ValueType::U512 => {
+            // Wider than any primitive, only hexadecimal
+            let hex = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X"))?.replace('_', "");
+            if hex.is_empty() || hex.len() > size * 2 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            let hex = format!("{:0>width$}", hex, width = size * 2);
+            Some((0..size).rev().map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap()).collect())
+        }
+        ValueType::I8 | ValueType::I16 | ValueType::I32 | ValueType::I64 => {
+            let value = parse_integer(text)?;
+            let bits = value_type.size();
+            if value < -(1i128 << (bits - 1)) || value >= (1i128 << (bits - 1)) {
+                return None;
+            }
+            Some(value.to_le_bytes()[..size].to_vec())
+        }
+        _ => {
+            // Unsigned, negative numbers wrap around
+            let value = parse_integer(text)?;
+            let bits = value_type.size();
+            if bits < 128 && (value >= (1i128 << bits) || value < -(1i128 << (bits - 1))) {
+                return None;
+            }
+            Some(value.to_le_bytes()[..size].to_vec())
+        }
+    }
+}
+
+pub fn read_bytes(cpu: &CPU, location: &EditLocation, offset: usize, size: usize) -> Vec<u8> {
+    match location {
+        EditLocation::Register(reg) => match reg.get_type() {
+            RegType::Vector => cpu.registers.get_by_sections::<u8>(reg.get_vector().0, reg.get_vector().1).unwrap()[offset..offset + size].to_vec(),
+            RegType::GPR => cpu.registers.get_gpr_value(reg.get_gpr()).to_le_bytes()[offset..offset + size].to_vec(),
+            RegType::None => vec![],
+        },
+        EditLocation::Memory(addr) => cpu.memory.read_vec::<u8>(addr + offset, size),
+    }
+}
+
+pub fn write_bytes(cpu: &mut CPU, location: &EditLocation, offset: usize, bytes: &[u8]) {
+    match location {
+        EditLocation::Register(reg) => match reg.get_type() {
+            RegType::Vector => {
+                let (name, index) = reg.get_vector();
+                let mut v = cpu.registers.get_by_sections::<u8>(name, index).unwrap();
+                v[offset..offset + bytes.len()].copy_from_slice(bytes);
+                cpu.registers.set_by_sections::<u8>(name, index, v);
+            }
+            RegType::GPR => {
+                let mut v = cpu.registers.get_gpr_value(reg.get_gpr()).to_le_bytes();
+                v[offset..offset + bytes.len()].copy_from_slice(bytes);
+                cpu.registers.set_gpr_value(reg.get_gpr(), u64::from_le_bytes(v));
+            }
+            RegType::None => {}
+        },
+        EditLocation::Memory(addr) => cpu.memory.write_vec::<u8>(addr + offset, bytes.to_vec()),
+    }
+}
+
+fn reset_origins(provenance: &mut Provenance, location: &EditLocation, offset: usize, size: usize) {
+    // Edited bytes are inputs again
+    let locations = match location {
+        EditLocation::Register(reg) => provenance::get_register_locations(reg).into_iter().skip(offset).take(size).collect(),
+        EditLocation::Memory(addr) => (0..size).map(|i| Location::Memory((addr + offset + i) as u64)).collect::<Vec<Location>>(),
+    };
+    provenance.set_origins(locations.into_iter().map(|location| (location, Origin::Initial(location))).collect());
+}
+
+pub fn get_location_size(location: &EditLocation) -> usize {
+    match location {
+        EditLocation::Register(reg) => match reg.get_type() {
+            RegType::Vector => match reg.get_vector().0 {
+                VecRegName::XMM => 16,
+                VecRegName::YMM => 32,
+                VecRegName::ZMM => 64,
+            },
+            RegType::GPR => cpulib::Utilities::get_gpr_size(&reg.get_gpr()) as usize / 8,
+            RegType::None => 0,
+        },
+        EditLocation::Memory(_) => usize::MAX,
+    }
+}
+
+#[derive(Default)]
+pub struct EditHistory {
+    entries: Vec<(EditLocation, usize, Vec<u8>)>, // location, byte offset, bytes before the edit
+}
+
+impl EditHistory {
+    pub fn apply(&mut self, cpu: &mut CPU, provenance: &mut Provenance, edit: &ValueEdit) -> Result<(), String> {
+        // A comma separated list fills a register from lane 0, or memory from the clicked address
+        let lane_size = edit.value_type.size() / 8;
+        let (texts, first_lane): (Vec<&str>, usize) = if edit.text.contains(',') {
+            (edit.text.split(',').collect(), if let EditLocation::Register(_) = edit.location { 0 } else { edit.lane })
+        } else {
+            (vec![edit.text.as_str()], edit.lane)
+        };
+        let mut bytes = vec![];
+        for text in texts.iter() {
+            match parse_value_bytes(text, edit.value_type) {
+                Some(b) => bytes.extend(b),
+                None => return Err(format!("Invalid value: {}", text.trim())),
+            }
+        }
+        let offset = first_lane * lane_size;
+        if offset.saturating_add(bytes.len()) > get_location_size(&edit.location) {
+            return Err(format!("Too many values: {}", texts.len()));
+        }
+        let before = read_bytes(cpu, &edit.location, offset, bytes.len());
+        write_bytes(cpu, &edit.location, offset, &bytes);
+        reset_origins(provenance, &edit.location, offset, bytes.len());
+        self.entries.push((edit.location, offset, before));
+        Ok(())
+    }
+    pub fn undo(&mut self, cpu: &mut CPU, provenance: &mut Provenance) -> bool {
+        if let Some((location, offset, before)) = self.entries.pop() {
+            write_bytes(cpu, &location, offset, &before);
+            reset_origins(provenance, &location, offset, before.len());
+            true
+        } else {
+            false
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+pub fn show_edit_popup(ctx: &Context, id: &str, position: Pos2, text: &mut String, error: Option<&str>) -> Option<bool> {
+    // Some(true): commit, Some(false): cancel; `error` is why the last commit was rejected
+    let mut result = None;
+    egui::Area::new(egui::Id::new(id))
+        .fixed_pos(position)
+        .order(egui::Order::Tooltip)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                let response = ui.add(TextEdit::singleline(text).desired_width(160.0).hint_text("value or v0, v1, ..."));
+                response.request_focus();
+                if let Some(error) = error {
+                    ui.colored_label(egui::Color32::from_rgb(220, 50, 50), error);
+                }
+                if ui.input(|i| i.key_pressed(Key::Enter)) {
+                    result = Some(true);
+                } else if ui.input(|i| i.key_pressed(Key::Escape)) {
+                    result = Some(false);
+                }
+            });
+        });
+    result
+}
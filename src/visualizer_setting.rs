@@ -1,6 +1,7 @@
 use eframe::egui::{Ui, Id, Sense, CursorIcon, LayerId, Order, InnerResponse, Vec2, Shape, Rect, epaint, Label, Slider, ComboBox};
 use cpulib::{VecRegName, GPRName};
 use super::*;
+use crate::theme::{Theme, ColorScheme};
 
 fn drag_source(ui: &mut Ui, id: Id, body: impl FnOnce(&mut Ui)) {
     let is_being_dragged = ui.memory(|mem| mem.is_being_dragged(id));
@@ -82,6 +83,8 @@ pub struct VisualizerSetting {
     vec_name: VecRegName,
     vec_index: usize,
     data_type: ValueType,
+    radix: Radix,
+    bit_grid: bool,
 }
 
 impl Default for VisualizerSetting {
@@ -95,6 +98,8 @@ impl Default for VisualizerSetting {
             vec_name: VecRegName::YMM,
             vec_index: 0,
             data_type: ValueType::U32,
+            radix: Radix::Decimal,
+            bit_grid: false,
         }
     }
 }
@@ -139,6 +144,21 @@ impl VisualizerSetting {
         if slider_response.changed() {
             data.max_speed = self.max_speed;
         }
+        ui.label("Appearance:");
+        ui.horizontal(|ui| {
+            ui.label("Theme:");
+            ui.radio_value(&mut data.theme, Theme::Dark, "Dark");
+            ui.radio_value(&mut data.theme, Theme::Light, "Light");
+            ui.radio_value(&mut data.theme, Theme::HighContrast, "High Contrast");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Colour:");
+            ui.radio_value(&mut data.color_scheme, ColorScheme::Uniform, "Uniform");
+            ui.radio_value(&mut data.color_scheme, ColorScheme::Register, "Source Register");
+            ui.radio_value(&mut data.color_scheme, ColorScheme::LaneGradient, "Lane Gradient");
+            ui.radio_value(&mut data.color_scheme, ColorScheme::Heatmap, "Heatmap");
+            ui.radio_value(&mut data.color_scheme, ColorScheme::Provenance, "Origin");
+        });
         ui.label("Registers Setting:");
         ui.horizontal(|ui| {
             ui.label("Type:");
@@ -194,6 +214,12 @@ impl VisualizerSetting {
                     ui.radio_value(&mut self.data_type, ValueType::F32, "F32");
                     ui.radio_value(&mut self.data_type, ValueType::U64, "U64");
                     ui.radio_value(&mut self.data_type, ValueType::F64, "F64");
+                    ui.radio_value(&mut self.data_type, ValueType::F16, "F16");
+                    ui.radio_value(&mut self.data_type, ValueType::BF16, "BF16");
+                    ui.radio_value(&mut self.data_type, ValueType::I8, "I8");
+                    ui.radio_value(&mut self.data_type, ValueType::I16, "I16");
+                    ui.radio_value(&mut self.data_type, ValueType::I32, "I32");
+                    ui.radio_value(&mut self.data_type, ValueType::I64, "I64");
                     ui.radio_value(&mut self.data_type, ValueType::U128, "U128");
                     if self.vec_name == VecRegName::YMM || self.vec_name == VecRegName::ZMM {
                         ui.radio_value(&mut self.data_type, ValueType::U256, "U256");
@@ -205,19 +231,39 @@ impl VisualizerSetting {
             }
             _ => {/*None: Do nothing, there is NO possible to run into here!*/}
         }
+        ui.horizontal(|ui| {
+            ui.label("Radix:");
+            ui.radio_value(&mut self.radix, Radix::Decimal, "Dec");
+            ui.radio_value(&mut self.radix, Radix::Hex, "Hex");
+            ui.radio_value(&mut self.radix, Radix::Binary, "Bin");
+            ui.radio_value(&mut self.radix, Radix::Char, "Char");
+            ui.checkbox(&mut self.bit_grid, "Bit Grid");
+        });
         ui.horizontal(|ui| {
             if ui.button("Add/Update").clicked() {
                 match self.reg_type {
                     RegType::GPR => {
-                        if !data.registers[0].iter().any(|r| *r == self.gpr_name) {
+                        if !data.contains(&Register::gpr(self.gpr_name)) {
                             data.registers[0].push(Register::gpr(self.gpr_name));
                         }
+                        data.registers_radix.insert(Register::gpr(self.gpr_name), self.radix);
+                        if self.bit_grid {
+                            data.bit_grid_registers.insert(Register::gpr(self.gpr_name));
+                        } else {
+                            data.bit_grid_registers.remove(&Register::gpr(self.gpr_name));
+                        }
                     }
                     RegType::Vector => {
-                        if !data.registers[0].iter().any(|r| *r == (self.vec_name, self.vec_index)) {
+                        if !data.contains(&Register::vector(self.vec_name, self.vec_index)) {
                             data.registers[0].push(Register::vector(self.vec_name, self.vec_index));
                         }
                         data.vector_regs_type.insert((self.vec_name, self.vec_index), self.data_type);
+                        data.registers_radix.insert(Register::vector(self.vec_name, self.vec_index), self.radix);
+                        if self.bit_grid {
+                            data.bit_grid_registers.insert(Register::vector(self.vec_name, self.vec_index));
+                        } else {
+                            data.bit_grid_registers.remove(&Register::vector(self.vec_name, self.vec_index));
+                        }
                     }
                     _ => {/*None: Do nothing, there is NO possible to run into here!*/}
                 }
@@ -225,18 +271,32 @@ impl VisualizerSetting {
             if ui.button("Delete").clicked() {
                 match self.reg_type {
                     RegType::GPR => {
-                        data.registers[0].retain(|r| *r != self.gpr_name);
+                        data.registers.iter_mut().for_each(|column| column.retain(|r| *r != self.gpr_name));
+                        data.registers_radix.remove(&Register::gpr(self.gpr_name));
+                        data.bit_grid_registers.remove(&Register::gpr(self.gpr_name));
                     }
                     RegType::Vector => {
-                        data.registers[0].retain(|r| *r != (self.vec_name, self.vec_index));
+                        data.registers.iter_mut().for_each(|column| column.retain(|r| *r != (self.vec_name, self.vec_index)));
                         data.vector_regs_type.remove(&(self.vec_name, self.vec_index));
+                        data.registers_radix.remove(&Register::vector(self.vec_name, self.vec_index));
+                        data.bit_grid_registers.remove(&Register::vector(self.vec_name, self.vec_index));
                     }
                     _ => {/*None: Do nothing, there is NO possible to run into here!*/}
                 }
             };
         });
         // Order
-        ui.label("Order:");
+        ui.horizontal(|ui| {
+            ui.label("Order:");
+            if ui.button("Add Column").clicked() {
+                data.registers.push(vec![]);
+            }
+            if data.registers.len() > 1 && ui.button("Remove Column").clicked() {
+                // Registers in the removed column move to the previous one
+                let column = data.registers.pop().unwrap();
+                data.registers.last_mut().unwrap().extend(column);
+            }
+        });
         let id_source = "visualizer_setting_registers";
         let mut source_col_row = None;
         let mut drop_col = None;
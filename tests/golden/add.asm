@@ -0,0 +1,51 @@
+; ADD dest, src (Intel SDM): DEST := DEST + SRC, wrapping around at the operand size.
+; ZF is set when the result is zero, so JNE after ADD jumps on a non-zero sum.
+
+case register plus register
+state rax u64 const 40
+state rbx u64 const 2
+add rax, rbx ; expect rax.u64 = [42]
+; expect rbx.u64 = [2]
+
+case register plus immediate
+state rdi u64 const 96
+add rdi, 32 ; expect rdi.u64 = [128]
+
+case wraps around
+state rcx i64 const -1
+add rcx, 2 ; expect rcx.u64 = [1]
+
+case zero result sets ZF
+state rax i64 const -2
+state rcx u64 const 0
+add rax, 2
+jne skip
+add rcx, 1
+skip:
+; expect rcx.u64 = [1]
+
+case non-zero result clears ZF
+state rax u64 const 1
+state rcx u64 const 0
+cmp rax, rax
+add rax, 2
+jne skip
+add rcx, 1
+skip:
+; expect rcx.u64 = [0] ; expect rax.u64 = [3]
+
+case memory source
+state mem 0x40000000 u64 1 const 40
+state rax u64 const 2
+state rsi u64 const 0x40000000
+add rax, [rsi] ; expect rax.u64 = [42]
+
+case memory source reads the operand size and wraps around
+state mem 0x40000000 u32 2 const 1
+state rax u64 const 0xFFFFFFFF
+state rcx u64 const 0
+add eax, [0x40000000]
+jne skip
+add rcx, 1
+skip:
+; expect rax.u64 = [0] ; expect rcx.u64 = [1]
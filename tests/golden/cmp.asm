@@ -0,0 +1,37 @@
+; CMP src1, src2 (Intel SDM): computes SRC1 - SRC2 and sets the flags, ZF := 1 when they are equal.
+; JNE label jumps when ZF = 0, so the pair drives loops.
+
+case equal registers fall through jne
+state rax u64 const 7
+state rbx u64 const 7
+state rcx u64 const 0
+cmp rax, rbx
+jne skip
+add rcx, 1
+skip:
+; expect rcx.u64 = [1]
+
+case different registers take jne
+state rax u64 const 7
+state rbx u64 const 8
+state rcx u64 const 0
+cmp rax, rbx
+jne skip
+add rcx, 1
+skip:
+; expect rcx.u64 = [0]
+
+case compare with an immediate ends a loop
+state rdi u64 const 0
+state rax u64 const 0
+loop:
+add rdi, 1
+add rax, rdi
+cmp rdi, 5
+jne loop
+; expect rdi.u64 = [5] ; expect rax.u64 = [15]
+
+case operands are only read
+state rax u64 const 3
+state rbx u64 const 4
+cmp rax, rbx ; expect rax.u64 = [3] ; expect rbx.u64 = [4]
@@ -0,0 +1,20 @@
+; LDMXCSR m32 (Intel SDM): MXCSR := m32. Rounding control is bits 14:13, exception flags bits 5:0.
+
+case the loaded value is read back by stmxcsr
+state mem 0x40000000 u32 1 const 0x7F80
+ldmxcsr [0x40000000]
+stmxcsr [0x40000010] ; expect [0x40000010].u32x1 = [32640]
+
+case round down applies to the following instructions
+state mem 0x40000000 u32 1 const 0x3F80
+state xmm1 f32 const 1
+state xmm2 f32 const -2.98023223876953125e-8
+ldmxcsr [0x40000000]
+vaddps xmm0, xmm1, xmm2 ; expect xmm0.f32 = [0.99999994, 0.99999994, 0.99999994, 0.99999994]
+
+case round up
+state mem 0x40000000 u32 1 const 0x5F80
+state xmm1 f32 const 1
+state xmm2 f32 const 1.4901161193847656e-8
+ldmxcsr [0x40000000]
+vaddps xmm0, xmm1, xmm2 ; expect xmm0.f32 = [1.0000001, 1.0000001, 1.0000001, 1.0000001]
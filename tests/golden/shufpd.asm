@@ -0,0 +1,26 @@
+; SHUFPD xmm1, xmm2, imm8 (Intel SDM, legacy SSE): DEST[63:0] := imm8[0] ? DEST[127:64] : DEST[63:0],
+; DEST[127:64] := imm8[1] ? SRC[127:64] : SRC[63:0]. Legacy encoding keeps DEST[MAXVL-1:128].
+
+case imm8 0 takes both low elements
+state xmm0 f64 iota 1 1
+state xmm1 f64 iota 10 1
+shufpd xmm0, xmm1, 0 ; expect xmm0.f64 = [1, 10]
+
+case imm8 3 takes both high elements
+state xmm0 f64 iota 1 1
+state xmm1 f64 iota 10 1
+shufpd xmm0, xmm1, 3 ; expect xmm0.f64 = [2, 11]
+
+case bits above 128 are preserved
+state ymm0 u64 iota 1 1
+state ymm1 u64 iota 10 1
+shufpd xmm0, xmm1, 1 ; expect ymm0.u64 = [2, 10, 3, 4]
+
+case swap the halves of one register
+state xmm14 f64 iota 1 1
+shufpd xmm14, xmm14, 1 ; expect xmm14.f64 = [2, 1]
+
+case memory source
+state xmm0 f64 iota 1 1
+state mem 0x40000000 f64 2 iota 10 1
+shufpd xmm0, [0x40000000], 2 ; expect xmm0.f64 = [1, 11]
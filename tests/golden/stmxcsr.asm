@@ -0,0 +1,17 @@
+; STMXCSR m32 (Intel SDM): m32 := MXCSR. The power-on value is 0x1F80: all exceptions masked, round to nearest.
+
+case default value
+stmxcsr [0x40000000] ; expect [0x40000000].u32x1 = [8064]
+
+case an inexact result sets the sticky precision flag
+state xmm1 f32 const 1
+state xmm2 f32 const 1.78813934326171875e-7
+vaddps xmm0, xmm1, xmm2
+stmxcsr [0x40000000] ; expect [0x40000000].u32x1 = [8096]
+
+case the flag stays set after exact results
+state xmm1 f32 const 1
+state xmm2 f32 const 1.78813934326171875e-7
+vaddps xmm0, xmm1, xmm2
+vaddps xmm0, xmm1, xmm1
+stmxcsr [0x40000000] ; expect [0x40000000].u32x1 = [8096]
@@ -0,0 +1,33 @@
+; VADDPH dest, src1, src2 (Intel SDM): DEST.fp16[i] := SRC1.fp16[i] + SRC2.fp16[i],
+; rounded once with MXCSR.RC. EVEX zeroes DEST[MAXVL-1:VL].
+
+case xmm, bits above 128 are zeroed
+state zmm0 u32 const 7
+state xmm1 f16 iota 1 1
+state xmm2 f16 const 0.5
+vaddph xmm0, xmm1, xmm2 ; expect xmm0.f16 = [1.5, 2.5, 3.5, 4.5, 5.5, 6.5, 7.5, 8.5]
+; expect zmm0.u128 = [0x48404780468045804480430041003E00, 0, 0, 0]
+
+case ymm
+state ymm1 f16 iota 0 1
+state ymm2 f16 iota 0 -0.25
+vaddph ymm0, ymm1, ymm2 ; expect ymm0.f16 = [0, 0.75, 1.5, 2.25, 3, 3.75, 4.5, 5.25, 6, 6.75, 7.5, 8.25, 9, 9.75, 10.5, 11.25]
+
+case zmm
+state zmm1 f16 iota 1 1
+state zmm2 f16 const -1
+vaddph zmm0, zmm1, zmm2 ; expect zmm0.f16 = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31]
+
+case ties round to the even mantissa
+state xmm1 f16 const 2048
+state xmm2 f16 const 1
+state xmm3 f16 const 3
+vaddph xmm4, xmm1, xmm2 ; expect xmm4.f16x2 = [2048, 2048, ...]
+vaddph xmm5, xmm1, xmm3 ; expect xmm5.f16x2 = [2052, 2052, ...]
+
+case overflow is infinity when rounding to nearest, the largest half toward zero
+state mem 0x40000000 u32 1 const 0x7F80
+state xmm1 f16 const 65504
+vaddph xmm2, xmm1, xmm1 ; expect xmm2.f16x2 = [inf, inf, ...]
+ldmxcsr [0x40000000]
+vaddph xmm3, xmm1, xmm1 ; expect xmm3.f16x2 = [65504, 65504, ...]
@@ -0,0 +1,62 @@
+; VADDPS dest, src1, src2 (Intel SDM): DEST[i] := SRC1[i] + SRC2[i] for every single-precision lane,
+; rounded with MXCSR.RC or the EVEX embedded rounding. VEX and EVEX zero DEST[MAXVL-1:VL].
+
+case xmm, bits above 128 are zeroed
+state zmm0 u32 const 7
+state xmm1 f32 iota 1 1
+state xmm2 f32 iota 0.5 0.25
+vaddps xmm0, xmm1, xmm2 ; expect xmm0.f32 = [1.5, 2.75, 4, 5.25]
+; expect zmm0.u128 = [0x40A8000040800000403000003FC00000, 0, 0, 0]
+
+case ymm
+state ymm1 f32 iota 1 1
+state ymm2 f32 const 0.5
+vaddps ymm0, ymm1, ymm2 ; expect ymm0.f32 = [1.5, 2.5, 3.5, 4.5, 5.5, 6.5, 7.5, 8.5]
+
+case zmm
+state zmm1 f32 iota 0 1
+state zmm2 f32 iota 0 -2
+vaddps zmm0, zmm1, zmm2 ; expect zmm0.f32 = [0, -1, -2, -3, -4, -5, -6, -7, -8, -9, -10, -11, -12, -13, -14, -15]
+
+case round to nearest even by default
+state xmm1 f32 const 1
+state xmm2 f32 const 1.78813934326171875e-7
+vaddps xmm0, xmm1, xmm2 ; expect xmm0.f32 = [1.0000002, 1.0000002, 1.0000002, 1.0000002]
+
+case MXCSR.RC = round toward zero
+state mem 0x40000000 u32 1 const 0x7F80
+state xmm1 f32 const 1
+state xmm2 f32 const 1.78813934326171875e-7
+ldmxcsr [0x40000000]
+vaddps xmm0, xmm1, xmm2 ; expect xmm0.f32 = [1.0000001, 1.0000001, 1.0000001, 1.0000001]
+
+case embedded rounding overrides MXCSR.RC
+state zmm1 f32 const 1
+state zmm2 f32 const 1.78813934326171875e-7
+vaddps zmm0, zmm1, zmm2, {rz-sae} ; expect zmm0.f32x4 = [1.0000001, 1.0000001, 1.0000001, 1.0000001]
+
+case rounding up past the largest single is an overflow
+state mem 0x40000000 u32 1 const 0x5F80
+state xmm1 f32 const 3.4028235e38
+state xmm2 f32 const 1
+ldmxcsr [0x40000000]
+vaddps xmm0, xmm1, xmm2 ; expect xmm0.f32 = [inf, inf, inf, inf]
+stmxcsr [0x40000010] ; expect [0x40000010].u32x1 = [24488]
+
+case infinity plus negative infinity is NaN
+state xmm1 f32 const inf
+state xmm2 f32 const -inf
+vaddps xmm0, xmm1, xmm2 ; expect xmm0.f32 = [nan, nan, nan, nan]
+
+case memory source, scaled index
+state mem 0x40000000 f32 16 iota 1 1
+state ymm1 f32 const 0.5
+state rsi u64 const 0x40000000
+state rax u64 const 4
+vaddps ymm0, ymm1, [rsi+rax*4] ; expect ymm0.f32 = [5.5, 6.5, 7.5, 8.5, 9.5, 10.5, 11.5, 12.5]
+
+case embedded broadcast repeats one element in every lane
+state mem 0x40000000 f32 4 iota 2.5 1
+state zmm1 f32 iota 1 1
+state rax u64 const 0x40000000
+vaddps zmm0, zmm1, dword ptr [rax]{1to16} ; expect zmm0.f32 = [3.5, 4.5, 5.5, 6.5, 7.5, 8.5, 9.5, 10.5, 11.5, 12.5, 13.5, 14.5, 15.5, 16.5, 17.5, 18.5]
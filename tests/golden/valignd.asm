@@ -0,0 +1,56 @@
+; VALIGND dest, src1, src2, imm8 (Intel SDM): temp := SRC1:SRC2 (SRC2 in the low half),
+; DEST := temp >> (32 * SHIFT) where SHIFT is imm8[1:0] for xmm, imm8[2:0] for ymm and imm8[3:0] for zmm.
+; The higher imm8 bits are ignored.
+
+case xmm
+state xmm1 u32 iota 10 1
+state xmm2 u32 iota 0 1
+valignd xmm0, xmm1, xmm2, 1 ; expect xmm0.u32 = [1, 2, 3, 10]
+
+case xmm ignores imm8[7:2]
+state xmm1 u32 iota 10 1
+state xmm2 u32 iota 0 1
+valignd xmm0, xmm1, xmm2, 6 ; expect xmm0.u32 = [2, 3, 10, 11]
+
+case ymm
+state ymm1 u32 iota 10 1
+state ymm2 u32 iota 0 1
+valignd ymm0, ymm1, ymm2, 3 ; expect ymm0.u32 = [3, 4, 5, 6, 7, 10, 11, 12]
+
+case zmm shift by 0 copies src2
+state zmm1 u32 iota 100 1
+state zmm2 u32 iota 0 1
+valignd zmm0, zmm1, zmm2, 0 ; expect zmm0.u32 = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]
+
+case zmm shift by 15
+state zmm1 u32 iota 100 1
+state zmm2 u32 iota 0 1
+valignd zmm0, zmm1, zmm2, 15 ; expect zmm0.u32 = [15, 100, 101, 102, 103, 104, 105, 106, 107, 108, 109, 110, 111, 112, 113, 114]
+
+case zmm imm8 16 wraps to a shift of 0
+state zmm1 u32 iota 100 1
+state zmm2 u32 iota 0 1
+valignd zmm0, zmm1, zmm2, 16 ; expect zmm0.u32 = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]
+
+case zmm imm8 17 wraps to a shift of 1
+state zmm1 u32 iota 100 1
+state zmm2 u32 iota 0 1
+valignd zmm0, zmm1, zmm2, 17 ; expect zmm0.u32 = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 100]
+
+case zmm imm8 255 wraps to a shift of 15
+state zmm1 u32 iota 100 1
+state zmm2 u32 iota 0 1
+valignd zmm0, zmm1, zmm2, 255 ; expect zmm0.u32 = [15, 100, 101, 102, 103, 104, 105, 106, 107, 108, 109, 110, 111, 112, 113, 114]
+
+case prefix sum demo
+state zmm0 u32 iota 1 1
+state zmm1 u32 const 0
+state zmm2 u32 const 0
+valignd zmm1, zmm0, zmm2, 15 ; expect zmm1.u32 = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]
+vpaddd zmm0, zmm0, zmm1
+valignd zmm1, zmm0, zmm2, 14
+vpaddd zmm0, zmm0, zmm1
+valignd zmm1, zmm0, zmm2, 12
+vpaddd zmm0, zmm0, zmm1
+valignd zmm1, zmm0, zmm2, 8
+vpaddd zmm0, zmm0, zmm1 ; expect zmm0.u32 = [1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 66, 78, 91, 105, 120, 136]
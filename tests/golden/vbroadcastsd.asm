@@ -0,0 +1,16 @@
+; VBROADCASTSD ymm1, xmm2 (Intel SDM): DEST[i] := SRC[63:0] for every double-precision lane.
+; VEX zeroes DEST[MAXVL-1:256].
+
+case ymm from the low element, bits above 256 are zeroed
+state zmm0 u64 const 7
+state xmm1 f64 iota 1.5 1
+vbroadcastsd ymm0, xmm1 ; expect zmm0.f64 = [1.5, 1.5, 1.5, 1.5, 0, 0, 0, 0]
+
+case source is left alone
+state xmm14 u64 iota 0xDEADBEEF 1
+vbroadcastsd ymm4, xmm14 ; expect ymm4.u64 = [3735928559, 3735928559, 3735928559, 3735928559]
+; expect xmm14.u64 = [3735928559, 3735928560]
+
+case memory source reads one element
+state mem 0x40000000 f64 4 iota 2.5 1
+vbroadcastsd ymm0, [0x40000000] ; expect ymm0.f64 = [2.5, 2.5, 2.5, 2.5]
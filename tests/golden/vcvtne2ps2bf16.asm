@@ -0,0 +1,38 @@
+; VCVTNE2PS2BF16 dest, src1, src2 (Intel SDM): the low half of DEST holds SRC2 and the high half SRC1,
+; each single converted to bfloat16 with round to nearest even, regardless of MXCSR.
+
+case xmm takes the low half from src2
+state xmm1 f32 iota 1 1
+state xmm2 f32 iota 5 1
+vcvtne2ps2bf16 xmm0, xmm1, xmm2 ; expect xmm0.bf16 = [5, 6, 7, 8, 1, 2, 3, 4]
+
+case ymm
+state ymm1 f32 iota 1 1
+state ymm2 f32 iota 9 1
+vcvtne2ps2bf16 ymm0, ymm1, ymm2 ; expect ymm0.bf16 = [9, 10, 11, 12, 13, 14, 15, 16, 1, 2, 3, 4, 5, 6, 7, 8]
+
+case zmm
+state zmm1 f32 const 2
+state zmm2 f32 const -3
+vcvtne2ps2bf16 zmm0, zmm1, zmm2 ; expect zmm0.bf16 = [-3, -3, -3, -3, -3, -3, -3, -3, -3, -3, -3, -3, -3, -3, -3, -3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2]
+
+case ties round to the even mantissa
+state xmm1 f32 const 1.00390625
+state xmm2 f32 const 1.01171875
+vcvtne2ps2bf16 xmm0, xmm1, xmm2 ; expect xmm0.bf16 = [1.015625, 1.015625, 1.015625, 1.015625, 1, 1, 1, 1]
+
+case MXCSR.RC is ignored
+state mem 0x40000000 u32 1 const 0x7F80
+state xmm1 f32 const 1.01171875
+ldmxcsr [0x40000000]
+vcvtne2ps2bf16 xmm0, xmm1, xmm1 ; expect xmm0.bf16 = [1.015625, 1.015625, 1.015625, 1.015625, 1.015625, 1.015625, 1.015625, 1.015625]
+
+case memory src2
+state xmm1 f32 iota 10 1
+state mem 0x40000000 f32 4 iota 1 1
+vcvtne2ps2bf16 xmm0, xmm1, [0x40000000] ; expect xmm0.bf16 = [1, 2, 3, 4, 10, 11, 12, 13]
+
+case denormal inputs are zero regardless of MXCSR
+state xmm1 f32 const 1e-40
+state xmm2 f32 const -1.1754942e-38
+vcvtne2ps2bf16 xmm0, xmm1, xmm2 ; expect xmm0.bf16 = [-0, -0, -0, -0, 0, 0, 0, 0]
@@ -0,0 +1,32 @@
+; VCVTPH2PS dest, src (Intel SDM): DEST.fp32[i] := SRC.fp16[i] for every destination lane, exactly.
+; VEX and EVEX zero DEST[MAXVL-1:VL].
+
+case xmm uses the low four halves, bits above 128 are zeroed
+state zmm0 u32 const 7
+state xmm1 f16 iota 1 1
+vcvtph2ps xmm0, xmm1 ; expect zmm0.f32 = [1, 2, 3, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+
+case ymm from xmm
+state xmm1 f16 iota 1 1
+vcvtph2ps ymm0, xmm1 ; expect ymm0.f32 = [1, 2, 3, 4, 5, 6, 7, 8]
+
+case zmm from ymm
+state ymm1 f16 iota 0 0.5
+vcvtph2ps zmm0, ymm1 ; expect zmm0.f32 = [0, 0.5, 1, 1.5, 2, 2.5, 3, 3.5, 4, 4.5, 5, 5.5, 6, 6.5, 7, 7.5]
+
+case largest half, infinity and the smallest subnormal are exact
+state xmm1 f16 const 65504
+state xmm2 f16 const -inf
+state xmm3 f16 const 5.9604644775390625e-8
+vcvtph2ps xmm4, xmm1 ; expect xmm4.f32 = [65504, 65504, 65504, 65504]
+vcvtph2ps xmm5, xmm2 ; expect xmm5.f32 = [-inf, -inf, -inf, -inf]
+vcvtph2ps xmm6, xmm3 ; expect xmm6.f32 = [5.9604645e-8, 5.9604645e-8, 5.9604645e-8, 5.9604645e-8]
+
+case memory source
+state mem 0x40000000 f16 8 iota 1 1
+vcvtph2ps ymm0, [0x40000000] ; expect ymm0.f32 = [1, 2, 3, 4, 5, 6, 7, 8]
+
+case a signaling NaN is quieted and sets IE
+state xmm1 u16 const 0x7C01
+vcvtph2ps xmm0, xmm1 ; expect xmm0.u32 = [0x7FC02000, 0x7FC02000, 0x7FC02000, 0x7FC02000]
+stmxcsr [0x40000000] ; expect [0x40000000].u32x1 = [8065]
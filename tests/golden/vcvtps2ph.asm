@@ -0,0 +1,57 @@
+; VCVTPS2PH dest, src, imm8 (Intel SDM): DEST.fp16[i] := SRC.fp32[i] rounded with imm8[1:0]
+; (0 nearest even, 1 down, 2 up, 3 toward zero), or with MXCSR.RC when imm8[2] is set.
+; The halves past the source lanes and DEST[MAXVL-1:VL/2] are zeroed.
+
+case xmm from xmm zeroes the high four halves
+state zmm0 u32 const 7
+state xmm1 f32 iota 1 0.5
+vcvtps2ph xmm0, xmm1, 0 ; expect xmm0.f16 = [1, 1.5, 2, 2.5, 0, 0, 0, 0]
+; expect zmm0.u128 = [0x0000000000000000410040003E003C00, 0, 0, 0]
+
+case xmm from ymm
+state ymm1 f32 iota 1 1
+vcvtps2ph xmm0, ymm1, 0 ; expect xmm0.f16 = [1, 2, 3, 4, 5, 6, 7, 8]
+
+case ymm from zmm
+state zmm1 f32 iota -8 1
+vcvtps2ph ymm0, zmm1, 0 ; expect ymm0.f16 = [-8, -7, -6, -5, -4, -3, -2, -1, 0, 1, 2, 3, 4, 5, 6, 7]
+
+case imm8[1:0] selects the rounding of a value between two halves
+state xmm1 f32 const 1.000732421875
+vcvtps2ph xmm2, xmm1, 0 ; expect xmm2.f16x4 = [1.0009765625, 1.0009765625, 1.0009765625, 1.0009765625]
+vcvtps2ph xmm3, xmm1, 1 ; expect xmm3.f16x4 = [1, 1, 1, 1]
+vcvtps2ph xmm4, xmm1, 2 ; expect xmm4.f16x4 = [1.0009765625, 1.0009765625, 1.0009765625, 1.0009765625]
+vcvtps2ph xmm5, xmm1, 3 ; expect xmm5.f16x4 = [1, 1, 1, 1]
+
+case imm8[2] uses MXCSR.RC instead
+state mem 0x40000000 u32 1 const 0x7F80
+state xmm1 f32 const 1.000732421875
+vcvtps2ph xmm2, xmm1, 4 ; expect xmm2.f16x4 = [1.0009765625, 1.0009765625, 1.0009765625, 1.0009765625]
+ldmxcsr [0x40000000]
+vcvtps2ph xmm3, xmm1, 4 ; expect xmm3.f16x4 = [1, 1, 1, 1]
+
+case overflow is infinity when rounding to nearest, the largest half toward zero
+state xmm1 f32 const 70000
+state xmm2 f32 const -70000
+vcvtps2ph xmm3, xmm1, 0 ; expect xmm3.f16x4 = [inf, inf, inf, inf]
+vcvtps2ph xmm4, xmm1, 3 ; expect xmm4.f16x4 = [65504, 65504, 65504, 65504]
+vcvtps2ph xmm5, xmm2, 1 ; expect xmm5.f16x4 = [-inf, -inf, -inf, -inf]
+vcvtps2ph xmm6, xmm2, 2 ; expect xmm6.f16x4 = [-65504, -65504, -65504, -65504]
+
+case an overflow clamped to the largest half sets OE and PE
+state xmm1 f32 const 70000
+vcvtps2ph xmm2, xmm1, 3 ; expect xmm2.f16x4 = [65504, 65504, 65504, 65504]
+stmxcsr [0x40000000] ; expect [0x40000000].u32x1 = [8104]
+
+case embedded rounding overrides imm8
+state zmm1 f32 const 1.000732421875
+vcvtps2ph ymm2, zmm1, 0, {rz-sae} ; expect ymm2.f16x4 = [1, 1, 1, 1]
+
+case MXCSR.DAZ zeroes a denormal single without DE, UE or PE
+state mem 0x40000000 u32 1 const 0x1FC0
+state xmm1 f32 const 1e-40
+vcvtps2ph xmm2, xmm1, 0
+stmxcsr [0x40000010] ; expect [0x40000010].u32x1 = [8114]
+ldmxcsr [0x40000000]
+vcvtps2ph xmm3, xmm1, 0 ; expect xmm3.f16x4 = [0, 0, 0, 0]
+stmxcsr [0x40000010] ; expect [0x40000010].u32x1 = [8128]
@@ -0,0 +1,43 @@
+; VDPBF16PS srcdest, src1, src2 (Intel SDM): for every single-precision lane i,
+; SRCDEST.fp32[i] += SRC1.bf16[2i+1] * SRC2.bf16[2i+1] + SRC1.bf16[2i] * SRC2.bf16[2i].
+
+case xmm accumulates pairs of products
+state xmm0 f32 const 1
+state xmm1 bf16 iota 1 1
+state xmm2 bf16 const 2
+vdpbf16ps xmm0, xmm1, xmm2 ; expect xmm0.f32 = [7, 15, 23, 31]
+
+case ymm
+state ymm0 f32 const 1
+state ymm1 bf16 iota 1 1
+state ymm2 bf16 const 2
+vdpbf16ps ymm0, ymm1, ymm2 ; expect ymm0.f32 = [7, 15, 23, 31, 39, 47, 55, 63]
+
+case zmm
+state zmm0 f32 const 1
+state zmm1 bf16 iota 1 1
+state zmm2 bf16 const 2
+vdpbf16ps zmm0, zmm1, zmm2 ; expect zmm0.f32 = [7, 15, 23, 31, 39, 47, 55, 63, 71, 79, 87, 95, 103, 111, 119, 127]
+
+case running twice keeps accumulating
+state xmm0 f32 const 0
+state xmm1 bf16 const 0.5
+state xmm2 bf16 const -4
+vdpbf16ps xmm0, xmm1, xmm2 ; expect xmm0.f32 = [-4, -4, -4, -4]
+vdpbf16ps xmm0, xmm1, xmm2 ; expect xmm0.f32 = [-8, -8, -8, -8]
+
+case memory src2
+state xmm0 f32 const 1
+state xmm1 bf16 iota 1 1
+state mem 0x40000000 bf16 8 const 2
+vdpbf16ps xmm0, xmm1, [0x40000000] ; expect xmm0.f32 = [7, 15, 23, 31]
+
+case denormal inputs and results are zero regardless of MXCSR
+state xmm0 f32 const 1e-40
+state xmm1 bf16 const 1
+state xmm2 bf16 const 0
+vdpbf16ps xmm0, xmm1, xmm2 ; expect xmm0.f32 = [0, 0, 0, 0]
+state xmm3 f32 const 0
+state xmm4 bf16 const 1e-39
+state xmm5 bf16 const 1e30
+vdpbf16ps xmm3, xmm4, xmm5 ; expect xmm3.f32 = [0, 0, 0, 0]
@@ -0,0 +1,16 @@
+; VEXTRACTF128 xmm, ymm, imm8 (Intel SDM): DEST[127:0] := imm8[0] ? SRC[255:128] : SRC[127:0].
+; A register destination has DEST[MAXVL-1:128] zeroed. imm8[7:1] is ignored.
+
+case low half, bits above 128 are zeroed
+state zmm0 u32 const 7
+state ymm1 u32 iota 0 1
+vextractf128 xmm0, ymm1, 0 ; expect zmm0.u32 = [0, 1, 2, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+
+case high half
+state ymm1 f64 iota 1.5 1
+vextractf128 xmm0, ymm1, 1 ; expect xmm0.f64 = [3.5, 4.5]
+
+case only imm8[0] is used
+state ymm1 u32 iota 0 1
+vextractf128 xmm0, ymm1, 2 ; expect xmm0.u32 = [0, 1, 2, 3]
+vextractf128 xmm0, ymm1, 3 ; expect xmm0.u32 = [4, 5, 6, 7]
@@ -0,0 +1,26 @@
+; VFMADD132PH dest, src2, src3 (Intel SDM): DEST[i] := DEST[i] * SRC3[i] + SRC2[i] for every half-precision lane,
+; computed with infinite precision and rounded once with MXCSR.RC. EVEX zeroes DEST[MAXVL-1:VL].
+
+case xmm
+state xmm0 f16 const 2
+state xmm1 f16 const 3
+state xmm2 f16 const 5
+vfmadd132ph xmm0, xmm1, xmm2 ; expect xmm0.f16 = [13, 13, 13, 13, 13, 13, 13, 13]
+
+case ymm picks the operands in 132 order
+state ymm0 f16 iota 0 1
+state ymm1 f16 const 3
+state ymm2 f16 const 5
+vfmadd132ph ymm0, ymm1, ymm2 ; expect ymm0.f16 = [3, 8, 13, 18, 23, 28, 33, 38, 43, 48, 53, 58, 63, 68, 73, 78]
+
+case zmm
+state zmm0 f16 const 2
+state zmm1 f16 const 3
+state zmm2 f16 const 5
+vfmadd132ph zmm0, zmm1, zmm2 ; expect zmm0.f16 = [13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13, 13]
+
+case the product is not rounded before the addition
+state xmm0 f16 const 1.0029296875
+state xmm1 f16 const -1
+state xmm2 f16 const 1.0029296875
+vfmadd132ph xmm0, xmm1, xmm2 ; expect xmm0.f16x2 = [0.00586700439453125, 0.00586700439453125, ...]
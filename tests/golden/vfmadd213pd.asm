@@ -0,0 +1,79 @@
+; VFMADD213PD dest, src2, src3 (Intel SDM): DEST[i] := SRC2[i] * DEST[i] + SRC3[i] for every double-precision lane,
+; computed with infinite precision and rounded once with MXCSR.RC. VEX and EVEX zero DEST[MAXVL-1:VL].
+
+case xmm, bits above 128 are zeroed
+state zmm0 u64 const 7
+state xmm0 f64 iota 1 1
+state xmm1 f64 iota 3 1
+state xmm2 f64 iota 10 10
+vfmadd213pd xmm0, xmm1, xmm2 ; expect zmm0.f64 = [13, 28, 0, 0, 0, 0, 0, 0]
+
+case ymm, only the destination is written
+state ymm0 f64 const 2
+state ymm1 f64 iota 1 1
+state ymm2 f64 const 0.5
+vfmadd213pd ymm0, ymm1, ymm2 ; expect ymm0.f64 = [2.5, 4.5, 6.5, 8.5]
+; expect ymm1.f64 = [1, 2, 3, 4]
+; expect ymm2.f64 = [0.5, 0.5, 0.5, 0.5]
+
+case zmm
+state zmm0 f64 iota 0 1
+state zmm1 f64 const -1
+state zmm2 f64 const 10
+vfmadd213pd zmm0, zmm1, zmm2 ; expect zmm0.f64 = [10, 9, 8, 7, 6, 5, 4, 3]
+
+case the product is not rounded before the addition
+state xmm0 f64 const 1.0000000009313226
+state xmm1 f64 const 1.0000000009313226
+state xmm2 f64 const -1
+vfmadd213pd xmm0, xmm1, xmm2 ; expect xmm0.f64 = [1.8626451500983188e-9, 1.8626451500983188e-9]
+
+case matrix multiplication demo
+state ymm0 f64 iota 16 -1
+state ymm1 f64 iota 12 -1
+state ymm2 f64 iota 8 -1
+state ymm3 f64 iota 4 -1
+state rdi u64 const 0
+state mem 0x40000000 f64 16 iota 1 1
+loop:
+vmovapd ymm15, [0x40000000 + rdi]
+vextractf128 xmm14, ymm15, 0
+vbroadcastsd ymm4, xmm14
+shufpd xmm14, xmm14, 1
+vbroadcastsd ymm5, xmm14
+vextractf128 xmm14, ymm15, 1
+vbroadcastsd ymm6, xmm14
+shufpd xmm14, xmm14, 1
+vbroadcastsd ymm7, xmm14
+vmulpd ymm7, ymm3, ymm7
+vfmadd213pd ymm6, ymm2, ymm7
+vfmadd213pd ymm5, ymm1, ymm6
+vfmadd213pd ymm4, ymm0, ymm5
+vmovapd [0x40000080 + rdi], ymm4
+add rdi, 32
+cmp rdi, 128
+jne loop
+; expect [0x40000080].f64x16 = [80, 70, 60, 50, 240, 214, 188, 162, 400, 358, 316, 274, 560, 502, 444, 386]
+; expect rdi.u64 = [128]
+
+case memory addend
+state mem 0x40000000 f64 2 iota 1 1
+state xmm0 f64 const 2
+state xmm1 f64 const 3
+vfmadd213pd xmm0, xmm1, [0x40000000] ; expect xmm0.f64 = [7, 8]
+
+case an overflow rounded toward zero is the largest double and still an overflow
+state mem 0x40000000 u32 1 const 0x7F80
+state xmm0 f64 const 1e308
+state xmm1 f64 const 10
+state xmm2 f64 const 0
+ldmxcsr [0x40000000]
+vfmadd213pd xmm0, xmm1, xmm2 ; expect xmm0.f64 = [1.7976931348623157e308, 1.7976931348623157e308]
+stmxcsr [0x40000010] ; expect [0x40000010].u32x1 = [32680]
+
+case an infinite addend is not an overflow
+state xmm0 f64 const 2
+state xmm1 f64 const 3
+state xmm2 f64 const inf
+vfmadd213pd xmm0, xmm1, xmm2 ; expect xmm0.f64 = [inf, inf]
+stmxcsr [0x40000000] ; expect [0x40000000].u32x1 = [8064]
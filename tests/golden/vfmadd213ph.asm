@@ -0,0 +1,26 @@
+; VFMADD213PH dest, src2, src3 (Intel SDM): DEST[i] := SRC2[i] * DEST[i] + SRC3[i] for every half-precision lane,
+; computed with infinite precision and rounded once with MXCSR.RC. EVEX zeroes DEST[MAXVL-1:VL].
+
+case xmm
+state xmm0 f16 const 2
+state xmm1 f16 const 3
+state xmm2 f16 const 5
+vfmadd213ph xmm0, xmm1, xmm2 ; expect xmm0.f16 = [11, 11, 11, 11, 11, 11, 11, 11]
+
+case ymm picks the operands in 213 order
+state ymm0 f16 iota 0 1
+state ymm1 f16 const 3
+state ymm2 f16 const 5
+vfmadd213ph ymm0, ymm1, ymm2 ; expect ymm0.f16 = [5, 8, 11, 14, 17, 20, 23, 26, 29, 32, 35, 38, 41, 44, 47, 50]
+
+case zmm
+state zmm0 f16 const 2
+state zmm1 f16 const 3
+state zmm2 f16 const 5
+vfmadd213ph zmm0, zmm1, zmm2 ; expect zmm0.f16 = [11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11, 11]
+
+case the product is not rounded before the addition
+state xmm0 f16 const 1.0029296875
+state xmm1 f16 const 1.0029296875
+state xmm2 f16 const -1
+vfmadd213ph xmm0, xmm1, xmm2 ; expect xmm0.f16x2 = [0.00586700439453125, 0.00586700439453125, ...]
@@ -0,0 +1,49 @@
+; VFMADD231PH dest, src2, src3 (Intel SDM): DEST[i] := SRC2[i] * SRC3[i] + DEST[i] for every half-precision lane,
+; computed with infinite precision and rounded once with MXCSR.RC. EVEX zeroes DEST[MAXVL-1:VL].
+
+case xmm
+state xmm0 f16 const 2
+state xmm1 f16 const 3
+state xmm2 f16 const 5
+vfmadd231ph xmm0, xmm1, xmm2 ; expect xmm0.f16 = [17, 17, 17, 17, 17, 17, 17, 17]
+
+case ymm picks the operands in 231 order
+state ymm0 f16 iota 0 1
+state ymm1 f16 const 3
+state ymm2 f16 const 5
+vfmadd231ph ymm0, ymm1, ymm2 ; expect ymm0.f16 = [15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30]
+
+case zmm
+state zmm0 f16 const 2
+state zmm1 f16 const 3
+state zmm2 f16 const 5
+vfmadd231ph zmm0, zmm1, zmm2 ; expect zmm0.f16 = [17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17, 17]
+
+case the product is not rounded before the addition
+state xmm0 f16 const -1
+state xmm1 f16 const 1.0029296875
+state xmm2 f16 const 1.0029296875
+vfmadd231ph xmm0, xmm1, xmm2 ; expect xmm0.f16x2 = [0.00586700439453125, 0.00586700439453125, ...]
+
+case rounded once under round up, 32 + 2^-48 rounds to 32 in f64 first
+state mem 0x40000000 u32 1 const 0x5F80
+state xmm0 f16 const 32
+state xmm1 u16 const 1
+state xmm2 u16 const 1
+ldmxcsr [0x40000000]
+vfmadd231ph xmm0, xmm1, xmm2 ; expect xmm0.f16x2 = [32.03125, 32.03125, ...]
+stmxcsr [0x40000010] ; expect [0x40000010].u32x1 = [24480]
+
+case rounded once toward zero, 64 - 2^-48 rounds to 64 in f64 first
+state mem 0x40000000 u32 1 const 0x7F80
+state xmm0 f16 const 64
+state xmm1 u16 const 1
+state xmm2 u16 const 0x8001
+ldmxcsr [0x40000000]
+vfmadd231ph xmm0, xmm1, xmm2 ; expect xmm0.f16x2 = [63.96875, 63.96875, ...]
+
+case embedded broadcast
+state mem 0x40000000 f16 2 iota 2 1
+state xmm0 f16 const 1
+state xmm1 f16 iota 1 1
+vfmadd231ph xmm0, xmm1, word ptr [0x40000000]{1to8} ; expect xmm0.f16 = [3, 5, 7, 9, 11, 13, 15, 17]
@@ -0,0 +1,34 @@
+; VMOVAPD (Intel SDM): moves a whole vector between registers or between a register and memory.
+; Register destinations have DEST[MAXVL-1:VL] zeroed.
+
+case register to register, bits above the destination are zeroed
+state zmm0 u64 const 7
+state ymm1 f64 iota 1 1
+vmovapd ymm0, ymm1 ; expect zmm0.f64 = [1, 2, 3, 4, 0, 0, 0, 0]
+
+case xmm
+state xmm1 f64 iota -1 -1
+vmovapd xmm0, xmm1 ; expect xmm0.f64 = [-1, -2]
+
+case zmm
+state zmm1 u64 iota 100 1
+vmovapd zmm0, zmm1 ; expect zmm0.u64 = [100, 101, 102, 103, 104, 105, 106, 107]
+
+case load from an absolute address
+state mem 0x40000000 f64 4 iota 1 1
+vmovapd ymm0, [0x40000000] ; expect ymm0.f64 = [1, 2, 3, 4]
+
+case load from base plus offset
+state mem 0x40000000 f64 8 iota 1 1
+state rdi u64 const 32
+vmovapd ymm0, [0x40000000 + rdi] ; expect ymm0.f64 = [5, 6, 7, 8]
+
+case store
+state ymm1 f64 iota 0.5 0.5
+vmovapd [0x40000080], ymm1 ; expect [0x40000080].f64x4 = [0.5, 1, 1.5, 2]
+; expect [0x400000A0].f64x1 = [0]
+
+case store zmm through a register
+state zmm1 u32 iota 1 1
+state rsi u64 const 0x40000100
+vmovapd [rsi], zmm1 ; expect [0x40000100].u32x16 = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]
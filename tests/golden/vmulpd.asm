@@ -0,0 +1,32 @@
+; VMULPD dest, src1, src2 (Intel SDM): DEST[i] := SRC1[i] * SRC2[i] for every double-precision lane,
+; rounded with MXCSR.RC. VEX and EVEX zero DEST[MAXVL-1:VL].
+
+case xmm, bits above 128 are zeroed
+state zmm0 u64 const 7
+state xmm1 f64 iota 1 1
+state xmm2 f64 const -0.5
+vmulpd xmm0, xmm1, xmm2 ; expect zmm0.f64 = [-0.5, -1, 0, 0, 0, 0, 0, 0]
+
+case ymm
+state ymm1 f64 iota 1 1
+state ymm2 f64 iota 2 2
+vmulpd ymm0, ymm1, ymm2 ; expect ymm0.f64 = [2, 8, 18, 32]
+
+case zmm
+state zmm1 f64 iota 0 1
+state zmm2 f64 const 0.25
+vmulpd zmm0, zmm1, zmm2 ; expect zmm0.f64 = [0, 0.25, 0.5, 0.75, 1, 1.25, 1.5, 1.75]
+
+case zero times infinity is NaN
+state ymm1 f64 const 0
+state ymm2 f64 const inf
+vmulpd ymm0, ymm1, ymm2 ; expect ymm0.f64 = [nan, nan, nan, nan]
+
+case overflow gives infinity
+state ymm1 f64 const 1e300
+vmulpd ymm0, ymm1, ymm1 ; expect ymm0.f64 = [inf, inf, inf, inf]
+
+case embedded broadcast
+state mem 0x40000000 f64 2 iota 3 1
+state ymm1 f64 iota 1 1
+vmulpd ymm0, ymm1, qword ptr [0x40000000]{1to4} ; expect ymm0.f64 = [3, 6, 9, 12]
@@ -0,0 +1,26 @@
+; VMULPH dest, src1, src2 (Intel SDM): DEST.fp16[i] := SRC1.fp16[i] * SRC2.fp16[i],
+; rounded once with MXCSR.RC. EVEX zeroes DEST[MAXVL-1:VL].
+
+case xmm
+state xmm1 f16 iota 1 1
+state xmm2 f16 const 2
+vmulph xmm0, xmm1, xmm2 ; expect xmm0.f16 = [2, 4, 6, 8, 10, 12, 14, 16]
+
+case ymm
+state ymm1 f16 iota 0 1
+state ymm2 f16 const -0.5
+vmulph ymm0, ymm1, ymm2 ; expect ymm0.f16 = [-0, -0.5, -1, -1.5, -2, -2.5, -3, -3.5, -4, -4.5, -5, -5.5, -6, -6.5, -7, -7.5]
+
+case zmm
+state zmm1 f16 iota 1 1
+state zmm2 f16 iota 1 1
+vmulph zmm0, zmm1, zmm2
+; expect zmm0.f16 = [1, 4, 9, 16, 25, 36, 49, 64, 81, 100, 121, 144, 169, 196, 225, 256, 289, 324, 361, 400, 441, 484, 529, 576, 625, 676, 729, 784, 841, 900, 961, 1024]
+
+case products past 65504 overflow
+state xmm1 f16 const 300
+vmulph xmm0, xmm1, xmm1 ; expect xmm0.f16x2 = [inf, inf, ...]
+
+case products below the smallest subnormal round to zero
+state xmm1 f16 const 0.0001220703125
+vmulph xmm0, xmm1, xmm1 ; expect xmm0.f16x2 = [0, 0, ...]
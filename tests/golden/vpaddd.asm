@@ -0,0 +1,34 @@
+; VPADDD dest, src1, src2 (Intel SDM): DEST[i] := SRC1[i] + SRC2[i] for every doubleword, wrapping around.
+; VEX and EVEX zero DEST[MAXVL-1:VL].
+
+case xmm wraps around, bits above 128 are zeroed
+state zmm0 u32 const 7
+state xmm1 u32 const 0xFFFFFFFF
+state xmm2 u32 iota 1 1
+vpaddd xmm0, xmm1, xmm2 ; expect zmm0.u32 = [0, 1, 2, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+
+case ymm
+state ymm1 u32 iota 0 1
+state ymm2 u32 iota 100 10
+vpaddd ymm0, ymm1, ymm2 ; expect ymm0.u32 = [100, 111, 122, 133, 144, 155, 166, 177]
+
+case zmm, destination is also a source
+state zmm0 u32 iota 1 1
+state zmm1 u32 const 0x80000000
+vpaddd zmm0, zmm0, zmm1 ; expect zmm0.u32 = [2147483649, 2147483650, 2147483651, 2147483652, 2147483653, 2147483654, 2147483655, 2147483656, 2147483657, 2147483658, 2147483659, 2147483660, 2147483661, 2147483662, 2147483663, 2147483664]
+
+case signed view
+state ymm1 i32 iota -4 1
+state ymm2 i32 const -1
+vpaddd ymm0, ymm1, ymm2 ; expect ymm0.i32 = [-5, -4, -3, -2, -1, 0, 1, 2]
+
+case memory source below a register
+state mem 0x40000000 u32 8 iota 10 1
+state xmm1 u32 const 1
+state rdi u64 const 0x40000010
+vpaddd xmm0, xmm1, [rdi - 8] ; expect xmm0.u32 = [13, 14, 15, 16]
+
+case embedded broadcast
+state mem 0x40000000 u32 4 iota 100 1
+state xmm1 u32 iota 1 1
+vpaddd xmm0, xmm1, dword ptr [0x40000000]{1to4} ; expect xmm0.u32 = [101, 102, 103, 104]
@@ -0,0 +1,68 @@
+; VPERM2F128 dest, src1, src2, imm8 (Intel SDM): each 128-bit half of DEST selects one of
+; SRC1[127:0], SRC1[255:128], SRC2[127:0], SRC2[255:128] with imm8[1:0] (low) and imm8[5:4] (high).
+; imm8[3] zeroes the low half and imm8[7] the high half. VEX zeroes DEST[MAXVL-1:256].
+
+case select low halves
+state zmm0 u32 const 7
+state ymm1 u32 iota 0 1
+state ymm2 u32 iota 10 1
+vperm2f128 ymm0, ymm1, ymm2, 0x20 ; expect zmm0.u32 = [0, 1, 2, 3, 10, 11, 12, 13, 0, 0, 0, 0, 0, 0, 0, 0]
+
+case select high halves
+state ymm1 u32 iota 0 1
+state ymm2 u32 iota 10 1
+vperm2f128 ymm0, ymm1, ymm2, 0x31 ; expect ymm0.u32 = [4, 5, 6, 7, 14, 15, 16, 17]
+
+case swap sources
+state ymm1 u32 iota 0 1
+state ymm2 u32 iota 10 1
+vperm2f128 ymm0, ymm1, ymm2, 0x13 ; expect ymm0.u32 = [14, 15, 16, 17, 4, 5, 6, 7]
+
+case imm8[3] zeroes the low half
+state ymm1 u32 iota 0 1
+state ymm2 u32 iota 10 1
+vperm2f128 ymm0, ymm1, ymm2, 0x08 ; expect ymm0.u32 = [0, 0, 0, 0, 0, 1, 2, 3]
+
+case imm8[7] zeroes the high half
+state ymm1 u32 iota 0 1
+state ymm2 u32 iota 10 1
+vperm2f128 ymm0, ymm1, ymm2, 0x83 ; expect ymm0.u32 = [14, 15, 16, 17, 0, 0, 0, 0]
+
+case zeroing wins over the selector
+state ymm1 u32 iota 1 1
+state ymm2 u32 iota 10 1
+vperm2f128 ymm0, ymm1, ymm2, 0xBB ; expect ymm0.u32 = [0, 0, 0, 0, 0, 0, 0, 0]
+
+case matrix transpose demo
+state ymm0 u32 iota 1 1
+state ymm1 u32 iota 9 1
+state ymm2 u32 iota 17 1
+state ymm3 u32 iota 25 1
+state ymm4 u32 iota 33 1
+state ymm5 u32 iota 41 1
+state ymm6 u32 iota 49 1
+state ymm7 u32 iota 57 1
+vunpcklps ymm8, ymm0, ymm1
+vunpcklps ymm9, ymm2, ymm3
+vunpcklps ymm10, ymm4, ymm5
+vunpcklps ymm11, ymm6, ymm7
+vunpckhps ymm12, ymm0, ymm1
+vunpckhps ymm13, ymm2, ymm3
+vunpckhps ymm14, ymm4, ymm5
+vunpckhps ymm15, ymm6, ymm7
+vshufps ymm0, ymm8, ymm9, 0b01000100
+vshufps ymm1, ymm8, ymm9, 0b11101110
+vshufps ymm2, ymm10, ymm11, 0b01000100
+vshufps ymm3, ymm10, ymm11, 0b11101110
+vshufps ymm4, ymm12, ymm13, 0b01000100
+vshufps ymm5, ymm12, ymm13, 0b11101110
+vshufps ymm6, ymm14, ymm15, 0b01000100
+vshufps ymm7, ymm14, ymm15, 0b11101110
+vperm2f128 ymm8, ymm0, ymm2, 0x20 ; expect ymm8.u32 = [1, 9, 17, 25, 33, 41, 49, 57]
+vperm2f128 ymm9, ymm1, ymm3, 0x20 ; expect ymm9.u32 = [2, 10, 18, 26, 34, 42, 50, 58]
+vperm2f128 ymm10, ymm0, ymm2, 0x31 ; expect ymm10.u32 = [5, 13, 21, 29, 37, 45, 53, 61]
+vperm2f128 ymm11, ymm1, ymm3, 0x31 ; expect ymm11.u32 = [6, 14, 22, 30, 38, 46, 54, 62]
+vperm2f128 ymm12, ymm4, ymm6, 0x20 ; expect ymm12.u32 = [3, 11, 19, 27, 35, 43, 51, 59]
+vperm2f128 ymm13, ymm5, ymm7, 0x20 ; expect ymm13.u32 = [4, 12, 20, 28, 36, 44, 52, 60]
+vperm2f128 ymm14, ymm4, ymm6, 0x31 ; expect ymm14.u32 = [7, 15, 23, 31, 39, 47, 55, 63]
+vperm2f128 ymm15, ymm5, ymm7, 0x31 ; expect ymm15.u32 = [8, 16, 24, 32, 40, 48, 56, 64]
@@ -0,0 +1,24 @@
+; VSHUFPS dest, src1, src2, imm8 (Intel SDM): in every 128-bit lane
+; DEST[0] := SRC1[imm8[1:0]], DEST[1] := SRC1[imm8[3:2]], DEST[2] := SRC2[imm8[5:4]], DEST[3] := SRC2[imm8[7:6]].
+; VEX and EVEX zero DEST[MAXVL-1:VL].
+
+case xmm, bits above 128 are zeroed
+state zmm0 u32 const 7
+state xmm1 u32 iota 0 1
+state xmm2 u32 iota 10 1
+vshufps xmm0, xmm1, xmm2, 0b00011011 ; expect zmm0.u32 = [3, 2, 11, 10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+
+case ymm uses the same selector in both lanes
+state ymm1 u32 iota 0 1
+state ymm2 u32 iota 10 1
+vshufps ymm0, ymm1, ymm2, 0b01000100 ; expect ymm0.u32 = [0, 1, 10, 11, 4, 5, 14, 15]
+vshufps ymm0, ymm1, ymm2, 0b11101110 ; expect ymm0.u32 = [2, 3, 12, 13, 6, 7, 16, 17]
+
+case zmm
+state zmm1 u32 iota 0 1
+state zmm2 u32 iota 100 1
+vshufps zmm0, zmm1, zmm2, 0xE4 ; expect zmm0.u32 = [0, 1, 102, 103, 4, 5, 106, 107, 8, 9, 110, 111, 12, 13, 114, 115]
+
+case broadcast one element per lane
+state zmm1 u32 iota 0 1
+vshufps zmm0, zmm1, zmm1, 0xFF ; expect zmm0.u32 = [3, 3, 3, 3, 7, 7, 7, 7, 11, 11, 11, 11, 15, 15, 15, 15]
@@ -0,0 +1,18 @@
+; VUNPCKHPS dest, src1, src2 (Intel SDM): in every 128-bit lane
+; DEST := SRC2[3], SRC1[3], SRC2[2], SRC1[2] (highest dword first). VEX and EVEX zero DEST[MAXVL-1:VL].
+
+case xmm, bits above 128 are zeroed
+state zmm0 u32 const 7
+state xmm1 u32 iota 0 1
+state xmm2 u32 iota 10 1
+vunpckhps xmm0, xmm1, xmm2 ; expect zmm0.u32 = [2, 12, 3, 13, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+
+case ymm works per 128-bit lane
+state ymm1 u32 iota 0 1
+state ymm2 u32 iota 10 1
+vunpckhps ymm0, ymm1, ymm2 ; expect ymm0.u32 = [2, 12, 3, 13, 6, 16, 7, 17]
+
+case zmm
+state zmm1 u32 iota 0 1
+state zmm2 u32 iota 100 1
+vunpckhps zmm0, zmm1, zmm2 ; expect zmm0.u32 = [2, 102, 3, 103, 6, 106, 7, 107, 10, 110, 11, 111, 14, 114, 15, 115]
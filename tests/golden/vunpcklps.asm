@@ -0,0 +1,28 @@
+; VUNPCKLPS dest, src1, src2 (Intel SDM): in every 128-bit lane
+; DEST := SRC2[1], SRC1[1], SRC2[0], SRC1[0] (highest dword first). VEX and EVEX zero DEST[MAXVL-1:VL].
+
+case xmm, bits above 128 are zeroed
+state zmm0 u32 const 7
+state xmm1 u32 iota 0 1
+state xmm2 u32 iota 10 1
+vunpcklps xmm0, xmm1, xmm2 ; expect zmm0.u32 = [0, 10, 1, 11, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+
+case ymm works per 128-bit lane
+state ymm1 u32 iota 0 1
+state ymm2 u32 iota 10 1
+vunpcklps ymm0, ymm1, ymm2 ; expect ymm0.u32 = [0, 10, 1, 11, 4, 14, 5, 15]
+
+case zmm
+state zmm1 u32 iota 0 1
+state zmm2 u32 iota 100 1
+vunpcklps zmm0, zmm1, zmm2 ; expect zmm0.u32 = [0, 100, 1, 101, 4, 104, 5, 105, 8, 108, 9, 109, 12, 112, 13, 113]
+
+case memory source
+state xmm1 u32 iota 1 1
+state mem 0x40000000 u32 4 iota 10 1
+vunpcklps xmm0, xmm1, [0x40000000] ; expect xmm0.u32 = [1, 10, 2, 11]
+
+case embedded broadcast
+state xmm1 u32 iota 1 1
+state mem 0x40000000 u32 4 iota 9 1
+vunpcklps xmm0, xmm1, dword ptr [0x40000000]{1to4} ; expect xmm0.u32 = [1, 9, 2, 9]
@@ -0,0 +1,11 @@
+; VZEROALL (Intel SDM): zeroes ZMM0-ZMM15 entirely, ZMM16-ZMM31 are unmodified.
+
+case zmm0 to zmm15 are cleared
+state zmm0 u32 iota 1 1
+state zmm15 f64 const -1
+vzeroall ; expect zmm0.u32 = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+; expect zmm15.f64 = [0, 0, 0, 0, 0, 0, 0, 0]
+
+case zmm16 and above are untouched
+state zmm16 u32 iota 1 1
+vzeroall ; expect zmm16.u32 = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]
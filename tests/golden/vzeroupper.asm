@@ -0,0 +1,13 @@
+; VZEROUPPER (Intel SDM): zeroes bits MAXVL-1:128 of ZMM0-ZMM15, ZMM16-ZMM31 are unmodified.
+
+case the low 128 bits are kept
+state zmm0 u32 iota 1 1
+state zmm15 u32 iota 100 1
+vzeroupper ; expect zmm0.u32 = [1, 2, 3, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+; expect zmm15.u32 = [100, 101, 102, 103, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+
+case zmm16 and above are untouched
+state zmm16 u32 iota 1 1
+state zmm31 u32 const 9
+vzeroupper ; expect zmm16.u32 = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]
+; expect zmm31.u32 = [9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9]